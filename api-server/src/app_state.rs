@@ -1,10 +1,23 @@
 use std::sync::Arc;
 
-use crate::services::{AuthService, PictureService, StatusService, UserService};
+use crate::metrics::Metrics;
+use crate::services::{
+    AuditService, AuthService, DeviceService, EventService, HealthService, HouseholdService,
+    PictureService, SessionService, StatusService, UserService, WebhookService,
+};
 
 pub struct AppState {
     pub status_service: Arc<dyn StatusService>,
     pub picture_service: Arc<dyn PictureService>,
     pub user_service: Arc<dyn UserService>,
     pub auth_service: Arc<dyn AuthService>,
+    pub device_service: Arc<dyn DeviceService>,
+    pub audit_service: Arc<dyn AuditService>,
+    pub event_service: Arc<dyn EventService>,
+    pub webhook_service: Arc<dyn WebhookService>,
+    pub session_service: Arc<dyn SessionService>,
+    pub household_service: Arc<dyn HouseholdService>,
+    pub health_service: Arc<dyn HealthService>,
+    pub metrics: Arc<Metrics>,
+    pub metrics_token: Option<String>,
 }
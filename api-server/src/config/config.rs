@@ -1,48 +1,787 @@
+use bson::Uuid;
+use serde::Deserialize;
 use std::env;
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing_subscriber::EnvFilter;
+
+// Read when no `--config` or `CONFIG_FILE` is given, the server also starts without it.
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RepositoryBackend {
+    Mongo,
+    Memory,
+}
+
+impl FromStr for RepositoryBackend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "mongo" => Ok(Self::Mongo),
+            "memory" => Ok(Self::Memory),
+            other => Err(format!("unknown repository backend {}", other)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StorageBackend {
+    Gcs,
+    S3,
+    Local,
+    Memory,
+}
+
+impl FromStr for StorageBackend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "gcs" => Ok(Self::Gcs),
+            "s3" => Ok(Self::S3),
+            "local" => Ok(Self::Local),
+            "memory" => Ok(Self::Memory),
+            other => Err(format!("unknown storage backend {}", other)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(format!("unknown log format {}", other)),
+        }
+    }
+}
+
+// What the MQTT bridge does with decision commands.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MqttDecisions {
+    Disabled,
+    // Signed with the secret of an integration device, the way devices sign their requests.
+    Signed,
+    // Trusted as they come, only when the broker ACLs restrict who publishes them.
+    Unsigned,
+}
+
+impl FromStr for MqttDecisions {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "disabled" => Ok(Self::Disabled),
+            "signed" => Ok(Self::Signed),
+            "unsigned" => Ok(Self::Unsigned),
+            other => Err(format!("unknown decision commands mode {}", other)),
+        }
+    }
+}
+
+// The config file, every value is optional and the environment overrides it.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    server: FileServer,
+    database: FileDatabase,
+    storage: FileStorage,
+    auth: FileAuth,
+    notifications: FileNotifications,
+    logging: FileLogging,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileServer {
+    bind_address: Option<SocketAddr>,
+    public_base_url: Option<String>,
+    local_ip_probe: Option<String>,
+    metrics_token: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileDatabase {
+    backend: Option<String>,
+    url: Option<String>,
+    name: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileStorage {
+    backend: Option<String>,
+    bucket_name: Option<String>,
+    credentials_path: Option<String>,
+    s3_endpoint: Option<String>,
+    s3_region: Option<String>,
+    s3_access_key_id: Option<String>,
+    s3_secret_access_key: Option<String>,
+    local_path: Option<String>,
+    url_signing_secret: Option<String>,
+    signed_url_ttl_secs: Option<u64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileAuth {
+    session_signing_secret: Option<String>,
+    access_token_ttl_secs: Option<u64>,
+    refresh_token_ttl_secs: Option<u64>,
+    oauth_state_ttl_secs: Option<u64>,
+    invitation_ttl_secs: Option<u64>,
+    device_auth_window_secs: Option<u64>,
+    admin_user_ids: Option<Vec<String>>,
+    providers: Vec<FileProvider>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileProvider {
+    name: String,
+    issuer: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    redirect_url: Option<String>,
+    scope: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileNotifications {
+    outbox_poll_interval_secs: Option<u64>,
+    outbox_max_attempts: Option<u32>,
+    outbox_ttl_secs: Option<u64>,
+    status_pending_timeout_secs: Option<u64>,
+    event_history_size: Option<usize>,
+    webhook_poll_interval_secs: Option<u64>,
+    webhook_max_attempts: Option<u32>,
+    mqtt: FileMqtt,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileMqtt {
+    host: Option<String>,
+    port: Option<u16>,
+    client_id: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    topic_prefix: Option<String>,
+    decision_commands: Option<String>,
+    home_assistant_discovery: Option<bool>,
+    home_assistant_discovery_prefix: Option<String>,
+    home_assistant_refresh_interval_secs: Option<u64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileLogging {
+    level: Option<String>,
+    format: Option<String>,
+}
+
+// Looks a variable up, `None` when it isn't set.
+type EnvSource = Box<dyn Fn(&str) -> Option<String>>;
+
+// Resolves each value from the environment, then the file, then its default, and keeps
+// every problem found so they are all reported at once.
+struct Loader {
+    problems: Vec<String>,
+    // Where the variables are read from, the process environment outside of tests.
+    env: EnvSource,
+}
+
+impl Loader {
+    fn new(env: impl Fn(&str) -> Option<String> + 'static) -> Self {
+        Self {
+            problems: Vec::new(),
+            env: Box::new(env),
+        }
+    }
+
+    // An empty variable counts as unset, compose files often declare them that way.
+    fn var(&self, name: &str) -> Option<String> {
+        (self.env)(name).filter(|value| !value.is_empty())
+    }
+
+    fn string(&self, var: &str, file: Option<String>) -> Option<String> {
+        self.var(var).or(file)
+    }
+
+    fn parse<T>(&mut self, key: &str, var: &str, file: Option<T>) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.var(var) {
+            Some(value) => match value.parse() {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    self.problems.push(format!(
+                        "{} ({}): invalid value {:?}: {}",
+                        key, var, value, e
+                    ));
+                    None
+                }
+            },
+            None => file,
+        }
+    }
+
+    // Backends are strings in the file as well, both go through `FromStr`.
+    fn parse_str<T>(&mut self, key: &str, var: &str, file: Option<String>) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let value = self.string(var, file)?;
+        match value.parse() {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.problems.push(format!("{} ({}): {}", key, var, e));
+                None
+            }
+        }
+    }
+
+    fn required(&mut self, key: &str, var: &str, value: Option<String>) -> String {
+        value.unwrap_or_else(|| {
+            self.problems.push(format!("{} ({}) is not set", key, var));
+            String::new()
+        })
+    }
+
+    fn positive<T>(&mut self, key: &str, var: &str, value: T) -> T
+    where
+        T: Copy + Default + PartialEq,
+    {
+        if value == T::default() {
+            self.problems
+                .push(format!("{} ({}) must be greater than 0", key, var));
+        }
+        value
+    }
+
+    fn url(&mut self, key: &str, var: &str, value: &str) {
+        if !value.is_empty() && !value.starts_with("http://") && !value.starts_with("https://") {
+            self.problems.push(format!(
+                "{} ({}): {:?} is not an http(s) url",
+                key, var, value
+            ));
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    pub bind_address: SocketAddr,
+    // Used to build the urls of pictures served by this server.
+    pub public_base_url: String,
+    // Address a UDP socket is pointed at to print the LAN ip at startup, nothing is sent.
+    pub local_ip_probe: Option<String>,
+    // Bearer token `/metrics` asks for, served to anyone when unset.
+    pub metrics_token: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DatabaseConfig {
+    pub backend: RepositoryBackend,
+    pub url: String,
+    pub name: String,
+}
+
+#[derive(Clone)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+    // Only read when the backend is GCS or S3.
+    pub bucket_name: String,
+    // Only read when the backend is GCS.
+    pub credentials_path: String,
+    // Only read when the backend is S3, leave the endpoint unset for AWS.
+    pub s3_endpoint: Option<String>,
+    pub s3_region: String,
+    pub s3_access_key_id: Option<String>,
+    pub s3_secret_access_key: Option<String>,
+    // Only read when the backend is local.
+    pub local_path: String,
+    // Signs local storage urls, a random one is generated at startup when unset.
+    pub url_signing_secret: Option<String>,
+    pub signed_url_ttl_secs: u64,
+}
+
+#[derive(Clone)]
+pub struct OidcProviderConfig {
+    pub name: String,
+    // Base url of the provider, `/.well-known/openid-configuration` is read under it.
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_url: String,
+    pub scope: String,
+}
+
+#[derive(Clone)]
+pub struct AuthConfig {
+    // Signs access tokens, a random one is generated at startup when unset.
+    pub session_signing_secret: Option<String>,
+    pub access_token_ttl_secs: u64,
+    // Sliding, every refresh starts it again.
+    pub refresh_token_ttl_secs: u64,
+    // How long a login may take between the authorisation url and the callback.
+    pub oauth_state_ttl_secs: u64,
+    // How long a household invitation can be redeemed.
+    pub invitation_ttl_secs: u64,
+    // How far a device clock may drift, and how long signatures are remembered.
+    pub device_auth_window_secs: u64,
+    // Users reading the whole audit log and verifying its chain.
+    pub admin_user_ids: Vec<Uuid>,
+    // The first one is used when a login doesn't name one.
+    pub providers: Vec<OidcProviderConfig>,
+}
+
+#[derive(Clone)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub topic_prefix: String,
+    pub decision_commands: MqttDecisions,
+    pub home_assistant_discovery: bool,
+    pub home_assistant_discovery_prefix: String,
+    pub home_assistant_refresh_interval_secs: u64,
+}
+
+#[derive(Clone)]
+pub struct NotificationsConfig {
+    // Delivery of decisions to controllers, a stale decision is never delivered.
+    pub outbox_poll_interval_secs: u64,
+    pub outbox_max_attempts: u32,
+    pub outbox_ttl_secs: u64,
+    // How long a picture waits for a decision before it expires.
+    pub status_pending_timeout_secs: u64,
+    // How many events are kept for streams resuming with `Last-Event-ID`.
+    pub event_history_size: usize,
+    // Webhooks are retried with a backoff from 10s up to an hour.
+    pub webhook_poll_interval_secs: u64,
+    pub webhook_max_attempts: u32,
+    // The MQTT bridge is only started when a broker host is set.
+    pub mqtt: Option<MqttConfig>,
+}
+
+#[derive(Clone, Debug)]
+pub struct LoggingConfig {
+    // Directives such as `info` or `info,api_server::workers=debug`.
+    pub level: String,
+    pub format: LogFormat,
+}
 
 // NOTE: I could use references to avoid ownership
 // however we don't need critical performance :)
 #[derive(Clone)]
 pub struct Config {
-    pub database_name: String,
-    pub bucket_name: String,
-    pub mongodb_url: String,
-    pub credentials_path: String,
-    pub http_server_address: String,
-    pub google_auth_client_id: String,
-    pub google_auth_client_secret: String,
-    pub google_auth_redirect_url: String,
-    pub google_auth_scope: String,
+    // Where the values not coming from the environment were read, if anywhere.
+    pub file: Option<PathBuf>,
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub storage: StorageConfig,
+    pub auth: AuthConfig,
+    pub notifications: NotificationsConfig,
+    pub logging: LoggingConfig,
 }
 
 impl Config {
-    pub fn new() -> Self {
-        Self {
-            database_name: Self::value_or_fallback(
-                env::var("DATABASE_NAME").ok(),
-                "default".to_string(),
+    // Layers the environment over the config file over the defaults. `path` comes from
+    // `--config`, then `CONFIG_FILE`, and `config.toml` is read when it exists.
+    pub fn load(path: Option<PathBuf>) -> Result<Self, Vec<String>> {
+        let mut loader = Loader::new(|name| env::var(name).ok());
+
+        let path = path.or_else(|| loader.var("CONFIG_FILE").map(PathBuf::from));
+        let (file, file_config) = match path {
+            Some(path) => match Self::read_file(&path) {
+                Ok(file_config) => (Some(path), file_config),
+                Err(problem) => {
+                    loader.problems.push(problem);
+                    (Some(path), FileConfig::default())
+                }
+            },
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                let path = PathBuf::from(DEFAULT_CONFIG_FILE);
+                match Self::read_file(&path) {
+                    Ok(file_config) => (Some(path), file_config),
+                    Err(problem) => {
+                        loader.problems.push(problem);
+                        (Some(path), FileConfig::default())
+                    }
+                }
+            }
+            None => (None, FileConfig::default()),
+        };
+
+        let server = Self::server(&mut loader, file_config.server);
+        let database = Self::database(&mut loader, file_config.database);
+        let storage = Self::storage(&mut loader, file_config.storage);
+        let auth = Self::auth(&mut loader, file_config.auth);
+        let notifications = Self::notifications(&mut loader, file_config.notifications);
+        let logging = Self::logging(&mut loader, file_config.logging);
+
+        if !loader.problems.is_empty() {
+            return Err(loader.problems);
+        }
+
+        Ok(Self {
+            file,
+            server,
+            database,
+            storage,
+            auth,
+            notifications,
+            logging,
+        })
+    }
+
+    fn read_file(path: &Path) -> Result<FileConfig, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("{}: can't be read: {}", path.display(), e))?;
+        toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn server(loader: &mut Loader, file: FileServer) -> ServerConfig {
+        let bind_address = Self::value_or_fallback(
+            loader.parse("server.bind_address", "BIND_ADDRESS", file.bind_address),
+            SocketAddr::from(([0, 0, 0, 0], 8080)),
+        );
+        let public_base_url = Self::value_or_fallback(
+            loader.string("PUBLIC_BASE_URL", file.public_base_url),
+            "http://localhost:8080".to_string(),
+        );
+        loader.url(
+            "server.public_base_url",
+            "PUBLIC_BASE_URL",
+            &public_base_url,
+        );
+
+        // `none` skips the probe, for hosts without a default route.
+        let local_ip_probe = Self::value_or_fallback(
+            loader.string("LOCAL_IP_PROBE", file.local_ip_probe),
+            "8.8.8.8:80".to_string(),
+        );
+        let local_ip_probe = (local_ip_probe != "none").then_some(local_ip_probe);
+
+        ServerConfig {
+            bind_address,
+            public_base_url,
+            local_ip_probe,
+            metrics_token: loader.string("METRICS_TOKEN", file.metrics_token),
+        }
+    }
+
+    fn database(loader: &mut Loader, file: FileDatabase) -> DatabaseConfig {
+        let backend = Self::value_or_fallback(
+            loader.parse_str("database.backend", "REPOSITORY_BACKEND", file.backend),
+            RepositoryBackend::Mongo,
+        );
+        let url = Self::value_or_fallback(
+            loader.string("MONGODB_URL", file.url),
+            "mongodb://localhost:27017".to_string(),
+        );
+        let name = loader.string("DATABASE_NAME", file.name);
+
+        let name = match backend {
+            RepositoryBackend::Mongo => loader.required("database.name", "DATABASE_NAME", name),
+            RepositoryBackend::Memory => name.unwrap_or_default(),
+        };
+
+        DatabaseConfig { backend, url, name }
+    }
+
+    fn storage(loader: &mut Loader, file: FileStorage) -> StorageConfig {
+        let backend = Self::value_or_fallback(
+            loader.parse_str("storage.backend", "STORAGE_BACKEND", file.backend),
+            StorageBackend::Gcs,
+        );
+        let bucket_name = loader.string("BUCKET_NAME", file.bucket_name);
+        let credentials_path = Self::value_or_fallback(
+            loader.string("CREDENTIALS_PATH", file.credentials_path),
+            "service-account.json".to_string(),
+        );
+        let s3_endpoint = loader.string("S3_ENDPOINT", file.s3_endpoint);
+        let s3_access_key_id = loader.string("S3_ACCESS_KEY_ID", file.s3_access_key_id);
+        let s3_secret_access_key = loader.string("S3_SECRET_ACCESS_KEY", file.s3_secret_access_key);
+
+        let bucket_name = match backend {
+            StorageBackend::Gcs | StorageBackend::S3 => {
+                loader.required("storage.bucket_name", "BUCKET_NAME", bucket_name)
+            }
+            StorageBackend::Local | StorageBackend::Memory => bucket_name.unwrap_or_default(),
+        };
+        if backend == StorageBackend::Gcs && !Path::new(&credentials_path).is_file() {
+            loader.problems.push(format!(
+                "storage.credentials_path (CREDENTIALS_PATH): {} doesn't exist",
+                credentials_path
+            ));
+        }
+        if backend == StorageBackend::S3 {
+            if let Some(endpoint) = &s3_endpoint {
+                loader.url("storage.s3_endpoint", "S3_ENDPOINT", endpoint);
+            }
+            // The client only signs with this pair, there's no fallback to the AWS credential
+            // chain.
+            for (key, var, value) in [
+                (
+                    "storage.s3_access_key_id",
+                    "S3_ACCESS_KEY_ID",
+                    &s3_access_key_id,
+                ),
+                (
+                    "storage.s3_secret_access_key",
+                    "S3_SECRET_ACCESS_KEY",
+                    &s3_secret_access_key,
+                ),
+            ] {
+                if value.is_none() {
+                    loader
+                        .problems
+                        .push(format!("{} ({}) is not set", key, var));
+                }
+            }
+        }
+
+        let signed_url_ttl_secs = Self::value_or_fallback(
+            loader.parse(
+                "storage.signed_url_ttl_secs",
+                "SIGNED_URL_TTL_SECS",
+                file.signed_url_ttl_secs,
+            ),
+            900,
+        );
+
+        StorageConfig {
+            backend,
+            bucket_name,
+            credentials_path,
+            s3_endpoint,
+            s3_region: Self::value_or_fallback(
+                loader.string("S3_REGION", file.s3_region),
+                "us-east-1".to_string(),
+            ),
+            s3_access_key_id,
+            s3_secret_access_key,
+            local_path: Self::value_or_fallback(
+                loader.string("LOCAL_STORAGE_PATH", file.local_path),
+                "uploads".to_string(),
+            ),
+            url_signing_secret: loader.string("URL_SIGNING_SECRET", file.url_signing_secret),
+            signed_url_ttl_secs: loader.positive(
+                "storage.signed_url_ttl_secs",
+                "SIGNED_URL_TTL_SECS",
+                signed_url_ttl_secs,
+            ),
+        }
+    }
+
+    fn auth(loader: &mut Loader, file: FileAuth) -> AuthConfig {
+        let mut duration = |key: &str, var: &str, value: Option<u64>, fallback: u64| {
+            let value = Self::value_or_fallback(loader.parse(key, var, value), fallback);
+            loader.positive(key, var, value)
+        };
+        let access_token_ttl_secs = duration(
+            "auth.access_token_ttl_secs",
+            "ACCESS_TOKEN_TTL_SECS",
+            file.access_token_ttl_secs,
+            900,
+        );
+        let refresh_token_ttl_secs = duration(
+            "auth.refresh_token_ttl_secs",
+            "REFRESH_TOKEN_TTL_SECS",
+            file.refresh_token_ttl_secs,
+            30 * 24 * 3600,
+        );
+        let oauth_state_ttl_secs = duration(
+            "auth.oauth_state_ttl_secs",
+            "OAUTH_STATE_TTL_SECS",
+            file.oauth_state_ttl_secs,
+            600,
+        );
+        let invitation_ttl_secs = duration(
+            "auth.invitation_ttl_secs",
+            "INVITATION_TTL_SECS",
+            file.invitation_ttl_secs,
+            7 * 24 * 3600,
+        );
+        let device_auth_window_secs = duration(
+            "auth.device_auth_window_secs",
+            "DEVICE_AUTH_WINDOW_SECS",
+            file.device_auth_window_secs,
+            300,
+        );
+
+        // A comma separated list in the environment.
+        let admin_user_ids = loader
+            .var("ADMIN_USER_IDS")
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|id| !id.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .or(file.admin_user_ids)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|id: String| match Uuid::parse_str(&id) {
+                Ok(id) => Some(id),
+                Err(_) => {
+                    loader.problems.push(format!(
+                        "auth.admin_user_ids (ADMIN_USER_IDS): {:?} is not a user ID",
+                        id
+                    ));
+                    None
+                }
+            })
+            .collect();
+
+        AuthConfig {
+            session_signing_secret: loader
+                .string("SESSION_SIGNING_SECRET", file.session_signing_secret),
+            access_token_ttl_secs,
+            refresh_token_ttl_secs,
+            oauth_state_ttl_secs,
+            invitation_ttl_secs,
+            device_auth_window_secs,
+            admin_user_ids,
+            providers: OidcProviderConfig::load(loader, file.providers),
+        }
+    }
+
+    fn notifications(loader: &mut Loader, file: FileNotifications) -> NotificationsConfig {
+        let outbox_poll_interval_secs = Self::value_or_fallback(
+            loader.parse(
+                "notifications.outbox_poll_interval_secs",
+                "OUTBOX_POLL_INTERVAL_SECS",
+                file.outbox_poll_interval_secs,
+            ),
+            2,
+        );
+        let outbox_max_attempts = Self::value_or_fallback(
+            loader.parse(
+                "notifications.outbox_max_attempts",
+                "OUTBOX_MAX_ATTEMPTS",
+                file.outbox_max_attempts,
+            ),
+            8,
+        );
+        let outbox_ttl_secs = Self::value_or_fallback(
+            loader.parse(
+                "notifications.outbox_ttl_secs",
+                "OUTBOX_TTL_SECS",
+                file.outbox_ttl_secs,
+            ),
+            300,
+        );
+        let status_pending_timeout_secs = Self::value_or_fallback(
+            loader.parse(
+                "notifications.status_pending_timeout_secs",
+                "STATUS_PENDING_TIMEOUT_SECS",
+                file.status_pending_timeout_secs,
+            ),
+            600,
+        );
+        let event_history_size = Self::value_or_fallback(
+            loader.parse(
+                "notifications.event_history_size",
+                "EVENT_HISTORY_SIZE",
+                file.event_history_size,
+            ),
+            1000,
+        );
+        let webhook_poll_interval_secs = Self::value_or_fallback(
+            loader.parse(
+                "notifications.webhook_poll_interval_secs",
+                "WEBHOOK_POLL_INTERVAL_SECS",
+                file.webhook_poll_interval_secs,
+            ),
+            2,
+        );
+        let webhook_max_attempts = Self::value_or_fallback(
+            loader.parse(
+                "notifications.webhook_max_attempts",
+                "WEBHOOK_MAX_ATTEMPTS",
+                file.webhook_max_attempts,
+            ),
+            10,
+        );
+
+        NotificationsConfig {
+            outbox_poll_interval_secs: loader.positive(
+                "notifications.outbox_poll_interval_secs",
+                "OUTBOX_POLL_INTERVAL_SECS",
+                outbox_poll_interval_secs,
+            ),
+            outbox_max_attempts: loader.positive(
+                "notifications.outbox_max_attempts",
+                "OUTBOX_MAX_ATTEMPTS",
+                outbox_max_attempts,
+            ),
+            outbox_ttl_secs: loader.positive(
+                "notifications.outbox_ttl_secs",
+                "OUTBOX_TTL_SECS",
+                outbox_ttl_secs,
             ),
-            bucket_name: Self::value_or_fallback(
-                env::var("BUCKET_NAME").ok(),
-                "default".to_string(),
+            status_pending_timeout_secs: loader.positive(
+                "notifications.status_pending_timeout_secs",
+                "STATUS_PENDING_TIMEOUT_SECS",
+                status_pending_timeout_secs,
             ),
-            mongodb_url: Self::value_or_fallback(
-                env::var("MONGODB_URL").ok(),
-                "mongodb://localhost:27017".to_string(),
+            event_history_size: loader.positive(
+                "notifications.event_history_size",
+                "EVENT_HISTORY_SIZE",
+                event_history_size,
             ),
-            credentials_path: Self::value_or_panic(env::var("CREDENTIALS_PATH").ok()),
-            http_server_address: Self::value_or_panic(env::var("HTTP_SERVER_ADDRESS").ok()),
-            google_auth_client_id: Self::value_or_panic(env::var("GOOGLE_AUTH_CLIENT_ID").ok()),
-            google_auth_client_secret: Self::value_or_panic(
-                env::var("GOOGLE_AUTH_CLIENT_SECRET").ok(),
+            webhook_poll_interval_secs: loader.positive(
+                "notifications.webhook_poll_interval_secs",
+                "WEBHOOK_POLL_INTERVAL_SECS",
+                webhook_poll_interval_secs,
             ),
-            google_auth_redirect_url: Self::value_or_fallback(
-                env::var("GOOGLE_AUTH_REDIRECT_URL").ok(),
-                "http://localhost:8080".to_string(),
+            webhook_max_attempts: loader.positive(
+                "notifications.webhook_max_attempts",
+                "WEBHOOK_MAX_ATTEMPTS",
+                webhook_max_attempts,
             ),
-            google_auth_scope: Self::value_or_fallback(
-                env::var("GOOGLE_AUTH_SCOPE").ok(),
-                "openid".to_string(),
+            mqtt: MqttConfig::load(loader, file.mqtt),
+        }
+    }
+
+    fn logging(loader: &mut Loader, file: FileLogging) -> LoggingConfig {
+        let level =
+            Self::value_or_fallback(loader.string("LOG_LEVEL", file.level), "info".to_string());
+        if let Err(e) = EnvFilter::try_new(&level) {
+            loader
+                .problems
+                .push(format!("logging.level (LOG_LEVEL): {:?}: {}", level, e));
+        }
+
+        LoggingConfig {
+            level,
+            format: Self::value_or_fallback(
+                loader.parse_str("logging.format", "LOG_FORMAT", file.format),
+                LogFormat::Text,
             ),
         }
     }
@@ -51,12 +790,260 @@ impl Config {
     fn value_or_fallback<T>(value: Option<T>, fallback: T) -> T {
         value.unwrap_or(fallback)
     }
+}
 
-    // When a value is necessary to avoid bugs..
-    fn value_or_panic<T>(value: Option<T>) -> T {
-        match value {
-            Some(val) => val,
-            None => panic!("panic: value mustn't be empty"),
+impl OidcProviderConfig {
+    const DEFAULT_SCOPE: &'static str = "openid email profile";
+    const DEFAULT_REDIRECT_URL: &'static str = "http://localhost:8080";
+    const GOOGLE_ISSUER: &'static str = "https://accounts.google.com";
+
+    // The providers of the file in their order, then Google when `GOOGLE_AUTH_CLIENT_ID`
+    // is set and each name of `OIDC_PROVIDERS`. Google reads the `GOOGLE_AUTH_*` variables,
+    // the others `OIDC_<NAME>_*`.
+    fn load(loader: &mut Loader, file: Vec<FileProvider>) -> Vec<Self> {
+        let mut entries: Vec<FileProvider> = Vec::new();
+        for provider in file {
+            if provider.name.is_empty() {
+                loader
+                    .problems
+                    .push("auth.providers: a provider has no name".to_string());
+            } else if entries.iter().any(|entry| entry.name == provider.name) {
+                loader.problems.push(format!(
+                    "auth.providers: {} is configured twice",
+                    provider.name
+                ));
+            } else {
+                entries.push(provider);
+            }
+        }
+
+        let mut names: Vec<String> = Vec::new();
+        if loader.var("GOOGLE_AUTH_CLIENT_ID").is_some() {
+            names.push("google".to_string());
+        }
+        names.extend(
+            loader
+                .var("OIDC_PROVIDERS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string),
+        );
+        for name in names {
+            if !entries.iter().any(|entry| entry.name == name) {
+                entries.push(FileProvider {
+                    name,
+                    ..Default::default()
+                });
+            }
+        }
+
+        if entries.is_empty() {
+            loader.problems.push(
+                "auth.providers: no identity provider, set GOOGLE_AUTH_CLIENT_ID or OIDC_PROVIDERS"
+                    .to_string(),
+            );
         }
+
+        entries
+            .into_iter()
+            .map(|entry| Self::resolve(loader, entry))
+            .collect()
+    }
+
+    fn resolve(loader: &mut Loader, entry: FileProvider) -> Self {
+        let prefix = if entry.name == "google" {
+            "GOOGLE_AUTH".to_string()
+        } else {
+            format!("OIDC_{}", entry.name.to_uppercase().replace('-', "_"))
+        };
+        let var = |suffix: &str| format!("{}_{}", prefix, suffix);
+        let key = |field: &str| format!("auth.providers.{}.{}", entry.name, field);
+
+        let issuer = loader.string(&var("ISSUER"), entry.issuer);
+        let issuer_url = match issuer {
+            Some(issuer) => issuer,
+            None if entry.name == "google" => Self::GOOGLE_ISSUER.to_string(),
+            None => loader.required(&key("issuer"), &var("ISSUER"), None),
+        };
+        loader.url(&key("issuer"), &var("ISSUER"), &issuer_url);
+
+        let client_id = loader.string(&var("CLIENT_ID"), entry.client_id);
+        let client_id = loader.required(&key("client_id"), &var("CLIENT_ID"), client_id);
+
+        let redirect_url = Config::value_or_fallback(
+            loader.string(&var("REDIRECT_URL"), entry.redirect_url),
+            Self::DEFAULT_REDIRECT_URL.to_string(),
+        );
+        loader.url(&key("redirect_url"), &var("REDIRECT_URL"), &redirect_url);
+
+        Self {
+            client_secret: loader.string(&var("CLIENT_SECRET"), entry.client_secret),
+            scope: Config::value_or_fallback(
+                loader.string(&var("SCOPE"), entry.scope),
+                Self::DEFAULT_SCOPE.to_string(),
+            ),
+            name: entry.name,
+            issuer_url,
+            client_id,
+            redirect_url,
+        }
+    }
+}
+
+impl MqttConfig {
+    fn load(loader: &mut Loader, file: FileMqtt) -> Option<Self> {
+        let host = loader.string("MQTT_HOST", file.host);
+        let port = Config::value_or_fallback(
+            loader.parse("notifications.mqtt.port", "MQTT_PORT", file.port),
+            1883,
+        );
+        let home_assistant_discovery = Config::value_or_fallback(
+            loader.parse(
+                "notifications.mqtt.home_assistant_discovery",
+                "HOME_ASSISTANT_DISCOVERY",
+                file.home_assistant_discovery,
+            ),
+            false,
+        );
+        let home_assistant_refresh_interval_secs = Config::value_or_fallback(
+            loader.parse(
+                "notifications.mqtt.home_assistant_refresh_interval_secs",
+                "HOME_ASSISTANT_REFRESH_INTERVAL_SECS",
+                file.home_assistant_refresh_interval_secs,
+            ),
+            300,
+        );
+
+        let Some(host) = host else {
+            if home_assistant_discovery {
+                loader.problems.push(
+                    "notifications.mqtt.home_assistant_discovery (HOME_ASSISTANT_DISCOVERY) needs notifications.mqtt.host (MQTT_HOST)"
+                        .to_string(),
+                );
+            }
+            return None;
+        };
+
+        Some(Self {
+            host,
+            port: loader.positive("notifications.mqtt.port", "MQTT_PORT", port),
+            client_id: Config::value_or_fallback(
+                loader.string("MQTT_CLIENT_ID", file.client_id),
+                "rusty-secure-api".to_string(),
+            ),
+            username: loader.string("MQTT_USERNAME", file.username),
+            password: loader.string("MQTT_PASSWORD", file.password),
+            topic_prefix: Config::value_or_fallback(
+                loader.string("MQTT_TOPIC_PREFIX", file.topic_prefix),
+                "rusty-secure".to_string(),
+            ),
+            decision_commands: Config::value_or_fallback(
+                loader.parse_str(
+                    "notifications.mqtt.decision_commands",
+                    "MQTT_DECISION_COMMANDS",
+                    file.decision_commands,
+                ),
+                MqttDecisions::Disabled,
+            ),
+            home_assistant_discovery,
+            home_assistant_discovery_prefix: Config::value_or_fallback(
+                loader.string(
+                    "HOME_ASSISTANT_DISCOVERY_PREFIX",
+                    file.home_assistant_discovery_prefix,
+                ),
+                "homeassistant".to_string(),
+            ),
+            home_assistant_refresh_interval_secs: loader.positive(
+                "notifications.mqtt.home_assistant_refresh_interval_secs",
+                "HOME_ASSISTANT_REFRESH_INTERVAL_SECS",
+                home_assistant_refresh_interval_secs,
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    // Reads the given variables only, whatever the process environment holds.
+    fn loader(vars: &[(&str, &str)]) -> Loader {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Loader::new(move |name| vars.get(name).cloned())
+    }
+
+    fn s3_storage(access_key_id: Option<&str>, secret_access_key: Option<&str>) -> Vec<String> {
+        let mut loader = loader(&[]);
+        let file = FileStorage {
+            backend: Some("s3".to_string()),
+            bucket_name: Some("rusty-secure".to_string()),
+            s3_access_key_id: access_key_id.map(str::to_string),
+            s3_secret_access_key: secret_access_key.map(str::to_string),
+            ..FileStorage::default()
+        };
+        Config::storage(&mut loader, file);
+        loader.problems
+    }
+
+    #[test]
+    fn s3_requires_both_keys() {
+        assert!(s3_storage(Some("key"), Some("secret")).is_empty());
+
+        let problems = s3_storage(None, None);
+        assert_eq!(problems.len(), 2);
+        let problems = s3_storage(Some("key"), None);
+        assert_eq!(
+            problems,
+            vec!["storage.s3_secret_access_key (S3_SECRET_ACCESS_KEY) is not set".to_string()]
+        );
+        let problems = s3_storage(None, Some("secret"));
+        assert_eq!(
+            problems,
+            vec!["storage.s3_access_key_id (S3_ACCESS_KEY_ID) is not set".to_string()]
+        );
+    }
+
+    #[test]
+    fn local_storage_needs_no_s3_keys() {
+        let mut loader = loader(&[]);
+        let file = FileStorage {
+            backend: Some("local".to_string()),
+            ..FileStorage::default()
+        };
+        let storage = Config::storage(&mut loader, file);
+        assert!(loader.problems.is_empty());
+        assert_eq!(storage.backend, StorageBackend::Local);
+        assert_eq!(storage.local_path, "uploads");
+    }
+
+    #[test]
+    fn environment_wins_over_the_file() {
+        let mut loader = loader(&[
+            ("STORAGE_BACKEND", "s3"),
+            ("BUCKET_NAME", "from-env"),
+            ("S3_ACCESS_KEY_ID", "key"),
+            ("S3_SECRET_ACCESS_KEY", "secret"),
+            // Declared but empty, as compose files often do.
+            ("S3_REGION", ""),
+        ]);
+        let file = FileStorage {
+            backend: Some("local".to_string()),
+            bucket_name: Some("from-file".to_string()),
+            s3_region: Some("eu-west-3".to_string()),
+            ..FileStorage::default()
+        };
+
+        let storage = Config::storage(&mut loader, file);
+        assert!(loader.problems.is_empty(), "{:?}", loader.problems);
+        assert_eq!(storage.backend, StorageBackend::S3);
+        assert_eq!(storage.bucket_name, "from-env");
+        assert_eq!(storage.s3_access_key_id.as_deref(), Some("key"));
+        assert_eq!(storage.s3_region, "eu-west-3");
     }
 }
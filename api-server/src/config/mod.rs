@@ -1,2 +1,5 @@
+#[allow(clippy::module_inception)]
 mod config;
-pub use config::Config;
+pub use config::{
+    Config, LogFormat, LoggingConfig, MqttDecisions, RepositoryBackend, StorageBackend,
+};
@@ -17,6 +17,10 @@ pub enum Error {
     Internal(String),
     Parse(String),
     JSONUnmarshall(String),
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    Conflict(String),
 }
 
 impl Display for Error {
@@ -34,6 +38,10 @@ impl Display for Error {
             Error::JSONUnmarshall(msg) => {
                 write!(f, "error trying to unmarshall json: {}", msg)
             }
+            Error::BadRequest(msg) => write!(f, "bad request: {}", msg),
+            Error::Unauthorized(msg) => write!(f, "unauthorized: {}", msg),
+            Error::Forbidden(msg) => write!(f, "forbidden: {}", msg),
+            Error::Conflict(msg) => write!(f, "conflict: {}", msg),
         }
     }
 }
@@ -46,6 +54,10 @@ impl ResponseError for Error {
             Error::NotFound(_) => StatusCode::NOT_FOUND,
             Error::UuidFormat(_) => StatusCode::BAD_REQUEST,
             Error::Empty(_) => StatusCode::BAD_REQUEST,
+            Error::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Error::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Error::Forbidden(_) => StatusCode::FORBIDDEN,
+            Error::Conflict(_) => StatusCode::CONFLICT,
             Error::WithText(_) => StatusCode::INTERNAL_SERVER_ERROR,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
@@ -53,6 +65,10 @@ impl ResponseError for Error {
 
     fn error_response(&self) -> HttpResponse {
         let status_code = self.status_code();
+        // Clients only see the message, logged here inside the span of the request.
+        if status_code.is_server_error() {
+            tracing::error!(error = %self, "request error");
+        }
         HttpResponse::build(status_code).json(json!({
             "error": self.to_string()
         }))
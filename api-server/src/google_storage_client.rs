@@ -5,17 +5,16 @@ use crate::errors::Error;
 
 pub async fn init_google_storage_client(path: String) -> Result<Client, Error> {
     if !Path::new(&path).exists() {
-        println!("Service account not found at: {}", path);
+        tracing::error!(path = %path, "Service account not found");
         return Err(Error::WithText("Service account not found".into()));
     };
 
-    let credentials_json = fs::read_to_string(path).unwrap();
+    let credentials_json = fs::read_to_string(path).map_err(|e| Error::WithText(e.to_string()))?;
+    let credentials =
+        serde_json::from_str(&credentials_json).map_err(|e| Error::WithText(e.to_string()))?;
 
-    let _ = match ClientConfig::default()
-        .with_credentials(serde_json::from_str(&credentials_json).unwrap())
-        .await
-    {
-        Ok(config) => return Ok(Client::new(config)),
-        Err(e) => return Err(Error::WithText(e.to_string())),
-    };
+    match ClientConfig::default().with_credentials(credentials).await {
+        Ok(config) => Ok(Client::new(config)),
+        Err(e) => Err(Error::WithText(e.to_string())),
+    }
 }
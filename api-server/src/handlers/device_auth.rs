@@ -0,0 +1,61 @@
+use actix_web::{web, HttpRequest};
+use bson::Uuid;
+
+use crate::app_state::AppState;
+use crate::errors::Error;
+use crate::models::{Device, DeviceType};
+
+const DEVICE_ID_HEADER: &str = "X-Device-Id";
+const TIMESTAMP_HEADER: &str = "X-Timestamp";
+const SIGNATURE_HEADER: &str = "X-Signature";
+
+fn header_value<'a>(req: &'a HttpRequest, name: &str) -> Result<&'a str, Error> {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| Error::Unauthorized(format!("missing {} header", name)))
+}
+
+// Device-facing routes are signed with the device secret instead of a user token.
+pub async fn authenticate_device(
+    req: &HttpRequest,
+    body: &[u8],
+    data: &web::Data<AppState>,
+    device_type: DeviceType,
+) -> Result<Device, Error> {
+    let authentication = async {
+        let device_id = Uuid::parse_str(header_value(req, DEVICE_ID_HEADER)?)
+            .map_err(|_| Error::Unauthorized("invalid device ID".to_string()))?;
+        let timestamp: i64 = header_value(req, TIMESTAMP_HEADER)?
+            .parse()
+            .map_err(|_| Error::Unauthorized("invalid timestamp".to_string()))?;
+        let signature = header_value(req, SIGNATURE_HEADER)?;
+
+        let device = data
+            .device_service
+            .authenticate(device_id, timestamp, signature, body)
+            .await?;
+
+        if device.device_type != device_type {
+            return Err(Error::Unauthorized(format!(
+                "device is not a {:?}",
+                device_type
+            )));
+        }
+
+        Ok(device)
+    };
+
+    authentication.await.map_err(|e| {
+        let peer = req
+            .peer_addr()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_default();
+        tracing::warn!(peer = %peer, error = %e, "Rejected device request");
+
+        match e {
+            Error::Unauthorized(_) => e,
+            _ => Error::Internal("Failed to authenticate device".to_string()),
+        }
+    })
+}
@@ -0,0 +1,130 @@
+use actix_web::{routes, web, HttpRequest, HttpResponse, Responder};
+use bson::Uuid;
+
+use super::device_auth::authenticate_device;
+use crate::app_state::AppState;
+use crate::errors::Error;
+use crate::models::{DeviceType, User};
+use crate::payloads::{
+    CallbackUpdateRequest, DeviceResponse, LinkCameraRequest, RegisterDeviceRequest,
+    RegisteredDeviceResponse, RenameDeviceRequest,
+};
+
+#[routes]
+#[post("")]
+pub async fn register_device(
+    user: web::ReqData<User>,
+    body: web::Json<RegisterDeviceRequest>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let request = body.into_inner();
+    let household_id = match request.household_id {
+        Some(household_id) => household_id,
+        None => data.household_service.ensure_personal(&user).await?.id,
+    };
+
+    let device = data
+        .device_service
+        .register(
+            user.id,
+            household_id,
+            request.name,
+            request.device_type,
+            request.camera_id,
+            request.callback_url,
+            request.capture_url,
+        )
+        .await?;
+
+    Ok(HttpResponse::Created().json(RegisteredDeviceResponse::new(device)))
+}
+
+#[routes]
+#[get("")]
+pub async fn list_devices(
+    user: web::ReqData<User>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let devices = data.device_service.list(user.id).await?;
+
+    let device_responses: Vec<DeviceResponse> =
+        devices.into_iter().map(DeviceResponse::new).collect();
+
+    Ok(HttpResponse::Ok().json(device_responses))
+}
+
+#[routes]
+#[patch("/{id}")]
+pub async fn rename_device(
+    user: web::ReqData<User>,
+    path: web::Path<String>,
+    body: web::Json<RenameDeviceRequest>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let device_id = path.into_inner();
+    let device_uuid = Uuid::parse_str(&device_id)
+        .map_err(|_| Error::UuidFormat("Invalid device ID format".to_string()))?;
+
+    let device = data
+        .device_service
+        .rename(user.id, device_uuid, body.into_inner().name)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(DeviceResponse::new(device)))
+}
+
+#[routes]
+#[put("/{id}/camera")]
+pub async fn link_camera(
+    user: web::ReqData<User>,
+    path: web::Path<String>,
+    body: web::Json<LinkCameraRequest>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let device_id = path.into_inner();
+    let device_uuid = Uuid::parse_str(&device_id)
+        .map_err(|_| Error::UuidFormat("Invalid device ID format".to_string()))?;
+
+    let device = data
+        .device_service
+        .link_camera(user.id, device_uuid, body.into_inner().camera_id)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(DeviceResponse::new(device)))
+}
+
+#[routes]
+#[delete("/{id}")]
+pub async fn revoke_device(
+    user: web::ReqData<User>,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let device_id = path.into_inner();
+    let device_uuid = Uuid::parse_str(&device_id)
+        .map_err(|_| Error::UuidFormat("Invalid device ID format".to_string()))?;
+
+    let device = data.device_service.revoke(user.id, device_uuid).await?;
+
+    Ok(HttpResponse::Ok().json(DeviceResponse::new(device)))
+}
+
+#[routes]
+#[put("/device/callback")]
+pub async fn put_device_callback(
+    req: HttpRequest,
+    body: web::Bytes,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let device = authenticate_device(&req, &body, &data, DeviceType::Esp32Main).await?;
+
+    let request: CallbackUpdateRequest =
+        serde_json::from_slice(&body).map_err(|e| Error::JSONUnmarshall(e.to_string()))?;
+
+    let device = data
+        .device_service
+        .update_callback_url(&device, request.callback_url)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(DeviceResponse::new(device)))
+}
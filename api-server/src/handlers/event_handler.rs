@@ -0,0 +1,94 @@
+use actix_web::web::Bytes;
+use actix_web::{routes, web, HttpRequest, HttpResponse, Responder};
+use bson::Uuid;
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::app_state::AppState;
+use crate::errors::Error;
+use crate::models::User;
+use crate::payloads::Event;
+
+const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+// Keeps proxies from closing an idle stream.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+fn sse_frame(event: &Event) -> Result<Bytes, actix_web::Error> {
+    let data = serde_json::to_string(event)
+        .map_err(|e| Error::Parse(format!("Failed to serialise event: {}", e)))?;
+
+    Ok(Bytes::from(format!(
+        "id: {}\nevent: {}\ndata: {}\n\n",
+        event.id,
+        event.kind.name(),
+        data
+    )))
+}
+
+#[routes]
+#[get("")]
+pub async fn get_events(
+    req: HttpRequest,
+    user: web::ReqData<User>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let user_id = user.id;
+    let last_event_id = match req.headers().get(LAST_EVENT_ID_HEADER) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .ok_or_else(|| Error::BadRequest("invalid Last-Event-ID".to_string()))?,
+        ),
+        None => None,
+    };
+
+    // NOTE: Memberships are read when the stream opens, a member removed afterwards keeps
+    // receiving events until they reconnect.
+    let household_ids: Vec<Uuid> = data
+        .household_service
+        .list(user_id)
+        .await?
+        .into_iter()
+        .map(|household| household.id)
+        .collect();
+
+    let (missed, receiver) = data
+        .event_service
+        .subscribe(user_id, &household_ids, last_event_id);
+    let household_ids = Rc::new(household_ids);
+
+    let stream = futures_util::stream::unfold(
+        (missed.into_iter(), receiver),
+        move |(mut missed, mut receiver)| {
+            let household_ids = household_ids.clone();
+            async move {
+                if let Some(event) = missed.next() {
+                    return Some((sse_frame(&event), (missed, receiver)));
+                }
+
+                loop {
+                    match actix_web::rt::time::timeout(KEEP_ALIVE_INTERVAL, receiver.recv()).await {
+                        Err(_) => {
+                            let keep_alive = Ok(Bytes::from_static(b": keep-alive\n\n"));
+                            return Some((keep_alive, (missed, receiver)));
+                        }
+                        Ok(Ok(event)) if event.is_visible_to(user_id, &household_ids) => {
+                            return Some((sse_frame(&event), (missed, receiver)));
+                        }
+                        Ok(Ok(_)) => continue,
+                        // Too slow to keep up, the client reconnects with its Last-Event-ID.
+                        Ok(Err(RecvError::Lagged(_))) | Ok(Err(RecvError::Closed)) => return None,
+                    }
+                }
+            }
+        },
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(stream))
+}
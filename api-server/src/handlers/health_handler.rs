@@ -0,0 +1,25 @@
+use actix_web::{routes, web, HttpResponse, Responder};
+use std::collections::BTreeMap;
+
+use crate::app_state::AppState;
+use crate::payloads::{HealthResponse, HealthState};
+
+// Liveness, the process answers. Nothing else is checked so an outage of a backend
+// doesn't get the server restarted.
+#[routes]
+#[get("/healthz")]
+pub async fn get_health() -> impl Responder {
+    HttpResponse::Ok().json(HealthResponse::new(BTreeMap::new()))
+}
+
+// Readiness, `503 Service Unavailable` while one of the components is down.
+#[routes]
+#[get("/readyz")]
+pub async fn get_readiness(data: web::Data<AppState>) -> impl Responder {
+    let readiness = data.health_service.readiness().await;
+
+    match readiness.status {
+        HealthState::Ok => HttpResponse::Ok().json(readiness),
+        HealthState::Unavailable => HttpResponse::ServiceUnavailable().json(readiness),
+    }
+}
@@ -0,0 +1,199 @@
+use actix_web::{routes, web, HttpResponse, Responder};
+use bson::Uuid;
+
+use crate::app_state::AppState;
+use crate::errors::Error;
+use crate::models::{HouseholdRole, User};
+use crate::payloads::{
+    ChangeRoleRequest, CreateHouseholdRequest, CreatedInvitationResponse, HouseholdResponse,
+    InvitationResponse, InviteRequest, MemberResponse, RedeemInvitationRequest,
+};
+
+fn parse_id(id: &str, kind: &str) -> Result<Uuid, Error> {
+    Uuid::parse_str(id).map_err(|_| Error::UuidFormat(format!("Invalid {} ID format", kind)))
+}
+
+#[routes]
+#[post("")]
+pub async fn create_household(
+    user: web::ReqData<User>,
+    body: web::Json<CreateHouseholdRequest>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let household = data
+        .household_service
+        .create(user.id, body.into_inner().name)
+        .await?;
+
+    Ok(HttpResponse::Created().json(HouseholdResponse::new(household, user.id)))
+}
+
+#[routes]
+#[get("")]
+pub async fn list_households(
+    user: web::ReqData<User>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let households = data.household_service.list(user.id).await?;
+
+    let household_responses: Vec<HouseholdResponse> = households
+        .into_iter()
+        .map(|household| HouseholdResponse::new(household, user.id))
+        .collect();
+
+    Ok(HttpResponse::Ok().json(household_responses))
+}
+
+#[routes]
+#[get("/{id}")]
+pub async fn get_household(
+    user: web::ReqData<User>,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let household_uuid = parse_id(&path.into_inner(), "household")?;
+
+    let household = data
+        .household_service
+        .authorize(user.id, household_uuid, HouseholdRole::Viewer)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(HouseholdResponse::new(household, user.id)))
+}
+
+#[routes]
+#[get("/{id}/members")]
+pub async fn list_members(
+    user: web::ReqData<User>,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let household_uuid = parse_id(&path.into_inner(), "household")?;
+
+    let members = data
+        .household_service
+        .members(user.id, household_uuid)
+        .await?;
+
+    let member_responses: Vec<MemberResponse> = members
+        .into_iter()
+        .map(|(member, user)| MemberResponse::new(member, user))
+        .collect();
+
+    Ok(HttpResponse::Ok().json(member_responses))
+}
+
+#[routes]
+#[put("/{id}/members/{user_id}")]
+pub async fn change_member_role(
+    user: web::ReqData<User>,
+    path: web::Path<(String, String)>,
+    body: web::Json<ChangeRoleRequest>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let (household_id, member_id) = path.into_inner();
+    let household_uuid = parse_id(&household_id, "household")?;
+    let member_uuid = parse_id(&member_id, "user")?;
+
+    let household = data
+        .household_service
+        .change_role(user.id, household_uuid, member_uuid, body.into_inner().role)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(HouseholdResponse::new(household, user.id)))
+}
+
+#[routes]
+#[delete("/{id}/members/{user_id}")]
+pub async fn remove_member(
+    user: web::ReqData<User>,
+    path: web::Path<(String, String)>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let (household_id, member_id) = path.into_inner();
+    let household_uuid = parse_id(&household_id, "household")?;
+    let member_uuid = parse_id(&member_id, "user")?;
+
+    data.household_service
+        .remove_member(user.id, household_uuid, member_uuid)
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[routes]
+#[post("/{id}/invitations")]
+pub async fn create_invitation(
+    user: web::ReqData<User>,
+    path: web::Path<String>,
+    body: web::Json<InviteRequest>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let household_uuid = parse_id(&path.into_inner(), "household")?;
+    let request = body.into_inner();
+
+    let (invitation, code) = data
+        .household_service
+        .invite(user.id, household_uuid, request.email, request.role)
+        .await?;
+
+    Ok(HttpResponse::Created().json(CreatedInvitationResponse::new(invitation, code)))
+}
+
+#[routes]
+#[get("/{id}/invitations")]
+pub async fn list_invitations(
+    user: web::ReqData<User>,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let household_uuid = parse_id(&path.into_inner(), "household")?;
+
+    let invitations = data
+        .household_service
+        .pending_invitations(user.id, household_uuid)
+        .await?;
+
+    let invitation_responses: Vec<InvitationResponse> = invitations
+        .into_iter()
+        .map(InvitationResponse::new)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(invitation_responses))
+}
+
+#[routes]
+#[delete("/{id}/invitations/{invitation_id}")]
+pub async fn revoke_invitation(
+    user: web::ReqData<User>,
+    path: web::Path<(String, String)>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let (household_id, invitation_id) = path.into_inner();
+    let household_uuid = parse_id(&household_id, "household")?;
+    let invitation_uuid = parse_id(&invitation_id, "invitation")?;
+
+    data.household_service
+        .revoke_invitation(user.id, household_uuid, invitation_uuid)
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[routes]
+#[post("/redeem")]
+pub async fn redeem_invitation(
+    user: web::ReqData<User>,
+    body: web::Json<RedeemInvitationRequest>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    // Without a provider claim at hand, only an email verified when the account was created
+    // counts. The invitation can still be redeemed by signing in with it.
+    let verified_email = user.email_verified.then_some(user.email.as_str());
+    let household = data
+        .household_service
+        .redeem(&user, verified_email, &body.into_inner().code)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(HouseholdResponse::new(household, user.id)))
+}
@@ -0,0 +1,71 @@
+use actix_web::{http::header, routes, web, HttpRequest, HttpResponse, Responder};
+use sha2::{Digest, Sha256};
+
+use crate::app_state::AppState;
+use crate::errors::Error;
+
+// Digests are compared so the time taken doesn't tell how much of the token matched.
+fn is_authorized(req: &HttpRequest, token: &str) -> bool {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| Sha256::digest(given) == Sha256::digest(token))
+}
+
+// Scraped by Prometheus, the pending statuses are counted at scrape time. The other metrics
+// are still rendered when the database can't count them.
+#[routes]
+#[get("/metrics")]
+pub async fn get_metrics(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    if let Some(token) = &data.metrics_token {
+        if !is_authorized(&req, token) {
+            return Err(Error::Unauthorized("invalid metrics token".to_string()));
+        }
+    }
+
+    let pending = match data.status_service.count_pending().await {
+        Ok(pending) => Some(pending),
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to count pending statuses");
+            None
+        }
+    };
+    data.metrics.set_pending_statuses(pending);
+
+    Ok(HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(data.metrics.render()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn only_the_metrics_token_is_accepted() {
+        let request = |value: &str| {
+            TestRequest::default()
+                .insert_header((header::AUTHORIZATION, value))
+                .to_http_request()
+        };
+
+        assert!(is_authorized(
+            &request("Bearer scrape-token"),
+            "scrape-token"
+        ));
+        assert!(!is_authorized(
+            &request("Bearer other-token"),
+            "scrape-token"
+        ));
+        assert!(!is_authorized(&request("scrape-token"), "scrape-token"));
+        assert!(!is_authorized(
+            &TestRequest::default().to_http_request(),
+            "scrape-token"
+        ));
+    }
+}
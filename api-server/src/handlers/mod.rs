@@ -1,11 +1,44 @@
 mod status_handler;
-pub use status_handler::{get_status, patch_authorised};
+pub use status_handler::{get_status, patch_status};
 
 mod picture_hander;
-pub use picture_hander::post_picture;
+pub use picture_hander::{get_all_picture, post_picture};
 
 mod user_handler;
-pub use user_handler::get_by_google_id;
+pub use user_handler::get_me;
 
 mod auth_handler;
-pub use auth_handler::{auth_url, callback};
+pub use auth_handler::{auth_providers, auth_url, callback, login, logout, refresh};
+
+mod upload_handler;
+pub use upload_handler::get_upload;
+
+mod device_auth;
+
+mod device_handler;
+pub use device_handler::{
+    link_camera, list_devices, put_device_callback, register_device, rename_device, revoke_device,
+};
+
+mod audit_handler;
+pub use audit_handler::{get_audit_entries, verify_audit_log};
+
+mod event_handler;
+pub use event_handler::get_events;
+
+mod webhook_handler;
+pub use webhook_handler::{
+    delete_webhook, list_webhook_deliveries, list_webhooks, register_webhook,
+};
+
+mod household_handler;
+pub use household_handler::{
+    change_member_role, create_household, create_invitation, get_household, list_households,
+    list_invitations, list_members, redeem_invitation, remove_member, revoke_invitation,
+};
+
+mod metrics_handler;
+pub use metrics_handler::get_metrics;
+
+mod health_handler;
+pub use health_handler::{get_health, get_readiness};
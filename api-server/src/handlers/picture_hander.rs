@@ -1,46 +1,72 @@
-use actix_web::{routes, web, HttpResponse, Responder};
+use actix_web::{routes, web, HttpRequest, HttpResponse, Responder};
 use bson::Uuid;
+use serde_json::json;
 
+use super::device_auth::authenticate_device;
 use crate::app_state::AppState;
 use crate::errors::Error;
+use crate::models::{AuditAction, AuditContext, AuditEvent, DeviceType, User};
 
 #[routes]
-#[post("/picture/{user_id}")]
+#[post("/picture")]
 pub async fn post_picture(
-    path: web::Path<String>,
+    req: HttpRequest,
     body: web::Bytes,
     data: web::Data<AppState>,
 ) -> Result<impl Responder, Error> {
-    let image_data = body.to_vec();
-    let user_id = path.into_inner();
-    let user_uuid = Uuid::parse_str(user_id).map_err(|e| Error::Internal(e.to_string()))?;
+    let device = authenticate_device(&req, &body, &data, DeviceType::Esp32Cam).await?;
 
     let status_response = data
         .picture_service
-        .upload_and_register_picture(user_uuid, image_data)
+        .upload_and_register_picture(device.user_id, device.id, body.to_vec())
         .await
         .map_err(|_| Error::Internal("Failed to register or upload picture".to_string()))?;
 
+    let event = AuditEvent {
+        action: AuditAction::PictureUploaded,
+        context: AuditContext::new(
+            None,
+            Some(device.id),
+            req.peer_addr().map(|addr| addr.ip().to_string()),
+        ),
+        household_id: device.household_id,
+        target: Some(status_response.id.to_string()),
+        before: None,
+        after: Some(json!({
+            "picture_id": status_response.picture.id.to_string(),
+            "state": status_response.state,
+        })),
+    };
+    if let Err(e) = data.audit_service.record(event).await {
+        tracing::warn!(status_id = %status_response.id, error = %e, "Failed to record audit entry for upload");
+    }
+
     // NOTE: This is for testing, this request should be used when someone review
     // if the person on the picture is recognised to then authorised and sent it
-    let _ok = data
-        .status_service
-        .send_status(status_response.id)
-        .await
-        .map_err(|_| "Failed in send to esp 32".to_string());
+    if let Err(e) = data.status_service.send_status(status_response.id).await {
+        tracing::warn!(status_id = %status_response.id, error = %e, "Failed to queue status");
+    }
 
     Ok(HttpResponse::Ok().json(status_response))
 }
 
 #[routes]
-#[get("/api/picture/{user_id}")]
+#[get("/{user_id}")]
 pub async fn get_all_picture(
+    user: web::ReqData<User>,
     path: web::Path<String>,
     data: web::Data<AppState>,
 ) -> Result<impl Responder, Error> {
     let user_id = path.into_inner();
     let user_uuid = Uuid::parse_str(user_id).map_err(|e| Error::Internal(e.to_string()))?;
 
+    // What a user sees depends on their households, nobody lists it for them.
+    if user_uuid != user.id {
+        return Err(Error::Forbidden(
+            "pictures can only be listed by their user".to_string(),
+        ));
+    }
+
     let pictures = data
         .picture_service
         .get_all(user_uuid)
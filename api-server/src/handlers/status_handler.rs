@@ -1,20 +1,26 @@
-use actix_web::{routes, web, HttpResponse, Responder};
+use actix_web::{routes, web, HttpRequest, HttpResponse, Responder};
 use bson::Uuid;
 
 use crate::app_state::AppState;
 use crate::errors::Error;
-use crate::payloads::AuthorisedPatchRequest;
+use crate::models::{AuditContext, HouseholdRole, User};
+use crate::payloads::DecisionRequest;
 
 #[routes]
-#[get("/status/{id}")]
+#[get("/{id}")]
 pub async fn get_status(
     path: web::Path<String>,
+    user: web::ReqData<User>,
     data: web::Data<AppState>,
 ) -> Result<impl Responder, Error> {
     let status_id = path.into_inner();
     let status_uuid = Uuid::parse_str(&status_id)
         .map_err(|_| Error::UuidFormat("Invalid status ID format".to_string()))?;
 
+    data.household_service
+        .authorize_status(user.id, status_uuid, HouseholdRole::Viewer)
+        .await?;
+
     let status_response = data
         .status_service
         .get_status_details(status_uuid)
@@ -25,27 +31,34 @@ pub async fn get_status(
 }
 
 #[routes]
-#[patch("/status/{id}")]
-pub async fn patch_authorised(
-    body: web::Json<AuthorisedPatchRequest>,
+#[patch("/{id}")]
+pub async fn patch_status(
+    req: HttpRequest,
+    body: web::Json<DecisionRequest>,
     path: web::Path<String>,
+    user: web::ReqData<User>,
     data: web::Data<AppState>,
 ) -> Result<impl Responder, Error> {
     let status_id = path.into_inner();
     let status_uuid = Uuid::parse_str(&status_id)
         .map_err(|_| Error::UuidFormat("Invalid status ID format".to_string()))?;
 
-    let authorised = body.authorised;
+    // Viewers only see the history.
+    data.household_service
+        .authorize_status(user.id, status_uuid, HouseholdRole::Approver)
+        .await?;
+
+    let decision = body.into_inner();
+    let context = AuditContext::new(
+        Some(user.id),
+        None,
+        req.peer_addr().map(|addr| addr.ip().to_string()),
+    );
 
     let status_response = data
         .status_service
-        .update_authorisation(status_uuid, authorised)
-        .await
-        .map_err(|_| {
-            Error::Internal(
-                "Something went wrong trying to update status authorisation".to_string(),
-            )
-        })?;
+        .decide(status_uuid, context, decision.state, decision.reason)
+        .await?;
 
     Ok(HttpResponse::Ok().json(status_response))
 }
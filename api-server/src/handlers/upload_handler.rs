@@ -0,0 +1,26 @@
+use actix_web::{routes, web, HttpResponse, Responder};
+use serde::Deserialize;
+
+use crate::errors::Error;
+use crate::repositories::{LocalStorageRepository, StorageRepository};
+
+#[derive(Deserialize)]
+pub struct SignedUrlQuery {
+    pub expires: i64,
+    pub signature: String,
+}
+
+#[routes]
+#[get("/api/uploads/{name}")]
+pub async fn get_upload(
+    path: web::Path<String>,
+    query: web::Query<SignedUrlQuery>,
+    storage: web::Data<LocalStorageRepository>,
+) -> Result<impl Responder, Error> {
+    let name = path.into_inner();
+    storage.verify_signature(&name, query.expires, &query.signature)?;
+
+    let data = storage.download(&name).await?;
+
+    Ok(HttpResponse::Ok().content_type("image/jpeg").body(data))
+}
@@ -0,0 +1,80 @@
+use actix_web::{routes, web, HttpResponse, Responder};
+use bson::Uuid;
+
+use crate::app_state::AppState;
+use crate::errors::Error;
+use crate::models::User;
+use crate::payloads::{
+    RegisterWebhookRequest, RegisteredWebhookResponse, WebhookDeliveryResponse, WebhookResponse,
+};
+
+#[routes]
+#[post("")]
+pub async fn register_webhook(
+    user: web::ReqData<User>,
+    body: web::Json<RegisterWebhookRequest>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let request = body.into_inner();
+
+    let webhook = data
+        .webhook_service
+        .register(user.id, request.url, request.events)
+        .await?;
+
+    Ok(HttpResponse::Created().json(RegisteredWebhookResponse::new(webhook)))
+}
+
+#[routes]
+#[get("")]
+pub async fn list_webhooks(
+    user: web::ReqData<User>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let webhooks = data.webhook_service.list(user.id).await?;
+
+    let webhook_responses: Vec<WebhookResponse> =
+        webhooks.into_iter().map(WebhookResponse::new).collect();
+
+    Ok(HttpResponse::Ok().json(webhook_responses))
+}
+
+#[routes]
+#[delete("/{id}")]
+pub async fn delete_webhook(
+    user: web::ReqData<User>,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let webhook_id = path.into_inner();
+    let webhook_uuid = Uuid::parse_str(&webhook_id)
+        .map_err(|_| Error::UuidFormat("Invalid webhook ID format".to_string()))?;
+
+    data.webhook_service.delete(user.id, webhook_uuid).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[routes]
+#[get("/{id}/deliveries")]
+pub async fn list_webhook_deliveries(
+    user: web::ReqData<User>,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, Error> {
+    let webhook_id = path.into_inner();
+    let webhook_uuid = Uuid::parse_str(&webhook_id)
+        .map_err(|_| Error::UuidFormat("Invalid webhook ID format".to_string()))?;
+
+    let deliveries = data
+        .webhook_service
+        .deliveries(user.id, webhook_uuid)
+        .await?;
+
+    let delivery_responses: Vec<WebhookDeliveryResponse> = deliveries
+        .into_iter()
+        .map(WebhookDeliveryResponse::new)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(delivery_responses))
+}
@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+// A worker missing this many beats in a row is reported as stalled.
+const MISSED_BEATS: u32 = 3;
+
+struct WorkerState {
+    interval: Duration,
+    // Registration time until the first beat.
+    last_beat: Instant,
+    // Depends on something outside of our control, it doesn't make the server unready.
+    optional: bool,
+    started: bool,
+    last_error: Option<String>,
+}
+
+// Liveness of the background workers, each one beating at least every `interval` while
+// it runs.
+#[derive(Default)]
+pub struct Heartbeats {
+    workers: RwLock<BTreeMap<&'static str, WorkerState>>,
+}
+
+impl Heartbeats {
+    pub fn register(self: &Arc<Self>, name: &'static str, interval: Duration) -> Heartbeat {
+        self.insert(name, interval, false)
+    }
+
+    // Same as `register`, for a worker reported by the readiness probe without failing it.
+    pub fn register_optional(
+        self: &Arc<Self>,
+        name: &'static str,
+        interval: Duration,
+    ) -> Heartbeat {
+        self.insert(name, interval, true)
+    }
+
+    fn insert(
+        self: &Arc<Self>,
+        name: &'static str,
+        interval: Duration,
+        optional: bool,
+    ) -> Heartbeat {
+        if let Ok(mut workers) = self.workers.write() {
+            workers.insert(
+                name,
+                WorkerState {
+                    interval,
+                    last_beat: Instant::now(),
+                    optional,
+                    started: false,
+                    last_error: None,
+                },
+            );
+        }
+
+        Heartbeat {
+            name,
+            heartbeats: self.clone(),
+        }
+    }
+
+    // Every registered worker, whether it's optional and the reason it isn't healthy, if any.
+    pub fn check(&self) -> Vec<(&'static str, bool, Result<(), String>)> {
+        let Ok(workers) = self.workers.read() else {
+            return Vec::new();
+        };
+
+        workers
+            .iter()
+            .map(|(name, state)| {
+                let silence = state.last_beat.elapsed();
+                let result = if silence > state.interval * MISSED_BEATS {
+                    Err(format!("no heartbeat for {}s", silence.as_secs()))
+                } else if let Some(error) = &state.last_error {
+                    Err(error.clone())
+                } else if !state.started {
+                    Err("not started yet".to_string())
+                } else {
+                    Ok(())
+                };
+                (*name, state.optional, result)
+            })
+            .collect()
+    }
+
+    fn update(&self, name: &'static str, last_error: Option<String>) {
+        if let Ok(mut workers) = self.workers.write() {
+            if let Some(state) = workers.get_mut(name) {
+                state.last_beat = Instant::now();
+                state.started = true;
+                state.last_error = last_error;
+            }
+        }
+    }
+}
+
+// Handed to a worker to report that it's still running.
+pub struct Heartbeat {
+    name: &'static str,
+    heartbeats: Arc<Heartbeats>,
+}
+
+impl Heartbeat {
+    pub fn beat(&self) {
+        self.heartbeats.update(self.name, None);
+    }
+
+    // Still running, but its last iteration failed.
+    pub fn fail(&self, error: impl ToString) {
+        self.heartbeats.update(self.name, Some(error.to_string()));
+    }
+}
@@ -0,0 +1,213 @@
+use prometheus::{
+    Encoder, Gauge, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder,
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::errors::Error;
+
+const NAMESPACE: &str = "rusty_secure";
+
+// From a door opened right away to a picture left until it expires.
+const DECISION_BUCKETS: [f64; 10] = [
+    1.0, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0, 3600.0,
+];
+const BACKEND_BUCKETS: [f64; 11] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0,
+];
+
+const UPLOAD_RESULTS: [&str; 2] = ["success", "error"];
+const DECISION_STATES: [&str; 4] = ["approved", "denied", "cancelled", "expired"];
+const DELIVERY_RESULTS: [&str; 4] = ["delivered", "retried", "failed", "expired"];
+
+// Everything `GET /metrics` exposes, in the Prometheus text format.
+pub struct Metrics {
+    registry: Registry,
+    http_request_duration: HistogramVec,
+    uploads: IntCounterVec,
+    stored_bytes: IntCounter,
+    decisions: IntCounterVec,
+    time_to_decision: HistogramVec,
+    deliveries: IntCounterVec,
+    last_delivery: Gauge,
+    pending_statuses: IntGauge,
+    backend_call_duration: HistogramVec,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, Error> {
+        let registry = Registry::new_custom(Some(NAMESPACE.to_string()), None)
+            .map_err(|e| Error::Internal(e.to_string()))?;
+
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time to handle a request, by route pattern",
+            ),
+            &["method", "route", "status"],
+        )
+        .map_err(|e| Error::Internal(e.to_string()))?;
+        let uploads = IntCounterVec::new(
+            Opts::new("uploads_total", "Pictures uploaded by cameras, by result"),
+            &["result"],
+        )
+        .map_err(|e| Error::Internal(e.to_string()))?;
+        let stored_bytes = IntCounter::new(
+            "stored_bytes_total",
+            "Bytes of pictures written to the storage",
+        )
+        .map_err(|e| Error::Internal(e.to_string()))?;
+        let decisions = IntCounterVec::new(
+            Opts::new("decisions_total", "Statuses leaving Pending, by new state"),
+            &["state"],
+        )
+        .map_err(|e| Error::Internal(e.to_string()))?;
+        let time_to_decision = HistogramVec::new(
+            HistogramOpts::new(
+                "time_to_decision_seconds",
+                "Time between a picture and the decision on its status, by state",
+            )
+            .buckets(DECISION_BUCKETS.to_vec()),
+            &["state"],
+        )
+        .map_err(|e| Error::Internal(e.to_string()))?;
+        let deliveries = IntCounterVec::new(
+            Opts::new(
+                "controller_deliveries_total",
+                "Attempts to deliver a decision to a controller, by result",
+            ),
+            &["result"],
+        )
+        .map_err(|e| Error::Internal(e.to_string()))?;
+        let last_delivery = Gauge::new(
+            "controller_last_delivery_timestamp_seconds",
+            "Unix time of the last decision a controller accepted",
+        )
+        .map_err(|e| Error::Internal(e.to_string()))?;
+        let pending_statuses = IntGauge::new(
+            "pending_statuses",
+            "Statuses waiting for a decision, as of the scrape, -1 when they couldn't be counted",
+        )
+        .map_err(|e| Error::Internal(e.to_string()))?;
+        let backend_call_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "backend_call_duration_seconds",
+                "Time of each repository and storage call, by backend and operation",
+            )
+            .buckets(BACKEND_BUCKETS.to_vec()),
+            &["backend", "operation", "result"],
+        )
+        .map_err(|e| Error::Internal(e.to_string()))?;
+
+        let metrics = Self {
+            registry,
+            http_request_duration,
+            uploads,
+            stored_bytes,
+            decisions,
+            time_to_decision,
+            deliveries,
+            last_delivery,
+            pending_statuses,
+            backend_call_duration,
+        };
+        metrics.register()?;
+
+        Ok(metrics)
+    }
+
+    fn register(&self) -> Result<(), Error> {
+        let collectors: [Box<dyn prometheus::core::Collector>; 9] = [
+            Box::new(self.http_request_duration.clone()),
+            Box::new(self.uploads.clone()),
+            Box::new(self.stored_bytes.clone()),
+            Box::new(self.decisions.clone()),
+            Box::new(self.time_to_decision.clone()),
+            Box::new(self.deliveries.clone()),
+            Box::new(self.last_delivery.clone()),
+            Box::new(self.pending_statuses.clone()),
+            Box::new(self.backend_call_duration.clone()),
+        ];
+        for collector in collectors {
+            self.registry
+                .register(collector)
+                .map_err(|e| Error::Internal(e.to_string()))?;
+        }
+
+        // Exposed at zero from the start, so rates and alerts on them work before the
+        // first event.
+        for result in UPLOAD_RESULTS {
+            self.uploads.with_label_values(&[result]);
+        }
+        for state in DECISION_STATES {
+            self.decisions.with_label_values(&[state]);
+        }
+        for result in DELIVERY_RESULTS {
+            self.deliveries.with_label_values(&[result]);
+        }
+
+        Ok(())
+    }
+
+    // `route` is the pattern the request matched, IDs would make a series per request.
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        self.http_request_duration
+            .with_label_values(&[method, route, &status.to_string()])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn record_upload(&self, stored_bytes: Option<usize>) {
+        match stored_bytes {
+            Some(bytes) => {
+                self.uploads.with_label_values(&["success"]).inc();
+                self.stored_bytes.inc_by(bytes as u64);
+            }
+            None => self.uploads.with_label_values(&["error"]).inc(),
+        }
+    }
+
+    pub fn record_decision(&self, state: &str, waited: Duration) {
+        self.decisions.with_label_values(&[state]).inc();
+        self.time_to_decision
+            .with_label_values(&[state])
+            .observe(waited.as_secs_f64());
+    }
+
+    // One of `delivered`, `retried`, `failed` once out of attempts, or `expired`.
+    pub fn record_deliveries(&self, result: &str, count: u64) {
+        self.deliveries.with_label_values(&[result]).inc_by(count);
+        if result == "delivered" {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            self.last_delivery.set(now.as_secs_f64());
+        }
+    }
+
+    pub fn set_pending_statuses(&self, count: Option<u64>) {
+        self.pending_statuses
+            .set(count.map_or(-1, |count| count as i64));
+    }
+
+    pub fn observe_backend_call(
+        &self,
+        backend: &str,
+        operation: &str,
+        success: bool,
+        elapsed: Duration,
+    ) {
+        let result = if success { "success" } else { "error" };
+        self.backend_call_duration
+            .with_label_values(&[backend, operation, result])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn render(&self) -> Result<String, Error> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(|e| Error::Internal(e.to_string()))?;
+
+        String::from_utf8(buffer).map_err(|e| Error::Internal(e.to_string()))
+    }
+}
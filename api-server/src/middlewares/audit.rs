@@ -0,0 +1,98 @@
+use crate::app_state::AppState;
+use crate::models::{AuditAction, AuditContext, AuditEvent, User};
+use actix_web::web::Data;
+use actix_web::HttpMessage;
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error,
+};
+use futures_util::future::LocalBoxFuture;
+use serde_json::json;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+
+// Records every call going through the scope in the audit log, rejected ones included.
+// NOTE: Has to be wrapped after `CheckAuthToken` so it runs around it, the user is read once
+// the call is done.
+pub struct AuditTrail;
+
+impl<S, B> Transform<S, ServiceRequest> for AuditTrail
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = AuditTrailMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuditTrailMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct AuditTrailMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AuditTrailMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let app_state = req.app_data::<Data<AppState>>().cloned();
+        let ip = req.peer_addr().map(|addr| addr.ip().to_string());
+        let target = format!("{} {}", req.method(), req.path());
+
+        let service = self.service.clone();
+
+        Box::pin(async move {
+            let result = service.call(req).await;
+            // Not kept from before the call, the request must stay unshared for the inner
+            // middlewares to set the user. A rejected call has none.
+            let actor = result.as_ref().ok().and_then(|response| {
+                response
+                    .request()
+                    .extensions()
+                    .get::<User>()
+                    .map(|user| user.id)
+            });
+
+            let state = match app_state {
+                Some(s) => s,
+                None => return result,
+            };
+
+            let status_code = match &result {
+                Ok(response) => response.status().as_u16(),
+                Err(e) => e.as_response_error().status_code().as_u16(),
+            };
+
+            let event = AuditEvent {
+                action: AuditAction::AdminCall,
+                context: AuditContext::new(actor, None, ip),
+                household_id: None,
+                target: Some(target.clone()),
+                before: None,
+                after: Some(json!({ "status_code": status_code })),
+            };
+            if let Err(e) = state.audit_service.record(event).await {
+                tracing::warn!(target = %target, error = %e, "Failed to record audit entry");
+            }
+
+            result
+        })
+    }
+}
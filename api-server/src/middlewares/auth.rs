@@ -1,17 +1,19 @@
 use crate::app_state::AppState;
 use actix_web::web::Data;
+use actix_web::HttpMessage;
 use actix_web::{
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
     Error,
 };
 use futures_util::future::LocalBoxFuture;
 use std::future::{ready, Ready};
+use std::rc::Rc;
 
 pub struct CheckAuthToken;
 
 impl<S, B> Transform<S, ServiceRequest> for CheckAuthToken
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: 'static,
 {
@@ -22,17 +24,19 @@ where
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
-        ready(Ok(CheckAuthTokenMiddleware { service }))
+        ready(Ok(CheckAuthTokenMiddleware {
+            service: Rc::new(service),
+        }))
     }
 }
 
 pub struct CheckAuthTokenMiddleware<S> {
-    service: S,
+    service: Rc<S>,
 }
 
 impl<S, B> Service<ServiceRequest> for CheckAuthTokenMiddleware<S>
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: 'static,
 {
@@ -46,7 +50,7 @@ where
         let app_state = req.app_data::<Data<AppState>>().cloned();
         let auth_header = req.headers().get("Authorization").cloned();
 
-        let fut = self.service.call(req);
+        let service = self.service.clone();
 
         Box::pin(async move {
             let state = match app_state {
@@ -62,29 +66,19 @@ where
                 Some(h) => h.to_str().unwrap_or("").to_string(),
                 None => return Err(actix_web::error::ErrorUnauthorized("No token")),
             };
+            // The bare token is still accepted for the clients sending it without a scheme.
+            let token = token_str.strip_prefix("Bearer ").unwrap_or(&token_str);
 
-            let (is_valid, user) = state
-                .auth_service
-                .verify_token(token_str)
-                .await
-                .map_err(|e| actix_web::error::ErrorUnauthorized(format!("Auth failed: {}", e)))?;
+            let (user, session) =
+                state.session_service.verify(token).await.map_err(|e| {
+                    actix_web::error::ErrorUnauthorized(format!("Auth failed: {}", e))
+                })?;
 
-            if !is_valid {
-                return Err(actix_web::error::ErrorUnauthorized("Invalid token or user"));
-            }
+            // Handlers get the stored user and its session through `ReqData`.
+            req.extensions_mut().insert(user);
+            req.extensions_mut().insert(session);
 
-            let user_exists = state
-                .user_service
-                .get_by_google_id(user.google_id)
-                .await
-                .map_err(|e| actix_web::error::ErrorUnauthorized(e.to_string()))?;
-
-            match user_exists {
-                Some(_) => {}
-                None => return Err(actix_web::error::ErrorUnauthorized("User not found")),
-            }
-
-            fut.await
+            service.call(req).await
         })
     }
 }
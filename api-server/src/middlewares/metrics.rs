@@ -0,0 +1,79 @@
+use crate::app_state::AppState;
+use actix_web::web::Data;
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error,
+};
+use futures_util::future::LocalBoxFuture;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::time::Instant;
+
+// Requests matching no route share one label, so scanners can't add series.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+// Times every request by method, route pattern and status, rejections included.
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequestMetricsMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let app_state = req.app_data::<Data<AppState>>().cloned();
+        let method = req.method().to_string();
+        let route = req
+            .match_pattern()
+            .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+
+        let service = self.service.clone();
+        let started = Instant::now();
+
+        Box::pin(async move {
+            let result = service.call(req).await;
+
+            if let Some(state) = app_state {
+                let status_code = match &result {
+                    Ok(response) => response.status().as_u16(),
+                    Err(e) => e.as_response_error().status_code().as_u16(),
+                };
+                state
+                    .metrics
+                    .observe_request(&method, &route, status_code, started.elapsed());
+            }
+
+            result
+        })
+    }
+}
@@ -1,2 +1,11 @@
 mod auth;
 pub use auth::CheckAuthToken;
+
+mod audit;
+pub use audit::AuditTrail;
+
+mod request_trace;
+pub use request_trace::RequestTrace;
+
+mod metrics;
+pub use metrics::RequestMetrics;
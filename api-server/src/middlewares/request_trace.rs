@@ -0,0 +1,228 @@
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error,
+};
+use bson::Uuid;
+use futures_util::future::LocalBoxFuture;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::time::Instant;
+use tracing::Instrument;
+
+use crate::logging::{with_request_id, REQUEST_ID_HEADER};
+
+// Longer IDs, or IDs with other characters, are replaced rather than logged.
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+// Polled by the orchestrator and Prometheus, only logged at debug level when they succeed.
+const PROBE_PATHS: [&str; 3] = ["/healthz", "/readyz", "/metrics"];
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+// Gives every request an ID, taken from `X-Request-Id` when the client sent one, runs it
+// in a span carrying that ID and returns it in the response.
+// NOTE: Has to be the outermost middleware so the other ones log inside the span.
+pub struct RequestTrace;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestTrace
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestTraceMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestTraceMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequestTraceMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestTraceMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let request_id = req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| is_valid_request_id(id))
+            .map(str::to_string)
+            .unwrap_or_else(|| Uuid::new().to_string());
+
+        // Only the path, the query of a login callback carries its code and state.
+        let span = tracing::info_span!(
+            "request",
+            request_id = %request_id,
+            method = %req.method(),
+            path = %req.path(),
+        );
+        let header_value = HeaderValue::from_str(&request_id).ok();
+        let is_probe = PROBE_PATHS.contains(&req.path());
+
+        let service = self.service.clone();
+        let started = Instant::now();
+
+        Box::pin(with_request_id(
+            request_id,
+            async move {
+                let result = service.call(req).await;
+                let elapsed_ms = started.elapsed().as_millis() as u64;
+
+                match &result {
+                    Ok(response) if response.status().is_server_error() => {
+                        tracing::error!(
+                            status = response.status().as_u16(),
+                            elapsed_ms,
+                            "request failed"
+                        )
+                    }
+                    Ok(response) if is_probe => {
+                        tracing::debug!(
+                            status = response.status().as_u16(),
+                            elapsed_ms,
+                            "request finished"
+                        )
+                    }
+                    Ok(response) => {
+                        tracing::info!(
+                            status = response.status().as_u16(),
+                            elapsed_ms,
+                            "request finished"
+                        )
+                    }
+                    Err(e) => tracing::warn!(
+                        status = e.as_response_error().status_code().as_u16(),
+                        elapsed_ms,
+                        "request rejected"
+                    ),
+                }
+
+                result.map(|mut response| {
+                    if let Some(value) = header_value {
+                        response
+                            .headers_mut()
+                            .insert(HeaderName::from_static("x-request-id"), value);
+                    }
+                    response
+                })
+            }
+            .instrument(span),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging::current_request_id;
+    use actix_web::{test, web, App, HttpResponse};
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    // Collects what the subscriber writes.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    async fn echo_request_id() -> HttpResponse {
+        HttpResponse::Ok().body(current_request_id().unwrap_or_default())
+    }
+
+    #[actix_web::test]
+    async fn client_request_id_is_kept_and_returned() {
+        let app = test::init_service(
+            App::new()
+                .wrap(RequestTrace)
+                .route("/", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/")
+            .insert_header((REQUEST_ID_HEADER, "door-42"))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.headers().get("x-request-id").unwrap(), "door-42");
+        assert_eq!(test::read_body(response).await, "door-42");
+
+        // Replaced, and the new one is what the handlers see.
+        let request = test::TestRequest::get()
+            .uri("/")
+            .insert_header((REQUEST_ID_HEADER, "not valid!"))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        let returned = response.headers().get("x-request-id").unwrap().clone();
+        assert_ne!(returned, "not valid!");
+        assert_eq!(
+            test::read_body(response).await,
+            returned.to_str().unwrap().as_bytes()
+        );
+    }
+
+    #[actix_web::test]
+    async fn secrets_are_not_logged() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let app = test::init_service(
+            App::new()
+                .wrap(RequestTrace)
+                .route("/api/auth/callback", web::get().to(echo_request_id)),
+        )
+        .await;
+        let request = test::TestRequest::get()
+            .uri("/api/auth/callback?code=secret-code&state=secret-state")
+            .insert_header(("Authorization", "Bearer secret-token"))
+            .insert_header(("X-Signature", "secret-signature"))
+            .insert_header((REQUEST_ID_HEADER, "login-1"))
+            .to_request();
+        test::call_service(&app, request).await;
+
+        let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("request finished"));
+        assert!(logs.contains("login-1"));
+        assert!(logs.contains("/api/auth/callback"));
+        assert!(!logs.contains("secret"), "{}", logs);
+    }
+}
@@ -0,0 +1,159 @@
+use bson::serde_helpers::chrono_datetime_as_bson_datetime;
+use bson::Uuid;
+use chrono::{DateTime, SubsecRound, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+// Previous hash of the first entry of the chain.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AuditAction {
+    PictureUploaded,
+    StatusDecided,
+    StatusExpired,
+    Login,
+    Logout,
+    AdminCall,
+}
+
+// Who did it, from where.
+#[derive(Debug, Clone, Default)]
+pub struct AuditContext {
+    pub actor: Option<Uuid>,
+    pub device_id: Option<Uuid>,
+    pub ip: Option<String>,
+}
+
+impl AuditContext {
+    pub fn new(actor: Option<Uuid>, device_id: Option<Uuid>, ip: Option<String>) -> Self {
+        Self {
+            actor,
+            device_id,
+            ip,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    pub action: AuditAction,
+    pub context: AuditContext,
+    // Household the entry is about, its owners read it.
+    pub household_id: Option<Uuid>,
+    pub target: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+// Entries are chained: each hash covers the entry and the hash of the one before,
+// so editing or removing an entry breaks every hash after it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    #[serde(rename = "_id")]
+    pub sequence: i64,
+    pub action: AuditAction,
+    pub actor: Option<Uuid>,
+    pub device_id: Option<Uuid>,
+    pub ip: Option<String>,
+    #[serde(default)]
+    pub household_id: Option<Uuid>,
+    pub target: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    pub previous_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    pub fn new(sequence: i64, previous_hash: String, event: AuditEvent) -> Self {
+        let mut entry = Self {
+            sequence,
+            action: event.action,
+            actor: event.context.actor,
+            device_id: event.context.device_id,
+            ip: event.context.ip,
+            household_id: event.household_id,
+            target: event.target,
+            before: event.before,
+            after: event.after,
+            // NOTE: BSON dates only keep milliseconds, the hash must survive a round trip.
+            created_at: Utc::now().trunc_subsecs(3),
+            previous_hash,
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+        entry
+    }
+
+    pub fn compute_hash(&self) -> String {
+        let mut content = json!({
+            "sequence": self.sequence,
+            "action": self.action,
+            "actor": self.actor.map(|id| id.to_string()),
+            "device_id": self.device_id.map(|id| id.to_string()),
+            "ip": self.ip,
+            "target": self.target,
+            "before": self.before,
+            "after": self.after,
+            "created_at": self.created_at.timestamp_millis(),
+            "previous_hash": self.previous_hash,
+        });
+        // NOTE: Only hashed when set, entries written before households keep their hash.
+        if let Some(household_id) = self.household_id {
+            content["household_id"] = json!(household_id.to_string());
+        }
+
+        hex::encode(Sha256::digest(canonical(content).to_string().as_bytes()))
+    }
+}
+
+// Sorts object keys so the hash doesn't depend on the order fields were stored in.
+fn canonical(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut fields: Vec<(String, Value)> = map.into_iter().collect();
+            fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(key, value)| (key, canonical(value)))
+                    .collect(),
+            )
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(canonical).collect()),
+        other => other,
+    }
+}
+
+// Entries of the households, or acted by the user.
+#[derive(Debug, Clone)]
+pub struct AuditScope {
+    pub actor: Uuid,
+    pub household_ids: Vec<Uuid>,
+}
+
+impl AuditScope {
+    pub fn contains(&self, entry: &AuditEntry) -> bool {
+        entry.actor == Some(self.actor)
+            || entry
+                .household_id
+                .is_some_and(|household_id| self.household_ids.contains(&household_id))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    // Every entry when unset.
+    pub scope: Option<AuditScope>,
+    pub action: Option<AuditAction>,
+    pub actor: Option<Uuid>,
+    pub device_id: Option<Uuid>,
+    pub target: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: i64,
+}
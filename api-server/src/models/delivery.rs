@@ -0,0 +1,68 @@
+use bson::serde_helpers::chrono_datetime_as_bson_datetime;
+use bson::Uuid;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DeliveryState {
+    Pending,
+    Delivered,
+    Failed,
+    Expired,
+    // A newer decision for the same status and controller was queued.
+    Superseded,
+}
+
+// One decision to deliver to one controller, kept in the outbox until it's done.
+// NOTE: Dates are stored as BSON dates so the worker can query due deliveries.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Delivery {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub status_id: Uuid,
+    pub device_id: Uuid,
+    pub callback_url: String,
+    pub authorised: bool,
+    pub state: DeliveryState,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    // Of the request that made the decision, sent along so the controller logs match ours.
+    #[serde(default)]
+    pub request_id: Option<String>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub next_attempt_at: DateTime<Utc>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Delivery {
+    pub fn new(
+        status_id: Uuid,
+        device_id: Uuid,
+        callback_url: String,
+        authorised: bool,
+        expires_at: DateTime<Utc>,
+        request_id: Option<String>,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new(),
+            status_id,
+            device_id,
+            callback_url,
+            authorised,
+            state: DeliveryState::Pending,
+            attempts: 0,
+            last_error: None,
+            request_id,
+            next_attempt_at: now,
+            expires_at,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
@@ -2,17 +2,66 @@ use bson::Uuid;
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DeviceType {
     Esp32Main,
     Esp32Cam,
+    // A home automation integration, it only signs MQTT decision commands.
+    Integration,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Device {
+    #[serde(rename = "_id")]
     pub id: Uuid,
+    // Who registered it, their topics and webhooks carry its events.
     pub user_id: Uuid,
+    // `None` for devices registered before households, only their user reaches them until
+    // they're moved into the user's household at the next login.
+    #[serde(default)]
+    pub household_id: Option<Uuid>,
+    pub name: String,
     pub device_type: DeviceType,
+    // Shared with the device at registration, it signs what the device sends us.
+    pub secret: String,
+    // Only set on esp32-main controllers: the camera they follow.
+    pub camera_id: Option<Uuid>,
+    // Only set on esp32-main controllers: where they receive their statuses.
+    pub callback_url: Option<String>,
+    // Only set on esp32-cam cameras: where they're asked to take a picture.
+    #[serde(default)]
+    pub capture_url: Option<String>,
+    pub revoked_at: Option<DateTime<Local>>,
     pub created_at: DateTime<Local>,
     pub updated_at: DateTime<Local>,
 }
+
+impl Device {
+    pub fn new(
+        user_id: Uuid,
+        household_id: Uuid,
+        name: String,
+        device_type: DeviceType,
+        secret: String,
+    ) -> Self {
+        let now = Local::now();
+        Self {
+            id: Uuid::new(),
+            user_id,
+            household_id: Some(household_id),
+            name,
+            device_type,
+            secret,
+            camera_id: None,
+            callback_url: None,
+            capture_url: None,
+            revoked_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+}
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::StatusState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum EventKind {
+    #[serde(rename = "capture.created")]
+    CaptureCreated,
+    #[serde(rename = "status.approved")]
+    StatusApproved,
+    #[serde(rename = "status.denied")]
+    StatusDenied,
+    #[serde(rename = "status.cancelled")]
+    StatusCancelled,
+    #[serde(rename = "status.expired")]
+    StatusExpired,
+}
+
+impl EventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::CaptureCreated => "capture.created",
+            EventKind::StatusApproved => "status.approved",
+            EventKind::StatusDenied => "status.denied",
+            EventKind::StatusCancelled => "status.cancelled",
+            EventKind::StatusExpired => "status.expired",
+        }
+    }
+
+    // The event announcing a status that just moved to `state`.
+    pub fn from_state(state: StatusState) -> Self {
+        match state {
+            StatusState::Pending => EventKind::CaptureCreated,
+            StatusState::Approved => EventKind::StatusApproved,
+            StatusState::Denied => EventKind::StatusDenied,
+            StatusState::Cancelled => EventKind::StatusCancelled,
+            StatusState::Expired => EventKind::StatusExpired,
+        }
+    }
+}
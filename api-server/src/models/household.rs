@@ -0,0 +1,77 @@
+use bson::Uuid;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+// What a member may do, each role grants what the ones below it do.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum HouseholdRole {
+    // Manages the devices and the members.
+    Owner,
+    // Decides statuses.
+    Approver,
+    // Only sees the history.
+    Viewer,
+}
+
+impl HouseholdRole {
+    fn rank(self) -> u8 {
+        match self {
+            Self::Owner => 2,
+            Self::Approver => 1,
+            Self::Viewer => 0,
+        }
+    }
+
+    pub fn grants(self, required: HouseholdRole) -> bool {
+        self.rank() >= required.rank()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HouseholdMember {
+    pub user_id: Uuid,
+    pub role: HouseholdRole,
+    pub joined_at: DateTime<Local>,
+}
+
+// Owns devices, and through them their pictures and statuses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Household {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub name: String,
+    pub members: Vec<HouseholdMember>,
+    pub created_at: DateTime<Local>,
+    pub updated_at: DateTime<Local>,
+}
+
+impl Household {
+    pub fn new(name: String, owner_id: Uuid) -> Self {
+        let now = Local::now();
+        Self {
+            id: Uuid::new(),
+            name,
+            members: vec![HouseholdMember {
+                user_id: owner_id,
+                role: HouseholdRole::Owner,
+                joined_at: now,
+            }],
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn owner_count(&self) -> usize {
+        self.members
+            .iter()
+            .filter(|member| member.role == HouseholdRole::Owner)
+            .count()
+    }
+
+    pub fn role_of(&self, user_id: Uuid) -> Option<HouseholdRole> {
+        self.members
+            .iter()
+            .find(|member| member.user_id == user_id)
+            .map(|member| member.role)
+    }
+}
@@ -0,0 +1,53 @@
+use bson::serde_helpers::chrono_datetime_as_bson_datetime;
+use bson::Uuid;
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::HouseholdRole;
+
+// Lets the user signing in with `email` join the household, once, before `expires_at`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Invitation {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub household_id: Uuid,
+    // Lowercased, compared with the email of the user redeeming it.
+    pub email: String,
+    pub role: HouseholdRole,
+    // SHA-256 of the code, the code itself is only returned to the owner inviting.
+    pub code_hash: String,
+    pub invited_by: Uuid,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+    pub accepted_by: Option<Uuid>,
+    pub accepted_at: Option<DateTime<Local>>,
+    pub created_at: DateTime<Local>,
+}
+
+impl Invitation {
+    pub fn new(
+        household_id: Uuid,
+        email: String,
+        role: HouseholdRole,
+        code_hash: String,
+        invited_by: Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            id: Uuid::new(),
+            household_id,
+            email,
+            role,
+            code_hash,
+            invited_by,
+            expires_at,
+            accepted_by: None,
+            accepted_at: None,
+            created_at: Local::now(),
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.accepted_by.is_none() && self.expires_at > Utc::now()
+    }
+}
@@ -2,13 +2,39 @@ mod picture;
 pub use picture::Picture;
 
 mod status;
-pub use status::Status;
+pub use status::{Status, StatusState};
 
 mod user;
-pub use user::User;
+pub use user::{User, UserIdentity};
 
 mod token;
 pub use token::Token;
 
 mod device;
 pub use device::{Device, DeviceType};
+
+mod delivery;
+pub use delivery::{Delivery, DeliveryState};
+
+mod audit;
+pub use audit::{
+    AuditAction, AuditContext, AuditEntry, AuditEvent, AuditFilter, AuditScope, GENESIS_HASH,
+};
+
+mod event;
+pub use event::EventKind;
+
+mod webhook;
+pub use webhook::{Webhook, WebhookDelivery};
+
+mod session;
+pub use session::{Session, MAX_ROTATED_TOKENS};
+
+mod oauth_state;
+pub use oauth_state::{LoginResponseType, OAuthState};
+
+mod household;
+pub use household::{Household, HouseholdMember, HouseholdRole};
+
+mod invitation;
+pub use invitation::Invitation;
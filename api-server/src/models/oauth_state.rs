@@ -0,0 +1,58 @@
+use bson::serde_helpers::chrono_datetime_as_bson_datetime;
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+// How the callback answers once the login is done.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoginResponseType {
+    #[default]
+    Json,
+    // A page showing the tokens, for clients that can't read the redirect themselves.
+    Html,
+}
+
+// Issued with each authorisation url and consumed by its callback, a login can't be
+// completed without it nor twice with it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthState {
+    // The `state` parameter itself, random and unguessable.
+    #[serde(rename = "_id")]
+    pub state: String,
+    // Name of the identity provider the user was sent to.
+    pub provider: String,
+    // Proves on the code exchange that we started the login (PKCE).
+    pub pkce_verifier: String,
+    // Expected in the ID token, so a token issued for another login is refused.
+    pub nonce: String,
+    pub response_type: LoginResponseType,
+    // Household invitation redeemed once the user is signed in.
+    #[serde(default)]
+    pub invitation: Option<String>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Local>,
+}
+
+impl OAuthState {
+    pub fn new(
+        state: String,
+        provider: String,
+        pkce_verifier: String,
+        nonce: String,
+        response_type: LoginResponseType,
+        invitation: Option<String>,
+        expires_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            state,
+            provider,
+            pkce_verifier,
+            nonce,
+            response_type,
+            invitation,
+            expires_at,
+            created_at: Local::now(),
+        }
+    }
+}
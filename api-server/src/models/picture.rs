@@ -7,19 +7,20 @@ pub struct Picture {
     #[serde(rename = "_id")]
     pub id: Uuid,
     pub user_id: Uuid,
+    // Camera that took the picture, `None` for uploads made before devices existed.
+    pub device_id: Option<Uuid>,
     pub name: String,
-    pub url: String,
     pub created_at: DateTime<Local>,
     pub updated_at: Option<DateTime<Local>>,
 }
 
 impl Picture {
-    pub fn new(user_id: Uuid, name: String, url: String) -> Self {
+    pub fn new(user_id: Uuid, device_id: Uuid, name: String) -> Self {
         Self {
             id: Uuid::new(),
             user_id,
+            device_id: Some(device_id),
             name,
-            url,
             created_at: Local::now(),
             updated_at: None,
         }
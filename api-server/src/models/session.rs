@@ -0,0 +1,46 @@
+use bson::serde_helpers::chrono_datetime_as_bson_datetime;
+use bson::Uuid;
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+// How many rotated refresh tokens a session remembers, older ones are forgotten.
+pub const MAX_ROTATED_TOKENS: usize = 50;
+
+// A login, kept alive by rotating its refresh token. Access tokens name the session they
+// were minted for, revoking it ends them all.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub user_id: Uuid,
+    // SHA-256 of the current refresh token, the token itself is never stored.
+    pub refresh_token_hash: String,
+    // Refresh tokens already rotated, the latest `MAX_ROTATED_TOKENS` ones, one coming back
+    // means it leaked.
+    pub rotated_token_hashes: Vec<String>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Local>>,
+    pub created_at: DateTime<Local>,
+    pub updated_at: DateTime<Local>,
+}
+
+impl Session {
+    pub fn new(user_id: Uuid, refresh_token_hash: String, expires_at: DateTime<Utc>) -> Self {
+        let now = Local::now();
+        Self {
+            id: Uuid::new(),
+            user_id,
+            refresh_token_hash,
+            rotated_token_hashes: Vec::new(),
+            expires_at,
+            revoked_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none() && self.expires_at > Utc::now()
+    }
+}
@@ -1,25 +1,51 @@
+use bson::serde_helpers::chrono_datetime_as_bson_datetime;
 use bson::Uuid;
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, Utc};
 use serde::{Deserialize, Serialize};
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum StatusState {
+    // Nobody has looked at the picture yet.
+    Pending,
+    Approved,
+    Denied,
+    // Nobody decided before the timeout.
+    Expired,
+    Cancelled,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Status {
     #[serde(rename = "_id")]
     pub id: Uuid,
     pub picture_id: Uuid,
-    pub authorised: bool,
+    pub state: StatusState,
+    pub decided_by: Option<Uuid>,
+    pub decided_at: Option<DateTime<Local>>,
+    pub reason: Option<String>,
+    // NOTE: Stored as a BSON date so stale pending statuses can be queried.
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
     pub created_at: DateTime<Local>,
     pub updated_at: Option<DateTime<Local>>,
 }
 
 impl Status {
-    pub fn new(picture_id: Uuid) -> Self {
+    pub fn new(picture_id: Uuid, expires_at: DateTime<Utc>) -> Self {
         Self {
             id: Uuid::new(),
             picture_id,
-            authorised: false,
+            state: StatusState::Pending,
+            decided_by: None,
+            decided_at: None,
+            reason: None,
+            expires_at,
             created_at: Local::now(),
             updated_at: None,
         }
     }
+
+    pub fn is_authorised(&self) -> bool {
+        self.state == StatusState::Approved
+    }
 }
@@ -2,12 +2,27 @@ use bson::Uuid;
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 
+// An account at an identity provider, `subject` being its `sub` claim.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserIdentity {
+    pub provider: String,
+    pub subject: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     #[serde(rename = "_id")]
     pub id: Uuid,
-    pub google_id: String,
+    // Only on accounts created before identities, the Google identity gets added at their
+    // next login.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub google_id: Option<String>,
+    #[serde(default)]
+    pub identities: Vec<UserIdentity>,
     pub email: String,
+    // As the provider said when the account was created, older accounts count as unverified.
+    #[serde(default)]
+    pub email_verified: bool,
     pub name: String,
     pub picture: Option<String>,
     pub created_at: DateTime<Local>,
@@ -15,11 +30,19 @@ pub struct User {
 }
 
 impl User {
-    pub fn new(google_id: String, email: String, name: String, picture: Option<String>) -> Self {
+    pub fn new(
+        identity: UserIdentity,
+        email: String,
+        email_verified: bool,
+        name: String,
+        picture: Option<String>,
+    ) -> Self {
         Self {
             id: Uuid::new(),
-            google_id,
+            google_id: None,
+            identities: vec![identity],
             email,
+            email_verified,
             name,
             picture,
             created_at: Local::now(),
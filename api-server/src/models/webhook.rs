@@ -0,0 +1,83 @@
+use bson::serde_helpers::chrono_datetime_as_bson_datetime;
+use bson::Uuid;
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{DeliveryState, EventKind};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub url: String,
+    pub events: Vec<EventKind>,
+    // Signs the payloads so the receiver can tell they come from us.
+    pub secret: String,
+    pub created_at: DateTime<Local>,
+    pub updated_at: DateTime<Local>,
+}
+
+impl Webhook {
+    pub fn new(user_id: Uuid, url: String, events: Vec<EventKind>, secret: String) -> Self {
+        let now = Local::now();
+        Self {
+            id: Uuid::new(),
+            user_id,
+            url,
+            events,
+            secret,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+// One event to post to one webhook, the payload is kept as queued and its picture url minted
+// again on each attempt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    // Household of the camera, the webhook's user must still be a member when it's sent.
+    #[serde(default)]
+    pub household_id: Option<Uuid>,
+    pub event: EventKind,
+    pub payload: String,
+    pub state: DeliveryState,
+    pub attempts: u32,
+    pub response_status: Option<u16>,
+    pub last_error: Option<String>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub next_attempt_at: DateTime<Utc>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl WebhookDelivery {
+    pub fn new(
+        webhook_id: Uuid,
+        household_id: Option<Uuid>,
+        event: EventKind,
+        payload: String,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new(),
+            webhook_id,
+            household_id,
+            event,
+            payload,
+            state: DeliveryState::Pending,
+            attempts: 0,
+            response_status: None,
+            last_error: None,
+            next_attempt_at: now,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
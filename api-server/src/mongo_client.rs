@@ -15,7 +15,7 @@ pub async fn init_mongo_client(
         .run_command(doc! {"ping": 1})
         .await?;
 
-    println!("MongoDB client initialised!");
+    tracing::info!("MongoDB client initialised");
 
     Ok(client)
 }
@@ -0,0 +1,35 @@
+use rumqttc::{AsyncClient, EventLoop, LastWill, MqttOptions, QoS};
+use std::time::Duration;
+
+pub const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const REQUEST_CAPACITY: usize = 64;
+// Pictures are published as they are, the default 10 KiB limit is too small for them.
+const MAX_INCOMING_PACKET_SIZE: usize = 10 * 1024;
+const MAX_OUTGOING_PACKET_SIZE: usize = 4 * 1024 * 1024;
+
+// Nothing is sent before the event loop is polled, the connection is made there.
+// The broker publishes `offline` on `availability_topic` when the connection is lost.
+pub fn init_mqtt_client(
+    host: String,
+    port: u16,
+    client_id: String,
+    username: Option<String>,
+    password: Option<String>,
+    availability_topic: String,
+) -> (AsyncClient, EventLoop) {
+    let mut options = MqttOptions::new(client_id, host, port);
+    options.set_keep_alive(KEEP_ALIVE);
+    options.set_max_packet_size(MAX_INCOMING_PACKET_SIZE, MAX_OUTGOING_PACKET_SIZE);
+    options.set_last_will(LastWill::new(
+        availability_topic,
+        "offline",
+        QoS::AtLeastOnce,
+        true,
+    ));
+
+    if let Some(username) = username {
+        options.set_credentials(username, password.unwrap_or_default());
+    }
+
+    AsyncClient::new(options, REQUEST_CAPACITY)
+}
@@ -0,0 +1,64 @@
+use bson::Uuid;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::models::{AuditAction, AuditEntry};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditQuery {
+    pub action: Option<AuditAction>,
+    pub actor: Option<String>,
+    pub device_id: Option<String>,
+    pub target: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntryResponse {
+    pub sequence: i64,
+    pub action: AuditAction,
+    pub actor: Option<Uuid>,
+    pub device_id: Option<Uuid>,
+    pub ip: Option<String>,
+    pub household_id: Option<Uuid>,
+    pub target: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub created_at: DateTime<Utc>,
+    pub previous_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntryResponse {
+    pub fn new(entry: AuditEntry) -> Self {
+        Self {
+            sequence: entry.sequence,
+            action: entry.action,
+            actor: entry.actor,
+            device_id: entry.device_id,
+            ip: entry.ip,
+            household_id: entry.household_id,
+            target: entry.target,
+            before: entry.before,
+            after: entry.after,
+            created_at: entry.created_at,
+            previous_hash: entry.previous_hash,
+            hash: entry.hash,
+        }
+    }
+}
+
+// `last_hash` can be written down elsewhere, removing entries at the end of the chain
+// is only noticed by comparing it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditVerificationResponse {
+    pub valid: bool,
+    pub checked: u64,
+    pub last_sequence: Option<i64>,
+    pub last_hash: Option<String>,
+    pub broken_at: Option<i64>,
+    pub reason: Option<String>,
+}
@@ -0,0 +1,32 @@
+use bson::Uuid;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Delivery, DeliveryState};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryResponse {
+    pub device_id: Uuid,
+    pub authorised: bool,
+    pub state: DeliveryState,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub request_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl DeliveryResponse {
+    pub fn new(delivery: Delivery) -> Self {
+        Self {
+            device_id: delivery.device_id,
+            authorised: delivery.authorised,
+            state: delivery.state,
+            attempts: delivery.attempts,
+            last_error: delivery.last_error,
+            request_id: delivery.request_id,
+            created_at: delivery.created_at,
+            updated_at: delivery.updated_at,
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use bson::Uuid;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Device, DeviceType};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceResponse {
+    pub id: Uuid,
+    pub household_id: Option<Uuid>,
+    pub name: String,
+    pub device_type: DeviceType,
+    pub camera_id: Option<Uuid>,
+    pub callback_url: Option<String>,
+    pub capture_url: Option<String>,
+    pub revoked_at: Option<DateTime<Local>>,
+    pub created_at: DateTime<Local>,
+    pub updated_at: DateTime<Local>,
+}
+
+impl DeviceResponse {
+    pub fn new(device: Device) -> Self {
+        Self {
+            id: device.id,
+            household_id: device.household_id,
+            name: device.name,
+            device_type: device.device_type,
+            camera_id: device.camera_id,
+            callback_url: device.callback_url,
+            capture_url: device.capture_url,
+            revoked_at: device.revoked_at,
+            created_at: device.created_at,
+            updated_at: device.updated_at,
+        }
+    }
+}
+
+// The secret is only ever returned here, it has to be flashed on the device.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisteredDeviceResponse {
+    pub device: DeviceResponse,
+    pub secret: String,
+}
+
+impl RegisteredDeviceResponse {
+    pub fn new(device: Device) -> Self {
+        let secret = device.secret.clone();
+        Self {
+            device: DeviceResponse::new(device),
+            secret,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterDeviceRequest {
+    // The user's own household when not set.
+    pub household_id: Option<Uuid>,
+    pub name: String,
+    pub device_type: DeviceType,
+    // Controllers only.
+    pub camera_id: Option<Uuid>,
+    pub callback_url: Option<String>,
+    // Cameras only.
+    pub capture_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenameDeviceRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkCameraRequest {
+    pub camera_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallbackUpdateRequest {
+    pub callback_url: String,
+}
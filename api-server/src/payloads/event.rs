@@ -0,0 +1,27 @@
+use bson::Uuid;
+use serde::{Deserialize, Serialize};
+
+use crate::models::EventKind;
+use crate::payloads::status::StatusResponse;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub id: u64,
+    pub kind: EventKind,
+    // Who registered the camera, the MQTT topics are theirs.
+    #[serde(skip)]
+    pub user_id: Uuid,
+    // Household of the camera, the streams of all its members receive the event.
+    #[serde(skip)]
+    pub household_id: Option<Uuid>,
+    pub status: StatusResponse,
+}
+
+impl Event {
+    pub fn is_visible_to(&self, user_id: Uuid, household_ids: &[Uuid]) -> bool {
+        match self.household_id {
+            Some(household_id) => household_ids.contains(&household_id),
+            None => self.user_id == user_id,
+        }
+    }
+}
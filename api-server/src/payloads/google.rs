@@ -1,19 +1,22 @@
 use serde::{Deserialize, Serialize};
 
-use crate::models::Token;
+use crate::payloads::SessionTokens;
 
 #[derive(Deserialize, Serialize)]
 pub struct UserInfo {
     #[serde(alias = "sub")]
     pub id: String,
     pub email: String,
+    // Only a verified email is trusted to match an invitation.
+    #[serde(default)]
+    pub email_verified: bool,
     pub name: String,
     pub picture: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct AuthResponse {
-    pub token: Token,
+    pub token: SessionTokens,
     pub user_info: UserInfo,
 }
 
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthState {
+    Ok,
+    Unavailable,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentHealth {
+    pub status: HealthState,
+    pub error: Option<String>,
+    // How long the check took, workers are only looked up.
+    pub elapsed_ms: Option<u64>,
+    // Reported without making the server unready.
+    #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+    pub optional: bool,
+}
+
+impl ComponentHealth {
+    pub fn new(result: Result<(), String>, elapsed_ms: Option<u64>) -> Self {
+        match result {
+            Ok(()) => Self {
+                status: HealthState::Ok,
+                error: None,
+                elapsed_ms,
+                optional: false,
+            },
+            Err(error) => Self {
+                status: HealthState::Unavailable,
+                error: Some(error),
+                elapsed_ms,
+                optional: false,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthResponse {
+    pub status: HealthState,
+    // Only filled by the readiness probe.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub components: BTreeMap<String, ComponentHealth>,
+}
+
+impl HealthResponse {
+    // Unavailable as soon as one of the components is, optional ones aside.
+    pub fn new(components: BTreeMap<String, ComponentHealth>) -> Self {
+        let status = if components
+            .values()
+            .all(|component| component.optional || component.status == HealthState::Ok)
+        {
+            HealthState::Ok
+        } else {
+            HealthState::Unavailable
+        };
+
+        Self { status, components }
+    }
+}
@@ -0,0 +1,111 @@
+use bson::Uuid;
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Household, HouseholdMember, HouseholdRole, Invitation, User};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HouseholdResponse {
+    pub id: Uuid,
+    pub name: String,
+    // Role of the user asking.
+    pub role: Option<HouseholdRole>,
+    pub created_at: DateTime<Local>,
+    pub updated_at: DateTime<Local>,
+}
+
+impl HouseholdResponse {
+    pub fn new(household: Household, user_id: Uuid) -> Self {
+        Self {
+            id: household.id,
+            role: household.role_of(user_id),
+            name: household.name,
+            created_at: household.created_at,
+            updated_at: household.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateHouseholdRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberResponse {
+    pub user_id: Uuid,
+    // `None` once the account is gone.
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub role: HouseholdRole,
+    pub joined_at: DateTime<Local>,
+}
+
+impl MemberResponse {
+    pub fn new(member: HouseholdMember, user: Option<User>) -> Self {
+        Self {
+            user_id: member.user_id,
+            name: user.as_ref().map(|user| user.name.clone()),
+            email: user.map(|user| user.email),
+            role: member.role,
+            joined_at: member.joined_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeRoleRequest {
+    pub role: HouseholdRole,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvitationResponse {
+    pub id: Uuid,
+    pub household_id: Uuid,
+    pub email: String,
+    pub role: HouseholdRole,
+    pub invited_by: Uuid,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Local>,
+}
+
+impl InvitationResponse {
+    pub fn new(invitation: Invitation) -> Self {
+        Self {
+            id: invitation.id,
+            household_id: invitation.household_id,
+            email: invitation.email,
+            role: invitation.role,
+            invited_by: invitation.invited_by,
+            expires_at: invitation.expires_at,
+            created_at: invitation.created_at,
+        }
+    }
+}
+
+// The code is only ever returned here, it has to be passed on to the invitee.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatedInvitationResponse {
+    pub invitation: InvitationResponse,
+    pub code: String,
+}
+
+impl CreatedInvitationResponse {
+    pub fn new(invitation: Invitation, code: String) -> Self {
+        Self {
+            invitation: InvitationResponse::new(invitation),
+            code,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InviteRequest {
+    pub email: String,
+    pub role: HouseholdRole,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedeemInvitationRequest {
+    pub code: String,
+}
@@ -1,10 +1,47 @@
 mod picture;
+pub use picture::PictureResponse;
 
 mod status;
-pub use status::{AuthorisedPatchRequest, StatusResponse};
+pub use status::{AuthorisedNotification, DecisionRequest, StatusResponse};
 
 mod user;
 pub use user::UserResponse;
 
+mod device;
+pub use device::{
+    CallbackUpdateRequest, DeviceResponse, LinkCameraRequest, RegisterDeviceRequest,
+    RegisteredDeviceResponse, RenameDeviceRequest,
+};
+
+mod delivery;
+pub use delivery::DeliveryResponse;
+
 mod google;
 pub use google::{AuthResponse, OAuthCallback, UserInfo};
+
+mod audit;
+pub use audit::{AuditEntryResponse, AuditQuery, AuditVerificationResponse};
+
+mod event;
+pub use event::Event;
+
+mod webhook;
+pub use webhook::{
+    RegisterWebhookRequest, RegisteredWebhookResponse, WebhookDeliveryResponse, WebhookPayload,
+    WebhookResponse,
+};
+
+mod mqtt;
+pub use mqtt::{MqttDecisionCommand, SignedMqttCommand};
+
+mod session;
+pub use session::{LogoutRequest, RefreshRequest, SessionTokens};
+
+mod household;
+pub use household::{
+    ChangeRoleRequest, CreateHouseholdRequest, CreatedInvitationResponse, HouseholdResponse,
+    InvitationResponse, InviteRequest, MemberResponse, RedeemInvitationRequest,
+};
+
+mod health;
+pub use health::{ComponentHealth, HealthResponse, HealthState};
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::StatusState;
+
+// Published on `<prefix>/<user_id>/<camera_id>/commands/decision`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MqttDecisionCommand {
+    // The latest pending status of the camera when not set.
+    pub status_id: Option<String>,
+    pub state: StatusState,
+    pub reason: Option<String>,
+}
+
+// A command signed by an integration device: `signature` is the hex HMAC-SHA256 of
+// `<timestamp>.<topic>.<command>` made with its secret, `command` the JSON of the command.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedMqttCommand {
+    pub device_id: String,
+    pub timestamp: i64,
+    pub signature: String,
+    pub command: String,
+}
@@ -7,6 +7,8 @@ use crate::models::Picture;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PictureResponse {
     pub id: Uuid,
+    // The camera that took the picture.
+    pub device_id: Option<Uuid>,
     pub name: String,
     pub url: String,
     pub created_at: DateTime<Local>,
@@ -14,11 +16,13 @@ pub struct PictureResponse {
 }
 
 impl PictureResponse {
-    pub fn new(picture: Picture) -> Self {
+    // The url is a short-lived signed link, minted each time the picture is read.
+    pub fn new(picture: Picture, url: String) -> Self {
         Self {
             id: picture.id,
+            device_id: picture.device_id,
             name: picture.name,
-            url: picture.url,
+            url,
             created_at: picture.created_at,
             updated_at: picture.updated_at,
         }
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+// What a login or a refresh hands out, lifetimes are in seconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionTokens {
+    pub token_type: String,
+    pub access_token: String,
+    pub expires_in: u64,
+    // Only valid once, each refresh returns the next one.
+    pub refresh_token: String,
+    pub refresh_expires_in: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogoutRequest {
+    pub refresh_token: String,
+    // Ends every session of the user instead of this one only.
+    #[serde(default)]
+    pub all: bool,
+}
@@ -1,32 +1,69 @@
 use bson::Uuid;
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::models::{Picture, Status};
+use crate::models::{Delivery, Picture, Status, StatusState};
+use crate::payloads::delivery::DeliveryResponse;
 use crate::payloads::picture::PictureResponse;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StatusResponse {
     pub id: Uuid,
     pub picture: PictureResponse,
+    pub state: StatusState,
+    // Kept for the firmwares, only true once approved.
     pub authorised: bool,
+    pub decided_by: Option<Uuid>,
+    pub decided_at: Option<DateTime<Local>>,
+    pub reason: Option<String>,
+    pub expires_at: DateTime<Utc>,
     pub created_at: DateTime<Local>,
     pub updated_at: Option<DateTime<Local>>,
+    pub deliveries: Vec<DeliveryResponse>,
 }
 
 impl StatusResponse {
-    pub fn new(status: Status, picture: Picture) -> Self {
+    pub fn new(
+        status: Status,
+        picture: Picture,
+        picture_url: String,
+        deliveries: Vec<DeliveryResponse>,
+    ) -> Self {
         Self {
             id: status.id,
-            picture: PictureResponse::new(picture),
-            authorised: status.authorised,
+            picture: PictureResponse::new(picture, picture_url),
+            state: status.state,
+            authorised: status.is_authorised(),
+            decided_by: status.decided_by,
+            decided_at: status.decided_at,
+            reason: status.reason,
+            expires_at: status.expires_at,
             created_at: status.created_at,
             updated_at: status.updated_at,
+            deliveries,
         }
     }
 }
 
+// Sent to the esp32-main controller, kept small for its fixed-size buffers.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AuthorisedPatchRequest {
+pub struct AuthorisedNotification {
+    pub id: Uuid,
     pub authorised: bool,
 }
+
+impl AuthorisedNotification {
+    pub fn new(delivery: &Delivery) -> Self {
+        Self {
+            id: delivery.status_id,
+            authorised: delivery.authorised,
+        }
+    }
+}
+
+// Approved, Denied or Cancelled, the reason is shown next to the decision.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecisionRequest {
+    pub state: StatusState,
+    pub reason: Option<String>,
+}
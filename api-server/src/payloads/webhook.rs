@@ -0,0 +1,100 @@
+use bson::Uuid;
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{DeliveryState, EventKind, Webhook, WebhookDelivery};
+use crate::payloads::event::Event;
+use crate::payloads::status::StatusResponse;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterWebhookRequest {
+    pub url: String,
+    pub events: Vec<EventKind>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookResponse {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<EventKind>,
+    pub created_at: DateTime<Local>,
+    pub updated_at: DateTime<Local>,
+}
+
+impl WebhookResponse {
+    pub fn new(webhook: Webhook) -> Self {
+        Self {
+            id: webhook.id,
+            url: webhook.url,
+            events: webhook.events,
+            created_at: webhook.created_at,
+            updated_at: webhook.updated_at,
+        }
+    }
+}
+
+// The secret is only ever returned here, the receiver needs it to check signatures.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisteredWebhookResponse {
+    pub webhook: WebhookResponse,
+    pub secret: String,
+}
+
+impl RegisteredWebhookResponse {
+    pub fn new(webhook: Webhook) -> Self {
+        let secret = webhook.secret.clone();
+        Self {
+            webhook: WebhookResponse::new(webhook),
+            secret,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDeliveryResponse {
+    pub id: Uuid,
+    pub event: EventKind,
+    pub state: DeliveryState,
+    pub attempts: u32,
+    pub response_status: Option<u16>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl WebhookDeliveryResponse {
+    pub fn new(delivery: WebhookDelivery) -> Self {
+        Self {
+            id: delivery.id,
+            event: delivery.event,
+            state: delivery.state,
+            attempts: delivery.attempts,
+            response_status: delivery.response_status,
+            last_error: delivery.last_error,
+            next_attempt_at: delivery.next_attempt_at,
+            created_at: delivery.created_at,
+            updated_at: delivery.updated_at,
+        }
+    }
+}
+
+// What is posted to the webhook url.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    pub id: u64,
+    pub event: EventKind,
+    pub created_at: DateTime<Utc>,
+    pub status: StatusResponse,
+}
+
+impl WebhookPayload {
+    pub fn new(event: Event) -> Self {
+        Self {
+            id: event.id,
+            event: event.kind,
+            created_at: Utc::now(),
+            status: event.status,
+        }
+    }
+}
@@ -1,6 +1,12 @@
 use async_trait::async_trait;
 use google_cloud_storage::client::Client;
+use google_cloud_storage::http::objects::delete::DeleteObjectRequest;
+use google_cloud_storage::http::objects::download::Range;
+use google_cloud_storage::http::objects::get::GetObjectRequest;
 use google_cloud_storage::http::objects::upload::{Media, UploadObjectRequest, UploadType};
+use google_cloud_storage::http::Error as GcsError;
+use google_cloud_storage::sign::SignedURLOptions;
+use std::time::Duration;
 
 use super::StorageRepository;
 use crate::errors::Error;
@@ -21,18 +27,39 @@ impl GcsRepository {
             bucket_name,
         }
     }
+
+    fn object_path(name: &str) -> String {
+        format!(
+            "{}/{}",
+            GOOGLE_STORAGE_BASE_PATH.trim_matches('/'),
+            name.trim_start_matches('/')
+        )
+    }
+
+    fn get_request(&self, name: &str) -> GetObjectRequest {
+        GetObjectRequest {
+            bucket: self.bucket_name.to_string(),
+            object: Self::object_path(name),
+            ..Default::default()
+        }
+    }
+
+    fn map_error(name: &str, error: GcsError) -> Error {
+        match error {
+            GcsError::Response(response) if response.code == 404 => {
+                Error::NotFound(format!("object not found: {}", name))
+            }
+            e => Error::Storage(e.to_string()),
+        }
+    }
 }
 
 #[async_trait]
 impl StorageRepository for GcsRepository {
     async fn upload_file(&self, name: &str, data: Vec<u8>) -> Result<String, Error> {
-        let object_path = format!(
-            "{}{}",
-            GOOGLE_STORAGE_BASE_PATH.trim_start_matches('/'),
-            name
-        );
+        let object_path = Self::object_path(name);
 
-        let mut media = Media::new(object_path.clone());
+        let mut media = Media::new(object_path);
         media.content_type = CONTENT_TYPE.into();
         media.content_length = Some(data.len() as u64);
 
@@ -50,8 +77,50 @@ impl StorageRepository for GcsRepository {
             .map_err(|e| Error::Storage(e.to_string()))?;
 
         Ok(format!(
-            "{}/{}/{}/{}",
-            GOOGLE_STORAGE_BASE_URL, self.bucket_name, GOOGLE_STORAGE_BASE_PATH, object.name
+            "{}/{}/{}",
+            GOOGLE_STORAGE_BASE_URL, self.bucket_name, object.name
         ))
     }
+
+    async fn download(&self, name: &str) -> Result<Vec<u8>, Error> {
+        self.client
+            .download_object(&self.get_request(name), &Range::default())
+            .await
+            .map_err(|e| Self::map_error(name, e))
+    }
+
+    async fn delete(&self, name: &str) -> Result<(), Error> {
+        self.client
+            .delete_object(&DeleteObjectRequest {
+                bucket: self.bucket_name.to_string(),
+                object: Self::object_path(name),
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| Self::map_error(name, e))
+    }
+
+    async fn exists(&self, name: &str) -> Result<bool, Error> {
+        match self.client.get_object(&self.get_request(name)).await {
+            Ok(_) => Ok(true),
+            Err(GcsError::Response(response)) if response.code == 404 => Ok(false),
+            Err(e) => Err(Error::Storage(e.to_string())),
+        }
+    }
+
+    async fn signed_url(&self, name: &str, expires_in: Duration) -> Result<String, Error> {
+        self.client
+            .signed_url(
+                &self.bucket_name,
+                &Self::object_path(name),
+                None,
+                None,
+                SignedURLOptions {
+                    expires: expires_in,
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| Error::Storage(e.to_string()))
+    }
 }
@@ -0,0 +1,249 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::StorageRepository;
+use crate::errors::Error;
+
+static LOCAL_STORAGE_ROUTE: &str = "/api/uploads";
+// Written and removed again by the readiness probe, `.part` files are never served.
+static PROBE_FILE: &str = "readiness-probe.part";
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub struct LocalStorageRepository {
+    root: PathBuf,
+    public_base_url: String,
+    signing_secret: Vec<u8>,
+}
+
+impl LocalStorageRepository {
+    pub fn new(
+        root: PathBuf,
+        public_base_url: String,
+        signing_secret: Vec<u8>,
+    ) -> Result<Self, Error> {
+        std::fs::create_dir_all(&root)
+            .map_err(|e| Error::Storage(format!("can't create {}: {}", root.display(), e)))?;
+
+        Ok(Self {
+            root,
+            public_base_url: public_base_url.trim_end_matches('/').to_string(),
+            signing_secret,
+        })
+    }
+
+    // Checks a link produced by `signed_url`, the route serving files has no other auth.
+    pub fn verify_signature(&self, name: &str, expires: i64, signature: &str) -> Result<(), Error> {
+        if expires < Utc::now().timestamp() {
+            return Err(Error::Unauthorized("signed url expired".to_string()));
+        }
+
+        let signature = hex::decode(signature)
+            .map_err(|_| Error::Unauthorized("invalid signature".to_string()))?;
+
+        self.mac(name, expires)?
+            .verify_slice(&signature)
+            .map_err(|_| Error::Unauthorized("invalid signature".to_string()))
+    }
+
+    fn mac(&self, name: &str, expires: i64) -> Result<HmacSha256, Error> {
+        let mut mac = HmacSha256::new_from_slice(&self.signing_secret)
+            .map_err(|e| Error::Internal(e.to_string()))?;
+        mac.update(format!("{}:{}", name, expires).as_bytes());
+        Ok(mac)
+    }
+
+    // Object names are flat (`esp32_cam_<picture_id>.jpg`), so anything that could
+    // climb out of the root directory is refused before touching the filesystem.
+    fn object_path(&self, name: &str) -> Result<PathBuf, Error> {
+        let is_valid = !name.is_empty()
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.');
+
+        if !is_valid || Path::new(name).file_name() != Some(name.as_ref()) {
+            return Err(Error::BadRequest(format!("invalid object name: {}", name)));
+        }
+
+        Ok(self.root.join(name))
+    }
+
+    fn public_url(&self, name: &str) -> String {
+        format!("{}{}/{}", self.public_base_url, LOCAL_STORAGE_ROUTE, name)
+    }
+}
+
+fn map_io_error(name: &str, error: std::io::Error) -> Error {
+    match error.kind() {
+        std::io::ErrorKind::NotFound => Error::NotFound(format!("object not found: {}", name)),
+        _ => Error::Storage(error.to_string()),
+    }
+}
+
+#[async_trait]
+impl StorageRepository for LocalStorageRepository {
+    async fn upload_file(&self, name: &str, data: Vec<u8>) -> Result<String, Error> {
+        let path = self.object_path(name)?;
+        let tmp_path = path.with_extension("part");
+
+        // Write then rename so a reader never sees a half-written picture.
+        tokio::fs::write(&tmp_path, data)
+            .await
+            .map_err(|e| Error::Storage(e.to_string()))?;
+        tokio::fs::rename(&tmp_path, &path)
+            .await
+            .map_err(|e| Error::Storage(e.to_string()))?;
+
+        Ok(self.public_url(name))
+    }
+
+    async fn download(&self, name: &str) -> Result<Vec<u8>, Error> {
+        let path = self.object_path(name)?;
+
+        tokio::fs::read(&path)
+            .await
+            .map_err(|e| map_io_error(name, e))
+    }
+
+    async fn delete(&self, name: &str) -> Result<(), Error> {
+        let path = self.object_path(name)?;
+
+        tokio::fs::remove_file(&path)
+            .await
+            .map_err(|e| map_io_error(name, e))
+    }
+
+    async fn exists(&self, name: &str) -> Result<bool, Error> {
+        let path = self.object_path(name)?;
+
+        tokio::fs::try_exists(&path)
+            .await
+            .map_err(|e| Error::Storage(e.to_string()))
+    }
+
+    async fn signed_url(&self, name: &str, expires_in: Duration) -> Result<String, Error> {
+        self.object_path(name)?;
+
+        let expires = Utc::now().timestamp() + expires_in.as_secs() as i64;
+        let signature = hex::encode(self.mac(name, expires)?.finalize().into_bytes());
+
+        Ok(format!(
+            "{}?expires={}&signature={}",
+            self.public_url(name),
+            expires,
+            signature
+        ))
+    }
+
+    // Looking a file up succeeds even when the directory is gone, so the probe writes one.
+    async fn ping(&self) -> Result<(), Error> {
+        let metadata = tokio::fs::metadata(&self.root)
+            .await
+            .map_err(|e| Error::Storage(format!("can't access {}: {}", self.root.display(), e)))?;
+        if !metadata.is_dir() {
+            return Err(Error::Storage(format!(
+                "{} is not a directory",
+                self.root.display()
+            )));
+        }
+
+        let path = self.root.join(PROBE_FILE);
+        tokio::fs::write(&path, b"").await.map_err(|e| {
+            Error::Storage(format!("can't write to {}: {}", self.root.display(), e))
+        })?;
+        tokio::fs::remove_file(&path)
+            .await
+            .map_err(|e| Error::Storage(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repository() -> LocalStorageRepository {
+        let root = std::env::temp_dir().join(format!("rusty-secure-{}", bson::Uuid::new()));
+        LocalStorageRepository::new(
+            root,
+            "http://localhost:8080/".to_string(),
+            b"signing secret".to_vec(),
+        )
+        .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn names_leaving_the_root_are_refused() {
+        let repository = repository();
+        for name in [
+            "../escape.jpg",
+            "..",
+            "/etc/passwd",
+            "nested/picture.jpg",
+            ".hidden",
+            "",
+        ] {
+            let result = repository.upload_file(name, vec![1]).await;
+            assert!(
+                matches!(result, Err(Error::BadRequest(_))),
+                "{:?} was accepted",
+                name
+            );
+        }
+
+        let url = repository
+            .upload_file("esp32_cam_1.jpg", vec![1, 2, 3])
+            .await
+            .unwrap();
+        assert_eq!(url, "http://localhost:8080/api/uploads/esp32_cam_1.jpg");
+        assert_eq!(
+            repository.download("esp32_cam_1.jpg").await.unwrap(),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[actix_web::test]
+    async fn only_untouched_unexpired_links_are_accepted() {
+        let repository = repository();
+        let name = "esp32_cam_1.jpg";
+        let expires = Utc::now().timestamp() + 60;
+        let signature = hex::encode(
+            repository
+                .mac(name, expires)
+                .unwrap()
+                .finalize()
+                .into_bytes(),
+        );
+        assert!(repository
+            .verify_signature(name, expires, &signature)
+            .is_ok());
+
+        let mut tampered = signature.clone();
+        tampered.replace_range(..1, if signature.starts_with('0') { "1" } else { "0" });
+        // Another picture, a later expiry or a changed signature.
+        for (name, expires, signature) in [
+            ("esp32_cam_2.jpg", expires, signature.clone()),
+            (name, expires + 60, signature.clone()),
+            (name, expires, tampered),
+            (name, expires, "not hex".to_string()),
+        ] {
+            let result = repository.verify_signature(name, expires, &signature);
+            assert!(matches!(result, Err(Error::Unauthorized(_))));
+        }
+
+        let expired = Utc::now().timestamp() - 1;
+        let signature = hex::encode(
+            repository
+                .mac(name, expired)
+                .unwrap()
+                .finalize()
+                .into_bytes(),
+        );
+        let result = repository.verify_signature(name, expired, &signature);
+        assert!(matches!(result, Err(Error::Unauthorized(_))));
+    }
+}
@@ -0,0 +1,1140 @@
+use async_trait::async_trait;
+use bson::Uuid;
+use chrono::{DateTime, Local, Utc};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use super::{
+    AuditRepository, DeliveryRepository, DeviceRepository, HealthRepository, HouseholdRepository,
+    InvitationRepository, OAuthStateRepository, PictureRepository, SessionRepository,
+    StatusRepository, StorageRepository, UserRepository, WebhookDeliveryRepository,
+    WebhookRepository,
+};
+use crate::errors::Error;
+use crate::models::{
+    AuditEntry, AuditFilter, Delivery, DeliveryState, Device, EventKind, Household,
+    HouseholdMember, HouseholdRole, Invitation, OAuthState, Picture, Session, Status, StatusState,
+    User, UserIdentity, Webhook, WebhookDelivery, MAX_ROTATED_TOKENS,
+};
+
+static MEMORY_STORAGE_BASE_URL: &str = "memory://uploads";
+
+// NOTE: Everything is lost on restart, this backend is meant for local
+// development, demos and tests where MongoDB and GCS aren't reachable.
+#[derive(Default)]
+pub struct InMemoryRepository {
+    statuses: RwLock<HashMap<Uuid, Status>>,
+    pictures: RwLock<HashMap<Uuid, Picture>>,
+    users: RwLock<HashMap<Uuid, User>>,
+    devices: RwLock<HashMap<Uuid, Device>>,
+    deliveries: RwLock<HashMap<Uuid, Delivery>>,
+    audit_log: RwLock<Vec<AuditEntry>>,
+    webhooks: RwLock<HashMap<Uuid, Webhook>>,
+    webhook_deliveries: RwLock<HashMap<Uuid, WebhookDelivery>>,
+    sessions: RwLock<HashMap<Uuid, Session>>,
+    oauth_states: RwLock<HashMap<String, OAuthState>>,
+    households: RwLock<HashMap<Uuid, Household>>,
+    invitations: RwLock<HashMap<Uuid, Invitation>>,
+    files: RwLock<HashMap<String, Vec<u8>>>,
+}
+
+impl InMemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn lock_error<T>(_: T) -> Error {
+    Error::Database("in-memory repository lock poisoned".to_string())
+}
+
+fn storage_lock_error<T>(_: T) -> Error {
+    Error::Storage("in-memory storage lock poisoned".to_string())
+}
+
+#[async_trait]
+impl HealthRepository for InMemoryRepository {
+    async fn ping(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StatusRepository for InMemoryRepository {
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Status>, Error> {
+        let statuses = self.statuses.read().map_err(lock_error)?;
+        Ok(statuses.get(&id).cloned())
+    }
+
+    async fn insert(&self, status: &Status) -> Result<(), Error> {
+        let mut statuses = self.statuses.write().map_err(lock_error)?;
+        if statuses.contains_key(&status.id) {
+            return Err(Error::Database(format!(
+                "duplicate key error: status {}",
+                status.id
+            )));
+        }
+        statuses.insert(status.id, status.clone());
+        Ok(())
+    }
+
+    async fn find_and_update_state(
+        &self,
+        status: &Status,
+        from: StatusState,
+    ) -> Result<Option<Status>, Error> {
+        let is_decision = from == StatusState::Pending && status.state != StatusState::Expired;
+        let now = Utc::now();
+
+        let mut statuses = self.statuses.write().map_err(lock_error)?;
+        Ok(statuses
+            .get_mut(&status.id)
+            .filter(|existing| existing.state == from)
+            .filter(|existing| !is_decision || existing.expires_at > now)
+            .map(|existing| {
+                existing.state = status.state;
+                existing.decided_by = status.decided_by;
+                existing.decided_at = status.decided_at;
+                existing.reason = status.reason.clone();
+                existing.updated_at = status.updated_at;
+                existing.clone()
+            }))
+    }
+
+    async fn find_stale_pending(&self) -> Result<Vec<Status>, Error> {
+        let now = Utc::now();
+
+        let statuses = self.statuses.read().map_err(lock_error)?;
+        Ok(statuses
+            .values()
+            .filter(|status| status.state == StatusState::Pending && status.expires_at <= now)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_pending_by_camera(&self, camera_id: Uuid) -> Result<Vec<Status>, Error> {
+        let statuses = self.statuses.read().map_err(lock_error)?;
+        let pictures = self.pictures.read().map_err(lock_error)?;
+        let mut pending: Vec<Status> = statuses
+            .values()
+            .filter(|status| status.state == StatusState::Pending)
+            .filter(|status| {
+                pictures
+                    .get(&status.picture_id)
+                    .is_some_and(|picture| picture.device_id == Some(camera_id))
+            })
+            .cloned()
+            .collect();
+        pending.sort_by_key(|status| std::cmp::Reverse(status.created_at));
+        Ok(pending)
+    }
+
+    async fn count_pending(&self) -> Result<u64, Error> {
+        let statuses = self.statuses.read().map_err(lock_error)?;
+        Ok(statuses
+            .values()
+            .filter(|status| status.state == StatusState::Pending)
+            .count() as u64)
+    }
+}
+
+#[async_trait]
+impl PictureRepository for InMemoryRepository {
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Picture>, Error> {
+        let pictures = self.pictures.read().map_err(lock_error)?;
+        Ok(pictures.get(&id).cloned())
+    }
+
+    async fn find_by_user_id(&self, user_id: Uuid) -> Result<Vec<Picture>, Error> {
+        let pictures = self.pictures.read().map_err(lock_error)?;
+        let mut user_pictures: Vec<Picture> = pictures
+            .values()
+            .filter(|picture| picture.user_id == user_id)
+            .cloned()
+            .collect();
+        user_pictures.sort_by_key(|picture| picture.created_at);
+
+        Ok(user_pictures)
+    }
+
+    async fn find_by_device_ids(&self, device_ids: &[Uuid]) -> Result<Vec<Picture>, Error> {
+        let pictures = self.pictures.read().map_err(lock_error)?;
+        let mut device_pictures: Vec<Picture> = pictures
+            .values()
+            .filter(|picture| {
+                picture
+                    .device_id
+                    .is_some_and(|device_id| device_ids.contains(&device_id))
+            })
+            .cloned()
+            .collect();
+        device_pictures.sort_by_key(|picture| picture.created_at);
+
+        Ok(device_pictures)
+    }
+
+    async fn insert(&self, picture: &Picture) -> Result<(), Error> {
+        let mut pictures = self.pictures.write().map_err(lock_error)?;
+        if pictures.contains_key(&picture.id) {
+            return Err(Error::Database(format!(
+                "duplicate key error: picture {}",
+                picture.id
+            )));
+        }
+        pictures.insert(picture.id, picture.clone());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserRepository for InMemoryRepository {
+    async fn insert(&self, user: &User) -> Result<(), Error> {
+        let mut users = self.users.write().map_err(lock_error)?;
+        if users.contains_key(&user.id) {
+            return Err(Error::Database(format!(
+                "duplicate key error: user {}",
+                user.id
+            )));
+        }
+        users.insert(user.id, user.clone());
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, Error> {
+        let users = self.users.read().map_err(lock_error)?;
+        Ok(users.get(&id).cloned())
+    }
+
+    async fn find_by_identity(&self, identity: &UserIdentity) -> Result<Option<User>, Error> {
+        let users = self.users.read().map_err(lock_error)?;
+        Ok(users
+            .values()
+            .find(|user| user.identities.contains(identity))
+            .cloned())
+    }
+
+    async fn get_by_google_id(&self, google_id: String) -> Result<Option<User>, Error> {
+        let users = self.users.read().map_err(lock_error)?;
+        Ok(users
+            .values()
+            .find(|user| user.google_id.as_ref() == Some(&google_id))
+            .cloned())
+    }
+
+    async fn add_identity(&self, id: Uuid, identity: &UserIdentity) -> Result<(), Error> {
+        let mut users = self.users.write().map_err(lock_error)?;
+        if let Some(user) = users.get_mut(&id) {
+            if !user.identities.contains(identity) {
+                user.identities.push(identity.clone());
+                user.updated_at = Some(Local::now());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DeviceRepository for InMemoryRepository {
+    async fn insert(&self, device: &Device) -> Result<(), Error> {
+        let mut devices = self.devices.write().map_err(lock_error)?;
+        if devices.contains_key(&device.id) {
+            return Err(Error::Database(format!(
+                "duplicate key error: device {}",
+                device.id
+            )));
+        }
+        devices.insert(device.id, device.clone());
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Device>, Error> {
+        let devices = self.devices.read().map_err(lock_error)?;
+        Ok(devices.get(&id).cloned())
+    }
+
+    async fn find_by_user_id(&self, user_id: Uuid) -> Result<Vec<Device>, Error> {
+        let devices = self.devices.read().map_err(lock_error)?;
+        let mut user_devices: Vec<Device> = devices
+            .values()
+            .filter(|device| device.user_id == user_id)
+            .cloned()
+            .collect();
+        user_devices.sort_by_key(|device| device.created_at);
+
+        Ok(user_devices)
+    }
+
+    async fn find_by_household_id(&self, household_id: Uuid) -> Result<Vec<Device>, Error> {
+        let devices = self.devices.read().map_err(lock_error)?;
+        let mut household_devices: Vec<Device> = devices
+            .values()
+            .filter(|device| device.household_id == Some(household_id))
+            .cloned()
+            .collect();
+        household_devices.sort_by_key(|device| device.created_at);
+
+        Ok(household_devices)
+    }
+
+    async fn find_by_camera_id(&self, camera_id: Uuid) -> Result<Vec<Device>, Error> {
+        let devices = self.devices.read().map_err(lock_error)?;
+        Ok(devices
+            .values()
+            .filter(|device| device.camera_id == Some(camera_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn find_all(&self) -> Result<Vec<Device>, Error> {
+        let devices = self.devices.read().map_err(lock_error)?;
+        Ok(devices.values().cloned().collect())
+    }
+
+    async fn update_name(&self, id: Uuid, name: String) -> Result<Option<Device>, Error> {
+        let mut devices = self.devices.write().map_err(lock_error)?;
+        Ok(devices.get_mut(&id).map(|device| {
+            device.name = name;
+            device.updated_at = Local::now();
+            device.clone()
+        }))
+    }
+
+    async fn update_camera_id(
+        &self,
+        id: Uuid,
+        camera_id: Option<Uuid>,
+    ) -> Result<Option<Device>, Error> {
+        let mut devices = self.devices.write().map_err(lock_error)?;
+        Ok(devices.get_mut(&id).map(|device| {
+            device.camera_id = camera_id;
+            device.updated_at = Local::now();
+            device.clone()
+        }))
+    }
+
+    async fn update_callback_url(
+        &self,
+        id: Uuid,
+        callback_url: String,
+    ) -> Result<Option<Device>, Error> {
+        let mut devices = self.devices.write().map_err(lock_error)?;
+        Ok(devices.get_mut(&id).map(|device| {
+            device.callback_url = Some(callback_url);
+            device.updated_at = Local::now();
+            device.clone()
+        }))
+    }
+
+    async fn revoke(&self, id: Uuid) -> Result<Option<Device>, Error> {
+        let mut devices = self.devices.write().map_err(lock_error)?;
+        Ok(devices.get_mut(&id).map(|device| {
+            let now = Local::now();
+            device.revoked_at = Some(now);
+            device.updated_at = now;
+            device.clone()
+        }))
+    }
+
+    async fn assign_household(&self, user_id: Uuid, household_id: Uuid) -> Result<u64, Error> {
+        let mut devices = self.devices.write().map_err(lock_error)?;
+        let mut assigned = 0;
+        for device in devices
+            .values_mut()
+            .filter(|device| device.user_id == user_id && device.household_id.is_none())
+        {
+            device.household_id = Some(household_id);
+            device.updated_at = Local::now();
+            assigned += 1;
+        }
+        Ok(assigned)
+    }
+}
+
+#[async_trait]
+impl DeliveryRepository for InMemoryRepository {
+    async fn insert(&self, delivery: &Delivery) -> Result<(), Error> {
+        let mut deliveries = self.deliveries.write().map_err(lock_error)?;
+        if deliveries.contains_key(&delivery.id) {
+            return Err(Error::Database(format!(
+                "duplicate key error: delivery {}",
+                delivery.id
+            )));
+        }
+        deliveries.insert(delivery.id, delivery.clone());
+        Ok(())
+    }
+
+    async fn update_claimed(&self, delivery: &Delivery) -> Result<bool, Error> {
+        let mut deliveries = self.deliveries.write().map_err(lock_error)?;
+        match deliveries.get_mut(&delivery.id) {
+            Some(existing)
+                if existing.state == DeliveryState::Pending
+                    && existing.attempts == delivery.attempts =>
+            {
+                *existing = delivery.clone();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn find_by_status_id(&self, status_id: Uuid) -> Result<Vec<Delivery>, Error> {
+        let deliveries = self.deliveries.read().map_err(lock_error)?;
+        let mut status_deliveries: Vec<Delivery> = deliveries
+            .values()
+            .filter(|delivery| delivery.status_id == status_id)
+            .cloned()
+            .collect();
+        status_deliveries.sort_by_key(|delivery| delivery.created_at);
+
+        Ok(status_deliveries)
+    }
+
+    async fn find_pending(
+        &self,
+        status_id: Uuid,
+        device_id: Uuid,
+    ) -> Result<Option<Delivery>, Error> {
+        let deliveries = self.deliveries.read().map_err(lock_error)?;
+        Ok(deliveries
+            .values()
+            .find(|delivery| {
+                delivery.status_id == status_id
+                    && delivery.device_id == device_id
+                    && delivery.state == DeliveryState::Pending
+            })
+            .cloned())
+    }
+
+    async fn supersede_pending(&self, status_id: Uuid, device_id: Uuid) -> Result<u64, Error> {
+        let mut deliveries = self.deliveries.write().map_err(lock_error)?;
+        let mut modified = 0;
+        for delivery in deliveries.values_mut().filter(|delivery| {
+            delivery.status_id == status_id
+                && delivery.device_id == device_id
+                && delivery.state == DeliveryState::Pending
+        }) {
+            delivery.state = DeliveryState::Superseded;
+            delivery.updated_at = Utc::now();
+            modified += 1;
+        }
+
+        Ok(modified)
+    }
+
+    async fn claim_due(&self, lease: Duration) -> Result<Option<Delivery>, Error> {
+        let now = Utc::now();
+        let lease = chrono::Duration::from_std(lease).map_err(|e| Error::Parse(e.to_string()))?;
+
+        let mut deliveries = self.deliveries.write().map_err(lock_error)?;
+        Ok(deliveries
+            .values_mut()
+            .filter(|delivery| {
+                delivery.state == DeliveryState::Pending
+                    && delivery.next_attempt_at <= now
+                    && delivery.expires_at > now
+            })
+            .min_by_key(|delivery| delivery.next_attempt_at)
+            .map(|delivery| {
+                delivery.next_attempt_at = now + lease;
+                delivery.updated_at = now;
+                delivery.attempts += 1;
+                delivery.clone()
+            }))
+    }
+
+    async fn expire_stale(&self) -> Result<u64, Error> {
+        let now = Utc::now();
+
+        let mut deliveries = self.deliveries.write().map_err(lock_error)?;
+        let mut modified = 0;
+        for delivery in deliveries.values_mut().filter(|delivery| {
+            delivery.state == DeliveryState::Pending && delivery.expires_at <= now
+        }) {
+            delivery.state = DeliveryState::Expired;
+            delivery.updated_at = now;
+            modified += 1;
+        }
+
+        Ok(modified)
+    }
+}
+
+#[async_trait]
+impl AuditRepository for InMemoryRepository {
+    async fn last(&self) -> Result<Option<AuditEntry>, Error> {
+        let audit_log = self.audit_log.read().map_err(lock_error)?;
+        Ok(audit_log.last().cloned())
+    }
+
+    async fn append(&self, entry: &AuditEntry) -> Result<(), Error> {
+        let mut audit_log = self.audit_log.write().map_err(lock_error)?;
+        if audit_log
+            .last()
+            .is_some_and(|last| last.sequence >= entry.sequence)
+        {
+            return Err(Error::Conflict(format!(
+                "audit entry {} already exists",
+                entry.sequence
+            )));
+        }
+        audit_log.push(entry.clone());
+        Ok(())
+    }
+
+    async fn find(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, Error> {
+        let audit_log = self.audit_log.read().map_err(lock_error)?;
+        Ok(audit_log
+            .iter()
+            .rev()
+            .filter(|entry| {
+                filter
+                    .scope
+                    .as_ref()
+                    .is_none_or(|scope| scope.contains(entry))
+            })
+            .filter(|entry| filter.action.is_none_or(|action| entry.action == action))
+            .filter(|entry| filter.actor.is_none_or(|actor| entry.actor == Some(actor)))
+            .filter(|entry| {
+                filter
+                    .device_id
+                    .is_none_or(|device_id| entry.device_id == Some(device_id))
+            })
+            .filter(|entry| {
+                filter
+                    .target
+                    .as_ref()
+                    .is_none_or(|target| entry.target.as_ref() == Some(target))
+            })
+            .filter(|entry| filter.from.is_none_or(|from| entry.created_at >= from))
+            .filter(|entry| filter.to.is_none_or(|to| entry.created_at <= to))
+            .take(filter.limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_from(&self, sequence: i64, limit: i64) -> Result<Vec<AuditEntry>, Error> {
+        let audit_log = self.audit_log.read().map_err(lock_error)?;
+        Ok(audit_log
+            .iter()
+            .filter(|entry| entry.sequence >= sequence)
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for InMemoryRepository {
+    async fn insert(&self, webhook: &Webhook) -> Result<(), Error> {
+        let mut webhooks = self.webhooks.write().map_err(lock_error)?;
+        if webhooks.contains_key(&webhook.id) {
+            return Err(Error::Database(format!(
+                "duplicate key error: webhook {}",
+                webhook.id
+            )));
+        }
+        webhooks.insert(webhook.id, webhook.clone());
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Webhook>, Error> {
+        let webhooks = self.webhooks.read().map_err(lock_error)?;
+        Ok(webhooks.get(&id).cloned())
+    }
+
+    async fn find_by_user_id(&self, user_id: Uuid) -> Result<Vec<Webhook>, Error> {
+        let webhooks = self.webhooks.read().map_err(lock_error)?;
+        Ok(webhooks
+            .values()
+            .filter(|webhook| webhook.user_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_subscribed(
+        &self,
+        user_id: Uuid,
+        event: EventKind,
+    ) -> Result<Vec<Webhook>, Error> {
+        let webhooks = self.webhooks.read().map_err(lock_error)?;
+        Ok(webhooks
+            .values()
+            .filter(|webhook| webhook.user_id == user_id && webhook.events.contains(&event))
+            .cloned()
+            .collect())
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), Error> {
+        let mut webhooks = self.webhooks.write().map_err(lock_error)?;
+        webhooks.remove(&id);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl WebhookDeliveryRepository for InMemoryRepository {
+    async fn insert(&self, delivery: &WebhookDelivery) -> Result<(), Error> {
+        let mut deliveries = self.webhook_deliveries.write().map_err(lock_error)?;
+        if deliveries.contains_key(&delivery.id) {
+            return Err(Error::Database(format!(
+                "duplicate key error: webhook delivery {}",
+                delivery.id
+            )));
+        }
+        deliveries.insert(delivery.id, delivery.clone());
+        Ok(())
+    }
+
+    async fn update_claimed(&self, delivery: &WebhookDelivery) -> Result<bool, Error> {
+        let mut deliveries = self.webhook_deliveries.write().map_err(lock_error)?;
+        match deliveries.get_mut(&delivery.id) {
+            Some(existing)
+                if existing.state == DeliveryState::Pending
+                    && existing.attempts == delivery.attempts =>
+            {
+                *existing = delivery.clone();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn find_by_webhook_id(
+        &self,
+        webhook_id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, Error> {
+        let deliveries = self.webhook_deliveries.read().map_err(lock_error)?;
+        let mut webhook_deliveries: Vec<WebhookDelivery> = deliveries
+            .values()
+            .filter(|delivery| delivery.webhook_id == webhook_id)
+            .cloned()
+            .collect();
+        webhook_deliveries.sort_by_key(|delivery| std::cmp::Reverse(delivery.created_at));
+        webhook_deliveries.truncate(limit.max(0) as usize);
+
+        Ok(webhook_deliveries)
+    }
+
+    async fn claim_due(&self, lease: Duration) -> Result<Option<WebhookDelivery>, Error> {
+        let now = Utc::now();
+        let lease = chrono::Duration::from_std(lease).map_err(|e| Error::Parse(e.to_string()))?;
+
+        let mut deliveries = self.webhook_deliveries.write().map_err(lock_error)?;
+        Ok(deliveries
+            .values_mut()
+            .filter(|delivery| {
+                delivery.state == DeliveryState::Pending && delivery.next_attempt_at <= now
+            })
+            .min_by_key(|delivery| delivery.next_attempt_at)
+            .map(|delivery| {
+                delivery.next_attempt_at = now + lease;
+                delivery.updated_at = now;
+                delivery.attempts += 1;
+                delivery.clone()
+            }))
+    }
+}
+
+#[async_trait]
+impl StorageRepository for InMemoryRepository {
+    async fn upload_file(&self, name: &str, data: Vec<u8>) -> Result<String, Error> {
+        let mut files = self.files.write().map_err(storage_lock_error)?;
+        files.insert(name.to_string(), data);
+
+        Ok(format!("{}/{}", MEMORY_STORAGE_BASE_URL, name))
+    }
+
+    async fn download(&self, name: &str) -> Result<Vec<u8>, Error> {
+        let files = self.files.read().map_err(storage_lock_error)?;
+        files
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::NotFound(format!("object not found: {}", name)))
+    }
+
+    async fn delete(&self, name: &str) -> Result<(), Error> {
+        let mut files = self.files.write().map_err(storage_lock_error)?;
+        files
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| Error::NotFound(format!("object not found: {}", name)))
+    }
+
+    async fn exists(&self, name: &str) -> Result<bool, Error> {
+        let files = self.files.read().map_err(storage_lock_error)?;
+        Ok(files.contains_key(name))
+    }
+
+    // Nothing can be served from memory, the url only identifies the object.
+    async fn signed_url(&self, name: &str, _expires_in: Duration) -> Result<String, Error> {
+        Ok(format!("{}/{}", MEMORY_STORAGE_BASE_URL, name))
+    }
+}
+
+#[async_trait]
+impl SessionRepository for InMemoryRepository {
+    async fn insert(&self, session: &Session) -> Result<(), Error> {
+        let mut sessions = self.sessions.write().map_err(lock_error)?;
+        // Expired sessions are dropped as they would be by the MongoDB TTL index.
+        let now = Utc::now();
+        sessions.retain(|_, session| session.expires_at > now);
+        if sessions.contains_key(&session.id) {
+            return Err(Error::Database(format!(
+                "duplicate key error: session {}",
+                session.id
+            )));
+        }
+        sessions.insert(session.id, session.clone());
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Session>, Error> {
+        let sessions = self.sessions.read().map_err(lock_error)?;
+        Ok(sessions.get(&id).cloned())
+    }
+
+    async fn find_by_token_hash(&self, token_hash: &str) -> Result<Option<Session>, Error> {
+        let sessions = self.sessions.read().map_err(lock_error)?;
+        Ok(sessions
+            .values()
+            .find(|session| {
+                session.refresh_token_hash == token_hash
+                    || session
+                        .rotated_token_hashes
+                        .iter()
+                        .any(|hash| hash == token_hash)
+            })
+            .cloned())
+    }
+
+    async fn rotate(
+        &self,
+        id: Uuid,
+        from_hash: &str,
+        to_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<Session>, Error> {
+        let mut sessions = self.sessions.write().map_err(lock_error)?;
+        Ok(sessions
+            .get_mut(&id)
+            .filter(|session| {
+                session.refresh_token_hash == from_hash && session.revoked_at.is_none()
+            })
+            .map(|session| {
+                session.rotated_token_hashes.push(from_hash.to_string());
+                let forgotten = session
+                    .rotated_token_hashes
+                    .len()
+                    .saturating_sub(MAX_ROTATED_TOKENS);
+                session.rotated_token_hashes.drain(..forgotten);
+                session.refresh_token_hash = to_hash.to_string();
+                session.expires_at = expires_at;
+                session.updated_at = Local::now();
+                session.clone()
+            }))
+    }
+
+    async fn revoke(&self, id: Uuid) -> Result<(), Error> {
+        let mut sessions = self.sessions.write().map_err(lock_error)?;
+        if let Some(session) = sessions
+            .get_mut(&id)
+            .filter(|session| session.revoked_at.is_none())
+        {
+            let now = Local::now();
+            session.revoked_at = Some(now);
+            session.updated_at = now;
+        }
+        Ok(())
+    }
+
+    async fn revoke_by_user_id(&self, user_id: Uuid) -> Result<u64, Error> {
+        let mut sessions = self.sessions.write().map_err(lock_error)?;
+        let now = Local::now();
+        let mut revoked = 0;
+        for session in sessions
+            .values_mut()
+            .filter(|session| session.user_id == user_id && session.revoked_at.is_none())
+        {
+            session.revoked_at = Some(now);
+            session.updated_at = now;
+            revoked += 1;
+        }
+        Ok(revoked)
+    }
+}
+
+#[async_trait]
+impl OAuthStateRepository for InMemoryRepository {
+    async fn insert(&self, state: &OAuthState) -> Result<(), Error> {
+        let mut states = self.oauth_states.write().map_err(lock_error)?;
+        if states.contains_key(&state.state) {
+            return Err(Error::Database(
+                "duplicate key error: oauth state".to_string(),
+            ));
+        }
+        states.insert(state.state.clone(), state.clone());
+        Ok(())
+    }
+
+    async fn take(&self, state: &str) -> Result<Option<OAuthState>, Error> {
+        let mut states = self.oauth_states.write().map_err(lock_error)?;
+        Ok(states
+            .remove(state)
+            .filter(|state| state.expires_at > Utc::now()))
+    }
+
+    async fn delete_expired(&self) -> Result<u64, Error> {
+        let now = Utc::now();
+        let mut states = self.oauth_states.write().map_err(lock_error)?;
+        let before = states.len();
+        states.retain(|_, state| state.expires_at > now);
+        Ok((before - states.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl HouseholdRepository for InMemoryRepository {
+    async fn insert(&self, household: &Household) -> Result<(), Error> {
+        let mut households = self.households.write().map_err(lock_error)?;
+        if households.contains_key(&household.id) {
+            return Err(Error::Database(format!(
+                "duplicate key error: household {}",
+                household.id
+            )));
+        }
+        households.insert(household.id, household.clone());
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Household>, Error> {
+        let households = self.households.read().map_err(lock_error)?;
+        Ok(households.get(&id).cloned())
+    }
+
+    async fn find_by_member(&self, user_id: Uuid) -> Result<Vec<Household>, Error> {
+        let households = self.households.read().map_err(lock_error)?;
+        let mut member_households: Vec<Household> = households
+            .values()
+            .filter(|household| household.role_of(user_id).is_some())
+            .cloned()
+            .collect();
+        member_households.sort_by_key(|household| household.created_at);
+
+        Ok(member_households)
+    }
+
+    async fn add_member(
+        &self,
+        id: Uuid,
+        member: &HouseholdMember,
+    ) -> Result<Option<Household>, Error> {
+        let mut households = self.households.write().map_err(lock_error)?;
+        Ok(households
+            .get_mut(&id)
+            .filter(|household| household.role_of(member.user_id).is_none())
+            .map(|household| {
+                household.members.push(member.clone());
+                household.updated_at = Local::now();
+                household.clone()
+            }))
+    }
+
+    async fn update_member_role(
+        &self,
+        id: Uuid,
+        user_id: Uuid,
+        role: HouseholdRole,
+    ) -> Result<Option<Household>, Error> {
+        let mut households = self.households.write().map_err(lock_error)?;
+        Ok(households.get_mut(&id).and_then(|household| {
+            let member = household
+                .members
+                .iter_mut()
+                .find(|member| member.user_id == user_id)?;
+            member.role = role;
+            household.updated_at = Local::now();
+            Some(household.clone())
+        }))
+    }
+
+    async fn remove_member(&self, id: Uuid, user_id: Uuid) -> Result<Option<Household>, Error> {
+        let mut households = self.households.write().map_err(lock_error)?;
+        Ok(households.get_mut(&id).map(|household| {
+            household.members.retain(|member| member.user_id != user_id);
+            household.updated_at = Local::now();
+            household.clone()
+        }))
+    }
+}
+
+#[async_trait]
+impl InvitationRepository for InMemoryRepository {
+    async fn insert(&self, invitation: &Invitation) -> Result<(), Error> {
+        let mut invitations = self.invitations.write().map_err(lock_error)?;
+        if invitations.contains_key(&invitation.id) {
+            return Err(Error::Database(format!(
+                "duplicate key error: invitation {}",
+                invitation.id
+            )));
+        }
+        invitations.insert(invitation.id, invitation.clone());
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Invitation>, Error> {
+        let invitations = self.invitations.read().map_err(lock_error)?;
+        Ok(invitations.get(&id).cloned())
+    }
+
+    async fn find_by_code_hash(&self, code_hash: &str) -> Result<Option<Invitation>, Error> {
+        let invitations = self.invitations.read().map_err(lock_error)?;
+        Ok(invitations
+            .values()
+            .find(|invitation| invitation.code_hash == code_hash)
+            .cloned())
+    }
+
+    async fn find_pending_by_household_id(
+        &self,
+        household_id: Uuid,
+    ) -> Result<Vec<Invitation>, Error> {
+        let invitations = self.invitations.read().map_err(lock_error)?;
+        let mut pending: Vec<Invitation> = invitations
+            .values()
+            .filter(|invitation| invitation.household_id == household_id)
+            .filter(|invitation| invitation.is_pending())
+            .cloned()
+            .collect();
+        pending.sort_by_key(|invitation| std::cmp::Reverse(invitation.expires_at));
+
+        Ok(pending)
+    }
+
+    async fn accept(&self, id: Uuid, user_id: Uuid) -> Result<Option<Invitation>, Error> {
+        let mut invitations = self.invitations.write().map_err(lock_error)?;
+        Ok(invitations
+            .get_mut(&id)
+            .filter(|invitation| invitation.is_pending())
+            .map(|invitation| {
+                invitation.accepted_by = Some(user_id);
+                invitation.accepted_at = Some(Local::now());
+                invitation.clone()
+            }))
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), Error> {
+        let mut invitations = self.invitations.write().map_err(lock_error)?;
+        invitations.remove(&id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DeviceType;
+
+    fn delivery(status_id: Uuid, device_id: Uuid) -> Delivery {
+        Delivery::new(
+            status_id,
+            device_id,
+            "http://192.168.1.20/authorised".to_string(),
+            true,
+            Utc::now() + chrono::Duration::minutes(5),
+            None,
+        )
+    }
+
+    #[actix_web::test]
+    async fn status_updates_only_from_the_expected_state() {
+        let repo: &dyn StatusRepository = &InMemoryRepository::new();
+        let mut status = Status::new(Uuid::new(), Utc::now() + chrono::Duration::minutes(10));
+        repo.insert(&status).await.unwrap();
+        assert!(repo.insert(&status).await.is_err());
+
+        status.state = StatusState::Approved;
+        let updated = repo
+            .find_and_update_state(&status, StatusState::Pending)
+            .await
+            .unwrap();
+        assert_eq!(updated.unwrap().state, StatusState::Approved);
+
+        status.state = StatusState::Denied;
+        let updated = repo
+            .find_and_update_state(&status, StatusState::Pending)
+            .await
+            .unwrap();
+        assert!(updated.is_none());
+        assert_eq!(repo.count_pending().await.unwrap(), 0);
+    }
+
+    #[actix_web::test]
+    async fn overdue_status_only_moves_to_expired() {
+        let repo: &dyn StatusRepository = &InMemoryRepository::new();
+        let mut status = Status::new(Uuid::new(), Utc::now() - chrono::Duration::seconds(1));
+        repo.insert(&status).await.unwrap();
+
+        status.state = StatusState::Approved;
+        let updated = repo
+            .find_and_update_state(&status, StatusState::Pending)
+            .await
+            .unwrap();
+        assert!(updated.is_none());
+
+        status.state = StatusState::Expired;
+        let updated = repo
+            .find_and_update_state(&status, StatusState::Pending)
+            .await
+            .unwrap();
+        assert_eq!(updated.unwrap().state, StatusState::Expired);
+    }
+
+    #[actix_web::test]
+    async fn finds_the_pending_statuses_of_a_camera() {
+        let repo = InMemoryRepository::new();
+        let camera = Device::new(
+            Uuid::new(),
+            Uuid::new(),
+            "door".to_string(),
+            DeviceType::Esp32Cam,
+            "secret".to_string(),
+        );
+        let picture = Picture::new(camera.user_id, camera.id, "a.jpg".to_string());
+        let other_picture = Picture::new(camera.user_id, Uuid::new(), "b.jpg".to_string());
+        PictureRepository::insert(&repo, &picture).await.unwrap();
+        PictureRepository::insert(&repo, &other_picture)
+            .await
+            .unwrap();
+
+        let stale = Status::new(picture.id, Utc::now() - chrono::Duration::seconds(1));
+        let fresh = Status::new(picture.id, Utc::now() + chrono::Duration::minutes(10));
+        let other = Status::new(other_picture.id, Utc::now() + chrono::Duration::minutes(10));
+        for status in [&stale, &fresh, &other] {
+            StatusRepository::insert(&repo, status).await.unwrap();
+        }
+
+        let pending = repo.find_pending_by_camera(camera.id).await.unwrap();
+        assert_eq!(
+            pending.iter().map(|status| status.id).collect::<Vec<_>>(),
+            vec![fresh.id, stale.id]
+        );
+        let stale_pending = repo.find_stale_pending().await.unwrap();
+        assert_eq!(stale_pending.len(), 1);
+        assert_eq!(stale_pending[0].id, stale.id);
+        assert_eq!(repo.count_pending().await.unwrap(), 3);
+    }
+
+    #[actix_web::test]
+    async fn superseded_delivery_drops_the_claimed_outcome() {
+        let repo: &dyn DeliveryRepository = &InMemoryRepository::new();
+        let (status_id, device_id) = (Uuid::new(), Uuid::new());
+        repo.insert(&delivery(status_id, device_id)).await.unwrap();
+
+        let mut claimed = repo
+            .claim_due(Duration::from_secs(30))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(claimed.attempts, 1);
+        // Leased, no other worker gets it meanwhile.
+        assert!(repo
+            .claim_due(Duration::from_secs(30))
+            .await
+            .unwrap()
+            .is_none());
+
+        assert_eq!(
+            repo.supersede_pending(status_id, device_id).await.unwrap(),
+            1
+        );
+        claimed.state = DeliveryState::Delivered;
+        assert!(!repo.update_claimed(&claimed).await.unwrap());
+
+        let deliveries = repo.find_by_status_id(status_id).await.unwrap();
+        assert_eq!(deliveries[0].state, DeliveryState::Superseded);
+        assert!(repo
+            .find_pending(status_id, device_id)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[actix_web::test]
+    async fn claimed_delivery_outcome_is_kept_while_pending() {
+        let repo: &dyn DeliveryRepository = &InMemoryRepository::new();
+        let (status_id, device_id) = (Uuid::new(), Uuid::new());
+        repo.insert(&delivery(status_id, device_id)).await.unwrap();
+
+        let mut claimed = repo
+            .claim_due(Duration::from_secs(30))
+            .await
+            .unwrap()
+            .unwrap();
+        claimed.state = DeliveryState::Delivered;
+        assert!(repo.update_claimed(&claimed).await.unwrap());
+        assert!(!repo.update_claimed(&claimed).await.unwrap());
+    }
+
+    #[actix_web::test]
+    async fn expired_delivery_is_never_claimed() {
+        let repo: &dyn DeliveryRepository = &InMemoryRepository::new();
+        let mut expired = delivery(Uuid::new(), Uuid::new());
+        expired.expires_at = Utc::now() - chrono::Duration::seconds(1);
+        repo.insert(&expired).await.unwrap();
+
+        assert!(repo
+            .claim_due(Duration::from_secs(30))
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(repo.expire_stale().await.unwrap(), 1);
+    }
+
+    #[actix_web::test]
+    async fn rotation_keeps_a_bounded_history() {
+        let repo: &dyn SessionRepository = &InMemoryRepository::new();
+        let expires_at = Utc::now() + chrono::Duration::days(1);
+        let session = Session::new(Uuid::new(), "0".to_string(), expires_at);
+        repo.insert(&session).await.unwrap();
+
+        for i in 0..MAX_ROTATED_TOKENS + 5 {
+            let rotated = repo
+                .rotate(session.id, &i.to_string(), &(i + 1).to_string(), expires_at)
+                .await
+                .unwrap();
+            assert!(rotated.is_some());
+        }
+        // Only the current hash can be rotated.
+        assert!(repo
+            .rotate(session.id, "0", "x", expires_at)
+            .await
+            .unwrap()
+            .is_none());
+
+        let session = repo.find_by_id(session.id).await.unwrap().unwrap();
+        assert_eq!(session.rotated_token_hashes.len(), MAX_ROTATED_TOKENS);
+        assert!(repo.find_by_token_hash("0").await.unwrap().is_none());
+        assert!(repo.find_by_token_hash("5").await.unwrap().is_some());
+    }
+
+    #[actix_web::test]
+    async fn oauth_state_is_taken_once() {
+        let repo: &dyn OAuthStateRepository = &InMemoryRepository::new();
+        let state = OAuthState::new(
+            "state".to_string(),
+            "mock".to_string(),
+            "verifier".to_string(),
+            "nonce".to_string(),
+            Default::default(),
+            None,
+            Utc::now() + chrono::Duration::minutes(10),
+        );
+        repo.insert(&state).await.unwrap();
+
+        assert!(repo.take(&state.state).await.unwrap().is_some());
+        assert!(repo.take(&state.state).await.unwrap().is_none());
+    }
+}
@@ -4,38 +4,236 @@ pub use mongo_repository::MongoRepository;
 mod gcs_repository;
 pub use gcs_repository::GcsRepository;
 
+mod s3_repository;
+pub use s3_repository::S3Repository;
+
+mod local_storage_repository;
+pub use local_storage_repository::LocalStorageRepository;
+
+mod memory_repository;
+pub use memory_repository::InMemoryRepository;
+
+mod timed_repository;
+pub use timed_repository::TimedRepository;
+
 use async_trait::async_trait;
 use bson::Uuid;
+use chrono::{DateTime, Utc};
+use std::time::Duration;
 
 use crate::errors::Error;
-use crate::models::{Picture, Status, User};
+use crate::models::{
+    AuditEntry, AuditFilter, Delivery, Device, EventKind, Household, HouseholdMember,
+    HouseholdRole, Invitation, OAuthState, Picture, Session, Status, StatusState, User,
+    UserIdentity, Webhook, WebhookDelivery,
+};
+
+// Whether the database answers, for the readiness probe.
+#[async_trait]
+pub trait HealthRepository: Send + Sync {
+    async fn ping(&self) -> Result<(), Error>;
+}
 
 #[async_trait]
 pub trait StatusRepository: Send + Sync {
     async fn find_by_id(&self, id: Uuid) -> Result<Option<Status>, Error>;
     // NOTE: We could be using the result of insert operation and return the model.
     async fn insert(&self, status: &Status) -> Result<(), Error>;
-    async fn find_and_update_authorised(
+    // Only applies the change while the status is still in `from`, returns the updated status.
+    // A decision taken on a pending status also requires it to be before its expiry date.
+    async fn find_and_update_state(
         &self,
-        id: Uuid,
-        authorised: bool,
+        status: &Status,
+        from: StatusState,
     ) -> Result<Option<Status>, Error>;
+    // Pending statuses past their expiry date.
+    async fn find_stale_pending(&self) -> Result<Vec<Status>, Error>;
+    // Statuses of the pictures taken by the camera still waiting for a decision, most recent
+    // first.
+    async fn find_pending_by_camera(&self, camera_id: Uuid) -> Result<Vec<Status>, Error>;
+    async fn count_pending(&self) -> Result<u64, Error>;
 }
 
 #[async_trait]
 pub trait PictureRepository: Send + Sync {
     async fn find_by_id(&self, id: Uuid) -> Result<Option<Picture>, Error>;
     async fn find_by_user_id(&self, user_id: Uuid) -> Result<Vec<Picture>, Error>;
+    // Pictures taken by any of the cameras.
+    async fn find_by_device_ids(&self, device_ids: &[Uuid]) -> Result<Vec<Picture>, Error>;
     async fn insert(&self, picture: &Picture) -> Result<(), Error>;
 }
 
 #[async_trait]
 pub trait StorageRepository: Send + Sync {
     async fn upload_file(&self, name: &str, data: Vec<u8>) -> Result<String, Error>;
+    async fn download(&self, name: &str) -> Result<Vec<u8>, Error>;
+    async fn delete(&self, name: &str) -> Result<(), Error>;
+    async fn exists(&self, name: &str) -> Result<bool, Error>;
+    // Time-limited url giving read access to the object without credentials.
+    async fn signed_url(&self, name: &str, expires_in: Duration) -> Result<String, Error>;
+    // Whether the storage answers, for the readiness probe. Only looks an object up by
+    // default, whether it exists doesn't matter.
+    async fn ping(&self) -> Result<(), Error> {
+        self.exists("readiness-probe").await.map(|_| ())
+    }
 }
 
 #[async_trait]
 pub trait UserRepository: Send + Sync {
     async fn insert(&self, user: &User) -> Result<(), Error>;
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, Error>;
+    async fn find_by_identity(&self, identity: &UserIdentity) -> Result<Option<User>, Error>;
+    // Accounts created before identities only have their Google subject.
     async fn get_by_google_id(&self, google_id: String) -> Result<Option<User>, Error>;
+    async fn add_identity(&self, id: Uuid, identity: &UserIdentity) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait SessionRepository: Send + Sync {
+    async fn insert(&self, session: &Session) -> Result<(), Error>;
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Session>, Error>;
+    // The session whose current or rotated refresh token has this hash.
+    async fn find_by_token_hash(&self, token_hash: &str) -> Result<Option<Session>, Error>;
+    // Replaces the refresh token only while `from_hash` is still the current one and the
+    // session isn't revoked, returns the updated session.
+    async fn rotate(
+        &self,
+        id: Uuid,
+        from_hash: &str,
+        to_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<Session>, Error>;
+    async fn revoke(&self, id: Uuid) -> Result<(), Error>;
+    // Revokes every active session of the user, returns how many were.
+    async fn revoke_by_user_id(&self, user_id: Uuid) -> Result<u64, Error>;
+}
+
+#[async_trait]
+pub trait DeviceRepository: Send + Sync {
+    async fn insert(&self, device: &Device) -> Result<(), Error>;
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Device>, Error>;
+    async fn find_by_user_id(&self, user_id: Uuid) -> Result<Vec<Device>, Error>;
+    async fn find_by_household_id(&self, household_id: Uuid) -> Result<Vec<Device>, Error>;
+    async fn find_by_camera_id(&self, camera_id: Uuid) -> Result<Vec<Device>, Error>;
+    // Every device of every user, revoked ones included.
+    async fn find_all(&self) -> Result<Vec<Device>, Error>;
+    // Updates return the updated device, or `None` when the ID is unknown.
+    async fn update_name(&self, id: Uuid, name: String) -> Result<Option<Device>, Error>;
+    async fn update_camera_id(
+        &self,
+        id: Uuid,
+        camera_id: Option<Uuid>,
+    ) -> Result<Option<Device>, Error>;
+    async fn update_callback_url(
+        &self,
+        id: Uuid,
+        callback_url: String,
+    ) -> Result<Option<Device>, Error>;
+    async fn revoke(&self, id: Uuid) -> Result<Option<Device>, Error>;
+    // Moves the devices the user registered before households into the household, returns
+    // how many were.
+    async fn assign_household(&self, user_id: Uuid, household_id: Uuid) -> Result<u64, Error>;
+}
+
+#[async_trait]
+pub trait DeliveryRepository: Send + Sync {
+    async fn insert(&self, delivery: &Delivery) -> Result<(), Error>;
+    // Writes the outcome of an attempt claimed with `claim_due`, only while the delivery is
+    // still pending with the same number of attempts. Returns false when it was superseded
+    // or claimed again meanwhile, the outcome is then dropped.
+    async fn update_claimed(&self, delivery: &Delivery) -> Result<bool, Error>;
+    async fn find_by_status_id(&self, status_id: Uuid) -> Result<Vec<Delivery>, Error>;
+    async fn find_pending(
+        &self,
+        status_id: Uuid,
+        device_id: Uuid,
+    ) -> Result<Option<Delivery>, Error>;
+    async fn supersede_pending(&self, status_id: Uuid, device_id: Uuid) -> Result<u64, Error>;
+    // Atomically takes the next due delivery and pushes its next attempt back by `lease`,
+    // so a delivery is only ever in the hands of a single worker.
+    async fn claim_due(&self, lease: Duration) -> Result<Option<Delivery>, Error>;
+    async fn expire_stale(&self) -> Result<u64, Error>;
+}
+
+// Append-only, entries are never updated nor deleted.
+#[async_trait]
+pub trait AuditRepository: Send + Sync {
+    async fn last(&self) -> Result<Option<AuditEntry>, Error>;
+    // Fails with `Error::Conflict` when another entry already took the sequence number.
+    async fn append(&self, entry: &AuditEntry) -> Result<(), Error>;
+    // Most recent entries first.
+    async fn find(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, Error>;
+    // Entries in chain order, starting at `sequence`.
+    async fn find_from(&self, sequence: i64, limit: i64) -> Result<Vec<AuditEntry>, Error>;
+}
+
+#[async_trait]
+pub trait WebhookRepository: Send + Sync {
+    async fn insert(&self, webhook: &Webhook) -> Result<(), Error>;
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Webhook>, Error>;
+    async fn find_by_user_id(&self, user_id: Uuid) -> Result<Vec<Webhook>, Error>;
+    // Webhooks of the user subscribed to `event`.
+    async fn find_subscribed(&self, user_id: Uuid, event: EventKind)
+        -> Result<Vec<Webhook>, Error>;
+    async fn delete(&self, id: Uuid) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait WebhookDeliveryRepository: Send + Sync {
+    async fn insert(&self, delivery: &WebhookDelivery) -> Result<(), Error>;
+    // Same as `DeliveryRepository::update_claimed`.
+    async fn update_claimed(&self, delivery: &WebhookDelivery) -> Result<bool, Error>;
+    // Most recent first.
+    async fn find_by_webhook_id(
+        &self,
+        webhook_id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, Error>;
+    // Same lease as `DeliveryRepository::claim_due`.
+    async fn claim_due(&self, lease: Duration) -> Result<Option<WebhookDelivery>, Error>;
+}
+
+#[async_trait]
+pub trait OAuthStateRepository: Send + Sync {
+    async fn insert(&self, state: &OAuthState) -> Result<(), Error>;
+    // Removes and returns the state when it exists and hasn't expired, so it's only used once.
+    async fn take(&self, state: &str) -> Result<Option<OAuthState>, Error>;
+    async fn delete_expired(&self) -> Result<u64, Error>;
+}
+
+#[async_trait]
+pub trait HouseholdRepository: Send + Sync {
+    async fn insert(&self, household: &Household) -> Result<(), Error>;
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Household>, Error>;
+    // Households the user is a member of, whatever their role, oldest first.
+    async fn find_by_member(&self, user_id: Uuid) -> Result<Vec<Household>, Error>;
+    // Member updates return the updated household, or `None` when the household is unknown,
+    // or when adding someone already a member.
+    async fn add_member(
+        &self,
+        id: Uuid,
+        member: &HouseholdMember,
+    ) -> Result<Option<Household>, Error>;
+    async fn update_member_role(
+        &self,
+        id: Uuid,
+        user_id: Uuid,
+        role: HouseholdRole,
+    ) -> Result<Option<Household>, Error>;
+    async fn remove_member(&self, id: Uuid, user_id: Uuid) -> Result<Option<Household>, Error>;
+}
+
+#[async_trait]
+pub trait InvitationRepository: Send + Sync {
+    async fn insert(&self, invitation: &Invitation) -> Result<(), Error>;
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Invitation>, Error>;
+    async fn find_by_code_hash(&self, code_hash: &str) -> Result<Option<Invitation>, Error>;
+    // Invitations of the household neither accepted nor expired, most recent first.
+    async fn find_pending_by_household_id(
+        &self,
+        household_id: Uuid,
+    ) -> Result<Vec<Invitation>, Error>;
+    // Marks the invitation accepted only while it's still pending, returns the updated one.
+    async fn accept(&self, id: Uuid, user_id: Uuid) -> Result<Option<Invitation>, Error>;
+    async fn delete(&self, id: Uuid) -> Result<(), Error>;
 }
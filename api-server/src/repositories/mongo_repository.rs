@@ -1,16 +1,39 @@
 use async_trait::async_trait;
 use bson::Uuid;
+use chrono::{DateTime, Local, Utc};
 use futures_util::TryStreamExt;
-use mongodb::{bson::doc, Client, Collection};
+use mongodb::bson::{Bson, DateTime as BsonDateTime, Document};
+use mongodb::options::{IndexOptions, ReturnDocument};
+use mongodb::{bson::doc, Client, Collection, IndexModel};
+use std::time::Duration;
 
-use super::{PictureRepository, StatusRepository};
+use super::{
+    AuditRepository, DeliveryRepository, DeviceRepository, HealthRepository, HouseholdRepository,
+    InvitationRepository, OAuthStateRepository, PictureRepository, SessionRepository,
+    StatusRepository, WebhookDeliveryRepository, WebhookRepository,
+};
 use crate::errors::Error;
-use crate::models::{Picture, Status, User};
+use crate::models::{
+    AuditEntry, AuditFilter, Delivery, DeliveryState, Device, EventKind, Household,
+    HouseholdMember, HouseholdRole, Invitation, OAuthState, Picture, Session, Status, StatusState,
+    User, UserIdentity, Webhook, WebhookDelivery, MAX_ROTATED_TOKENS,
+};
 use crate::repositories::UserRepository;
 
 const STATUS_COLL: &str = "statuses";
 const PICTURE_COLL: &str = "pictures";
 const USER_COLL: &str = "users";
+const DEVICE_COLL: &str = "devices";
+const DELIVERY_COLL: &str = "deliveries";
+const AUDIT_COLL: &str = "audit_log";
+const WEBHOOK_COLL: &str = "webhooks";
+const WEBHOOK_DELIVERY_COLL: &str = "webhook_deliveries";
+const SESSION_COLL: &str = "sessions";
+const OAUTH_STATE_COLL: &str = "oauth_states";
+const HOUSEHOLD_COLL: &str = "households";
+const INVITATION_COLL: &str = "invitations";
+
+const DUPLICATE_KEY_CODE: i32 = 11000;
 
 pub struct MongoRepository {
     client: Client,
@@ -33,6 +56,149 @@ impl MongoRepository {
     fn user_collection(&self) -> Collection<User> {
         self.client.database(&self.db_name).collection(USER_COLL)
     }
+
+    fn device_collection(&self) -> Collection<Device> {
+        self.client.database(&self.db_name).collection(DEVICE_COLL)
+    }
+
+    fn delivery_collection(&self) -> Collection<Delivery> {
+        self.client
+            .database(&self.db_name)
+            .collection(DELIVERY_COLL)
+    }
+
+    fn audit_collection(&self) -> Collection<AuditEntry> {
+        self.client.database(&self.db_name).collection(AUDIT_COLL)
+    }
+
+    fn webhook_collection(&self) -> Collection<Webhook> {
+        self.client.database(&self.db_name).collection(WEBHOOK_COLL)
+    }
+
+    fn webhook_delivery_collection(&self) -> Collection<WebhookDelivery> {
+        self.client
+            .database(&self.db_name)
+            .collection(WEBHOOK_DELIVERY_COLL)
+    }
+
+    fn session_collection(&self) -> Collection<Session> {
+        self.client.database(&self.db_name).collection(SESSION_COLL)
+    }
+
+    fn oauth_state_collection(&self) -> Collection<OAuthState> {
+        self.client
+            .database(&self.db_name)
+            .collection(OAUTH_STATE_COLL)
+    }
+
+    fn household_collection(&self) -> Collection<Household> {
+        self.client
+            .database(&self.db_name)
+            .collection(HOUSEHOLD_COLL)
+    }
+
+    fn invitation_collection(&self) -> Collection<Invitation> {
+        self.client
+            .database(&self.db_name)
+            .collection(INVITATION_COLL)
+    }
+
+    // Indexes the lookups of refresh tokens, and lets MongoDB remove the sessions once they
+    // expire.
+    pub async fn create_indexes(&self) -> Result<(), Error> {
+        let expire_on_date = IndexOptions::builder().expire_after(Duration::ZERO).build();
+
+        self.session_collection()
+            .create_indexes([
+                IndexModel::builder()
+                    .keys(doc! {"refresh_token_hash": 1})
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! {"rotated_token_hashes": 1})
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! {"expires_at": 1})
+                    .options(expire_on_date)
+                    .build(),
+            ])
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    // Statuses stored before their lifecycle only have `authorised`: they're approved when it's
+    // set, denied when it was changed to false and pending otherwise, expiring `pending_timeout`
+    // after their creation so the expiry worker takes care of the stale ones. Returns how many
+    // were migrated.
+    pub async fn migrate_statuses(&self, pending_timeout: Duration) -> Result<u64, Error> {
+        let collection: Collection<Document> =
+            self.client.database(&self.db_name).collection(STATUS_COLL);
+        let pending_timeout =
+            chrono::Duration::from_std(pending_timeout).map_err(|e| Error::Parse(e.to_string()))?;
+
+        let mut cursor = collection
+            .find(doc! {"state": {"$exists": false}})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        let mut migrated = 0;
+        while let Some(status) = cursor
+            .try_next()
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?
+        {
+            let Some(id) = status.get("_id").cloned() else {
+                continue;
+            };
+            let updated_at = status
+                .get("updated_at")
+                .filter(|updated_at| **updated_at != Bson::Null)
+                .cloned();
+            let state = match (status.get_bool("authorised").unwrap_or(false), &updated_at) {
+                (true, _) => StatusState::Approved,
+                (false, Some(_)) => StatusState::Denied,
+                (false, None) => StatusState::Pending,
+            };
+            let created_at = status
+                .get_str("created_at")
+                .ok()
+                .and_then(|created_at| DateTime::parse_from_rfc3339(created_at).ok())
+                .map(|created_at| created_at.with_timezone(&Utc))
+                .unwrap_or_else(Utc::now);
+
+            let mut set = doc! {
+                "state": to_bson(&state)?,
+                "expires_at": BsonDateTime::from_chrono(created_at + pending_timeout),
+            };
+            if let (true, Some(updated_at)) = (state != StatusState::Pending, updated_at) {
+                set.insert("decided_at", updated_at);
+            }
+
+            let result = collection
+                .update_one(
+                    doc! {"_id": id, "state": {"$exists": false}},
+                    doc! {"$set": set, "$unset": {"authorised": ""}},
+                )
+                .await
+                .map_err(|e| Error::Database(e.to_string()))?;
+            migrated += result.modified_count;
+        }
+
+        Ok(migrated)
+    }
+}
+
+// Same check as at startup, see `init_mongo_client`.
+#[async_trait]
+impl HealthRepository for MongoRepository {
+    async fn ping(&self) -> Result<(), Error> {
+        self.client
+            .database(&self.db_name)
+            .run_command(doc! {"ping": 1})
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::Database(e.to_string()))
+    }
 }
 
 #[async_trait]
@@ -52,13 +218,77 @@ impl StatusRepository for MongoRepository {
             .map_err(|e| Error::Database(e.to_string()))
     }
 
-    async fn find_and_update_authorised(
+    async fn find_and_update_state(
         &self,
-        id: Uuid,
-        authorised: bool,
+        status: &Status,
+        from: StatusState,
     ) -> Result<Option<Status>, Error> {
+        let mut filter = doc! {"_id": status.id, "state": to_bson(&from)?};
+        if from == StatusState::Pending && status.state != StatusState::Expired {
+            filter.insert("expires_at", doc! {"$gt": BsonDateTime::now()});
+        }
+
         self.status_collection()
-            .find_one_and_update(doc! {"_id": id}, doc! {"$set": {"authorised": authorised}})
+            .find_one_and_update(
+                filter,
+                doc! {"$set": {
+                    "state": to_bson(&status.state)?,
+                    "decided_by": status.decided_by,
+                    "decided_at": to_bson(&status.decided_at)?,
+                    "reason": status.reason.clone(),
+                    "updated_at": to_bson(&status.updated_at)?,
+                }},
+            )
+            .return_document(ReturnDocument::After)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_stale_pending(&self) -> Result<Vec<Status>, Error> {
+        let cursor = self
+            .status_collection()
+            .find(doc! {
+                "state": to_bson(&StatusState::Pending)?,
+                "expires_at": {"$lte": BsonDateTime::now()},
+            })
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        cursor
+            .try_collect()
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    // Joined with the pictures, which know the camera.
+    async fn find_pending_by_camera(&self, camera_id: Uuid) -> Result<Vec<Status>, Error> {
+        let cursor = self
+            .status_collection()
+            .aggregate(vec![
+                doc! {"$match": {"state": to_bson(&StatusState::Pending)?}},
+                doc! {"$lookup": {
+                    "from": PICTURE_COLL,
+                    "localField": "picture_id",
+                    "foreignField": "_id",
+                    "as": "picture",
+                }},
+                doc! {"$match": {"picture.device_id": camera_id}},
+                doc! {"$project": {"picture": 0}},
+                doc! {"$sort": {"created_at": -1}},
+            ])
+            .with_type::<Status>()
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        cursor
+            .try_collect()
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn count_pending(&self) -> Result<u64, Error> {
+        self.status_collection()
+            .count_documents(doc! {"state": to_bson(&StatusState::Pending)?})
             .await
             .map_err(|e| Error::Database(e.to_string()))
     }
@@ -88,6 +318,19 @@ impl PictureRepository for MongoRepository {
         Ok(pictures)
     }
 
+    async fn find_by_device_ids(&self, device_ids: &[Uuid]) -> Result<Vec<Picture>, Error> {
+        let cursor = self
+            .picture_collection()
+            .find(doc! {"device_id": {"$in": device_ids}})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        cursor
+            .try_collect()
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
     async fn insert(&self, picture: &Picture) -> Result<(), Error> {
         self.picture_collection()
             .insert_one(picture)
@@ -107,10 +350,813 @@ impl UserRepository for MongoRepository {
             .map_err(|e| Error::Database(e.to_string()))
     }
 
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, Error> {
+        self.user_collection()
+            .find_one(doc! {"_id": id})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_by_identity(&self, identity: &UserIdentity) -> Result<Option<User>, Error> {
+        self.user_collection()
+            .find_one(doc! {
+                "identities": {
+                    "$elemMatch": {"provider": &identity.provider, "subject": &identity.subject}
+                }
+            })
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
     async fn get_by_google_id(&self, google_id: String) -> Result<Option<User>, Error> {
         self.user_collection()
             .find_one(doc! {"google_id": google_id})
             .await
             .map_err(|e| Error::Database(e.to_string()))
     }
+
+    async fn add_identity(&self, id: Uuid, identity: &UserIdentity) -> Result<(), Error> {
+        let now = bson::to_bson(&Local::now()).map_err(|e| Error::Parse(e.to_string()))?;
+
+        self.user_collection()
+            .update_one(
+                doc! {"_id": id},
+                doc! {
+                    "$addToSet": {"identities": to_bson(identity)?},
+                    "$set": {"updated_at": now},
+                },
+            )
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl DeviceRepository for MongoRepository {
+    async fn insert(&self, device: &Device) -> Result<(), Error> {
+        self.device_collection()
+            .insert_one(device)
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Device>, Error> {
+        self.device_collection()
+            .find_one(doc! {"_id": id})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_by_user_id(&self, user_id: Uuid) -> Result<Vec<Device>, Error> {
+        let cursor = self
+            .device_collection()
+            .find(doc! {"user_id": user_id})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        cursor
+            .try_collect()
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_by_household_id(&self, household_id: Uuid) -> Result<Vec<Device>, Error> {
+        let cursor = self
+            .device_collection()
+            .find(doc! {"household_id": household_id})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        cursor
+            .try_collect()
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_by_camera_id(&self, camera_id: Uuid) -> Result<Vec<Device>, Error> {
+        let cursor = self
+            .device_collection()
+            .find(doc! {"camera_id": camera_id})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        cursor
+            .try_collect()
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_all(&self) -> Result<Vec<Device>, Error> {
+        let cursor = self
+            .device_collection()
+            .find(doc! {})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        cursor
+            .try_collect()
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn update_name(&self, id: Uuid, name: String) -> Result<Option<Device>, Error> {
+        let now = bson::to_bson(&Local::now()).map_err(|e| Error::Parse(e.to_string()))?;
+
+        self.device_collection()
+            .find_one_and_update(
+                doc! {"_id": id},
+                doc! {"$set": {"name": name, "updated_at": now}},
+            )
+            .return_document(ReturnDocument::After)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn update_camera_id(
+        &self,
+        id: Uuid,
+        camera_id: Option<Uuid>,
+    ) -> Result<Option<Device>, Error> {
+        let now = bson::to_bson(&Local::now()).map_err(|e| Error::Parse(e.to_string()))?;
+
+        self.device_collection()
+            .find_one_and_update(
+                doc! {"_id": id},
+                doc! {"$set": {"camera_id": camera_id, "updated_at": now}},
+            )
+            .return_document(ReturnDocument::After)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn update_callback_url(
+        &self,
+        id: Uuid,
+        callback_url: String,
+    ) -> Result<Option<Device>, Error> {
+        let now = bson::to_bson(&Local::now()).map_err(|e| Error::Parse(e.to_string()))?;
+
+        self.device_collection()
+            .find_one_and_update(
+                doc! {"_id": id},
+                doc! {"$set": {"callback_url": callback_url, "updated_at": now}},
+            )
+            .return_document(ReturnDocument::After)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn revoke(&self, id: Uuid) -> Result<Option<Device>, Error> {
+        let now = bson::to_bson(&Local::now()).map_err(|e| Error::Parse(e.to_string()))?;
+
+        self.device_collection()
+            .find_one_and_update(
+                doc! {"_id": id},
+                doc! {"$set": {"revoked_at": now.clone(), "updated_at": now}},
+            )
+            .return_document(ReturnDocument::After)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn assign_household(&self, user_id: Uuid, household_id: Uuid) -> Result<u64, Error> {
+        let now = bson::to_bson(&Local::now()).map_err(|e| Error::Parse(e.to_string()))?;
+
+        // A missing field matches `null` as well.
+        self.device_collection()
+            .update_many(
+                doc! {"user_id": user_id, "household_id": null},
+                doc! {"$set": {"household_id": household_id, "updated_at": now}},
+            )
+            .await
+            .map(|result| result.modified_count)
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+}
+
+fn to_bson<T: serde::Serialize>(value: &T) -> Result<bson::Bson, Error> {
+    bson::to_bson(value).map_err(|e| Error::Parse(e.to_string()))
+}
+
+#[async_trait]
+impl DeliveryRepository for MongoRepository {
+    async fn insert(&self, delivery: &Delivery) -> Result<(), Error> {
+        self.delivery_collection()
+            .insert_one(delivery)
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn update_claimed(&self, delivery: &Delivery) -> Result<bool, Error> {
+        self.delivery_collection()
+            .update_one(
+                doc! {
+                    "_id": delivery.id,
+                    "state": to_bson(&DeliveryState::Pending)?,
+                    "attempts": delivery.attempts,
+                },
+                doc! {"$set": {
+                    "state": to_bson(&delivery.state)?,
+                    "last_error": delivery.last_error.clone(),
+                    "next_attempt_at": BsonDateTime::from_chrono(delivery.next_attempt_at),
+                    "updated_at": BsonDateTime::from_chrono(delivery.updated_at),
+                }},
+            )
+            .await
+            .map(|result| result.matched_count > 0)
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_by_status_id(&self, status_id: Uuid) -> Result<Vec<Delivery>, Error> {
+        let cursor = self
+            .delivery_collection()
+            .find(doc! {"status_id": status_id})
+            .sort(doc! {"created_at": 1})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        cursor
+            .try_collect()
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_pending(
+        &self,
+        status_id: Uuid,
+        device_id: Uuid,
+    ) -> Result<Option<Delivery>, Error> {
+        self.delivery_collection()
+            .find_one(doc! {
+                "status_id": status_id,
+                "device_id": device_id,
+                "state": to_bson(&DeliveryState::Pending)?,
+            })
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn supersede_pending(&self, status_id: Uuid, device_id: Uuid) -> Result<u64, Error> {
+        self.delivery_collection()
+            .update_many(
+                doc! {
+                    "status_id": status_id,
+                    "device_id": device_id,
+                    "state": to_bson(&DeliveryState::Pending)?,
+                },
+                doc! {"$set": {
+                    "state": to_bson(&DeliveryState::Superseded)?,
+                    "updated_at": BsonDateTime::now(),
+                }},
+            )
+            .await
+            .map(|result| result.modified_count)
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn claim_due(&self, lease: Duration) -> Result<Option<Delivery>, Error> {
+        let now = Utc::now();
+        let lease = chrono::Duration::from_std(lease).map_err(|e| Error::Parse(e.to_string()))?;
+
+        self.delivery_collection()
+            .find_one_and_update(
+                doc! {
+                    "state": to_bson(&DeliveryState::Pending)?,
+                    "next_attempt_at": {"$lte": BsonDateTime::from_chrono(now)},
+                    "expires_at": {"$gt": BsonDateTime::from_chrono(now)},
+                },
+                doc! {
+                    "$set": {
+                        "next_attempt_at": BsonDateTime::from_chrono(now + lease),
+                        "updated_at": BsonDateTime::from_chrono(now),
+                    },
+                    "$inc": {"attempts": 1},
+                },
+            )
+            .sort(doc! {"next_attempt_at": 1})
+            .return_document(ReturnDocument::After)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn expire_stale(&self) -> Result<u64, Error> {
+        let now = BsonDateTime::now();
+
+        self.delivery_collection()
+            .update_many(
+                doc! {
+                    "state": to_bson(&DeliveryState::Pending)?,
+                    "expires_at": {"$lte": now},
+                },
+                doc! {"$set": {
+                    "state": to_bson(&DeliveryState::Expired)?,
+                    "updated_at": now,
+                }},
+            )
+            .await
+            .map(|result| result.modified_count)
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+}
+
+fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    matches!(
+        error.kind.as_ref(),
+        mongodb::error::ErrorKind::Write(mongodb::error::WriteFailure::WriteError(write_error))
+            if write_error.code == DUPLICATE_KEY_CODE
+    )
+}
+
+#[async_trait]
+impl AuditRepository for MongoRepository {
+    async fn last(&self) -> Result<Option<AuditEntry>, Error> {
+        self.audit_collection()
+            .find_one(doc! {})
+            .sort(doc! {"_id": -1})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn append(&self, entry: &AuditEntry) -> Result<(), Error> {
+        // The sequence number is the `_id`, so two writers can't both extend the chain.
+        self.audit_collection()
+            .insert_one(entry)
+            .await
+            .map(|_| ())
+            .map_err(|e| {
+                if is_duplicate_key(&e) {
+                    Error::Conflict(format!("audit entry {} already exists", entry.sequence))
+                } else {
+                    Error::Database(e.to_string())
+                }
+            })
+    }
+
+    async fn find(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, Error> {
+        let mut query = doc! {};
+        if let Some(scope) = &filter.scope {
+            query.insert(
+                "$or",
+                vec![
+                    doc! {"actor": scope.actor},
+                    doc! {"household_id": {"$in": scope.household_ids.clone()}},
+                ],
+            );
+        }
+        if let Some(action) = &filter.action {
+            query.insert("action", to_bson(action)?);
+        }
+        if let Some(actor) = filter.actor {
+            query.insert("actor", actor);
+        }
+        if let Some(device_id) = filter.device_id {
+            query.insert("device_id", device_id);
+        }
+        if let Some(target) = &filter.target {
+            query.insert("target", target);
+        }
+        let mut created_at = doc! {};
+        if let Some(from) = filter.from {
+            created_at.insert("$gte", BsonDateTime::from_chrono(from));
+        }
+        if let Some(to) = filter.to {
+            created_at.insert("$lte", BsonDateTime::from_chrono(to));
+        }
+        if !created_at.is_empty() {
+            query.insert("created_at", created_at);
+        }
+
+        let cursor = self
+            .audit_collection()
+            .find(query)
+            .sort(doc! {"_id": -1})
+            .limit(filter.limit)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        cursor
+            .try_collect()
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_from(&self, sequence: i64, limit: i64) -> Result<Vec<AuditEntry>, Error> {
+        let cursor = self
+            .audit_collection()
+            .find(doc! {"_id": {"$gte": sequence}})
+            .sort(doc! {"_id": 1})
+            .limit(limit)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        cursor
+            .try_collect()
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for MongoRepository {
+    async fn insert(&self, webhook: &Webhook) -> Result<(), Error> {
+        self.webhook_collection()
+            .insert_one(webhook)
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Webhook>, Error> {
+        self.webhook_collection()
+            .find_one(doc! {"_id": id})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_by_user_id(&self, user_id: Uuid) -> Result<Vec<Webhook>, Error> {
+        let cursor = self
+            .webhook_collection()
+            .find(doc! {"user_id": user_id})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        cursor
+            .try_collect()
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_subscribed(
+        &self,
+        user_id: Uuid,
+        event: EventKind,
+    ) -> Result<Vec<Webhook>, Error> {
+        let cursor = self
+            .webhook_collection()
+            .find(doc! {"user_id": user_id, "events": to_bson(&event)?})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        cursor
+            .try_collect()
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), Error> {
+        self.webhook_collection()
+            .delete_one(doc! {"_id": id})
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl WebhookDeliveryRepository for MongoRepository {
+    async fn insert(&self, delivery: &WebhookDelivery) -> Result<(), Error> {
+        self.webhook_delivery_collection()
+            .insert_one(delivery)
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn update_claimed(&self, delivery: &WebhookDelivery) -> Result<bool, Error> {
+        self.webhook_delivery_collection()
+            .update_one(
+                doc! {
+                    "_id": delivery.id,
+                    "state": to_bson(&DeliveryState::Pending)?,
+                    "attempts": delivery.attempts,
+                },
+                doc! {"$set": {
+                    "state": to_bson(&delivery.state)?,
+                    "response_status": delivery.response_status.map(i32::from),
+                    "last_error": delivery.last_error.clone(),
+                    "next_attempt_at": BsonDateTime::from_chrono(delivery.next_attempt_at),
+                    "updated_at": BsonDateTime::from_chrono(delivery.updated_at),
+                }},
+            )
+            .await
+            .map(|result| result.matched_count > 0)
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_by_webhook_id(
+        &self,
+        webhook_id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, Error> {
+        let cursor = self
+            .webhook_delivery_collection()
+            .find(doc! {"webhook_id": webhook_id})
+            .sort(doc! {"created_at": -1})
+            .limit(limit)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        cursor
+            .try_collect()
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn claim_due(&self, lease: Duration) -> Result<Option<WebhookDelivery>, Error> {
+        let now = Utc::now();
+        let lease = chrono::Duration::from_std(lease).map_err(|e| Error::Parse(e.to_string()))?;
+
+        self.webhook_delivery_collection()
+            .find_one_and_update(
+                doc! {
+                    "state": to_bson(&DeliveryState::Pending)?,
+                    "next_attempt_at": {"$lte": BsonDateTime::from_chrono(now)},
+                },
+                doc! {
+                    "$set": {
+                        "next_attempt_at": BsonDateTime::from_chrono(now + lease),
+                        "updated_at": BsonDateTime::from_chrono(now),
+                    },
+                    "$inc": {"attempts": 1},
+                },
+            )
+            .sort(doc! {"next_attempt_at": 1})
+            .return_document(ReturnDocument::After)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl SessionRepository for MongoRepository {
+    async fn insert(&self, session: &Session) -> Result<(), Error> {
+        self.session_collection()
+            .insert_one(session)
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Session>, Error> {
+        self.session_collection()
+            .find_one(doc! {"_id": id})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_by_token_hash(&self, token_hash: &str) -> Result<Option<Session>, Error> {
+        self.session_collection()
+            .find_one(doc! {
+                "$or": [
+                    {"refresh_token_hash": token_hash},
+                    {"rotated_token_hashes": token_hash},
+                ]
+            })
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn rotate(
+        &self,
+        id: Uuid,
+        from_hash: &str,
+        to_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<Session>, Error> {
+        let now = bson::to_bson(&Local::now()).map_err(|e| Error::Parse(e.to_string()))?;
+
+        self.session_collection()
+            .find_one_and_update(
+                doc! {"_id": id, "refresh_token_hash": from_hash, "revoked_at": null},
+                doc! {
+                    "$set": {
+                        "refresh_token_hash": to_hash,
+                        "expires_at": BsonDateTime::from_chrono(expires_at),
+                        "updated_at": now,
+                    },
+                    "$push": {"rotated_token_hashes": {
+                        "$each": [from_hash],
+                        "$slice": -(MAX_ROTATED_TOKENS as i64),
+                    }},
+                },
+            )
+            .return_document(ReturnDocument::After)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn revoke(&self, id: Uuid) -> Result<(), Error> {
+        let now = bson::to_bson(&Local::now()).map_err(|e| Error::Parse(e.to_string()))?;
+
+        self.session_collection()
+            .update_one(
+                doc! {"_id": id, "revoked_at": null},
+                doc! {"$set": {"revoked_at": now.clone(), "updated_at": now}},
+            )
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn revoke_by_user_id(&self, user_id: Uuid) -> Result<u64, Error> {
+        let now = bson::to_bson(&Local::now()).map_err(|e| Error::Parse(e.to_string()))?;
+
+        self.session_collection()
+            .update_many(
+                doc! {"user_id": user_id, "revoked_at": null},
+                doc! {"$set": {"revoked_at": now.clone(), "updated_at": now}},
+            )
+            .await
+            .map(|result| result.modified_count)
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl OAuthStateRepository for MongoRepository {
+    async fn insert(&self, state: &OAuthState) -> Result<(), Error> {
+        self.oauth_state_collection()
+            .insert_one(state)
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn take(&self, state: &str) -> Result<Option<OAuthState>, Error> {
+        self.oauth_state_collection()
+            .find_one_and_delete(doc! {
+                "_id": state,
+                "expires_at": {"$gt": BsonDateTime::now()},
+            })
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn delete_expired(&self) -> Result<u64, Error> {
+        self.oauth_state_collection()
+            .delete_many(doc! {"expires_at": {"$lte": BsonDateTime::now()}})
+            .await
+            .map(|result| result.deleted_count)
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl HouseholdRepository for MongoRepository {
+    async fn insert(&self, household: &Household) -> Result<(), Error> {
+        self.household_collection()
+            .insert_one(household)
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Household>, Error> {
+        self.household_collection()
+            .find_one(doc! {"_id": id})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_by_member(&self, user_id: Uuid) -> Result<Vec<Household>, Error> {
+        let cursor = self
+            .household_collection()
+            .find(doc! {"members.user_id": user_id})
+            .sort(doc! {"created_at": 1})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        cursor
+            .try_collect()
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn add_member(
+        &self,
+        id: Uuid,
+        member: &HouseholdMember,
+    ) -> Result<Option<Household>, Error> {
+        let now = bson::to_bson(&Local::now()).map_err(|e| Error::Parse(e.to_string()))?;
+
+        self.household_collection()
+            .find_one_and_update(
+                doc! {"_id": id, "members.user_id": {"$ne": member.user_id}},
+                doc! {
+                    "$push": {"members": to_bson(member)?},
+                    "$set": {"updated_at": now},
+                },
+            )
+            .return_document(ReturnDocument::After)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn update_member_role(
+        &self,
+        id: Uuid,
+        user_id: Uuid,
+        role: HouseholdRole,
+    ) -> Result<Option<Household>, Error> {
+        let now = bson::to_bson(&Local::now()).map_err(|e| Error::Parse(e.to_string()))?;
+
+        self.household_collection()
+            .find_one_and_update(
+                doc! {"_id": id, "members.user_id": user_id},
+                doc! {"$set": {"members.$.role": to_bson(&role)?, "updated_at": now}},
+            )
+            .return_document(ReturnDocument::After)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn remove_member(&self, id: Uuid, user_id: Uuid) -> Result<Option<Household>, Error> {
+        let now = bson::to_bson(&Local::now()).map_err(|e| Error::Parse(e.to_string()))?;
+
+        self.household_collection()
+            .find_one_and_update(
+                doc! {"_id": id},
+                doc! {
+                    "$pull": {"members": {"user_id": user_id}},
+                    "$set": {"updated_at": now},
+                },
+            )
+            .return_document(ReturnDocument::After)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl InvitationRepository for MongoRepository {
+    async fn insert(&self, invitation: &Invitation) -> Result<(), Error> {
+        self.invitation_collection()
+            .insert_one(invitation)
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Invitation>, Error> {
+        self.invitation_collection()
+            .find_one(doc! {"_id": id})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_by_code_hash(&self, code_hash: &str) -> Result<Option<Invitation>, Error> {
+        self.invitation_collection()
+            .find_one(doc! {"code_hash": code_hash})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn find_pending_by_household_id(
+        &self,
+        household_id: Uuid,
+    ) -> Result<Vec<Invitation>, Error> {
+        let cursor = self
+            .invitation_collection()
+            .find(doc! {
+                "household_id": household_id,
+                "accepted_by": null,
+                "expires_at": {"$gt": BsonDateTime::now()},
+            })
+            .sort(doc! {"expires_at": -1})
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        cursor
+            .try_collect()
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn accept(&self, id: Uuid, user_id: Uuid) -> Result<Option<Invitation>, Error> {
+        let now = bson::to_bson(&Local::now()).map_err(|e| Error::Parse(e.to_string()))?;
+
+        self.invitation_collection()
+            .find_one_and_update(
+                doc! {
+                    "_id": id,
+                    "accepted_by": null,
+                    "expires_at": {"$gt": BsonDateTime::now()},
+                },
+                doc! {"$set": {"accepted_by": user_id, "accepted_at": now}},
+            )
+            .return_document(ReturnDocument::After)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), Error> {
+        self.invitation_collection()
+            .delete_one(doc! {"_id": id})
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::Database(e.to_string()))
+    }
 }
@@ -0,0 +1,134 @@
+use async_trait::async_trait;
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use std::time::Duration;
+
+use super::StorageRepository;
+use crate::errors::Error;
+
+static S3_STORAGE_BASE_PATH: &str = "uploads";
+static CONTENT_TYPE: &str = "image/jpeg";
+
+pub struct S3Repository {
+    client: Client,
+    bucket_name: String,
+    base_url: String,
+}
+
+impl S3Repository {
+    pub fn new(
+        client: Client,
+        bucket_name: String,
+        region: String,
+        endpoint: Option<String>,
+    ) -> Self {
+        let base_url = match endpoint {
+            Some(endpoint) => format!("{}/{}", endpoint.trim_end_matches('/'), bucket_name),
+            None => format!("https://{}.s3.{}.amazonaws.com", bucket_name, region),
+        };
+
+        Self {
+            client,
+            bucket_name,
+            base_url,
+        }
+    }
+
+    fn object_key(name: &str) -> String {
+        format!("{}/{}", S3_STORAGE_BASE_PATH, name.trim_start_matches('/'))
+    }
+}
+
+#[async_trait]
+impl StorageRepository for S3Repository {
+    async fn upload_file(&self, name: &str, data: Vec<u8>) -> Result<String, Error> {
+        let object_key = Self::object_key(name);
+
+        self.client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(&object_key)
+            .content_type(CONTENT_TYPE)
+            .content_length(data.len() as i64)
+            .body(ByteStream::from(data))
+            .send()
+            .await
+            .map_err(|e| Error::Storage(e.to_string()))?;
+
+        Ok(format!("{}/{}", self.base_url, object_key))
+    }
+
+    async fn download(&self, name: &str) -> Result<Vec<u8>, Error> {
+        let object = self
+            .client
+            .get_object()
+            .bucket(&self.bucket_name)
+            .key(Self::object_key(name))
+            .send()
+            .await
+            .map_err(|e| match e.as_service_error() {
+                Some(service_error) if service_error.is_no_such_key() => {
+                    Error::NotFound(format!("object not found: {}", name))
+                }
+                _ => Error::Storage(e.to_string()),
+            })?;
+
+        let data = object
+            .body
+            .collect()
+            .await
+            .map_err(|e| Error::Storage(e.to_string()))?;
+
+        Ok(data.into_bytes().to_vec())
+    }
+
+    async fn delete(&self, name: &str) -> Result<(), Error> {
+        // S3 deletes are idempotent, check first to report missing objects like other backends.
+        if !self.exists(name).await? {
+            return Err(Error::NotFound(format!("object not found: {}", name)));
+        }
+
+        self.client
+            .delete_object()
+            .bucket(&self.bucket_name)
+            .key(Self::object_key(name))
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::Storage(e.to_string()))
+    }
+
+    async fn exists(&self, name: &str) -> Result<bool, Error> {
+        match self
+            .client
+            .head_object()
+            .bucket(&self.bucket_name)
+            .key(Self::object_key(name))
+            .send()
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => match e.as_service_error() {
+                Some(service_error) if service_error.is_not_found() => Ok(false),
+                _ => Err(Error::Storage(e.to_string())),
+            },
+        }
+    }
+
+    async fn signed_url(&self, name: &str, expires_in: Duration) -> Result<String, Error> {
+        let presigning_config =
+            PresigningConfig::expires_in(expires_in).map_err(|e| Error::Storage(e.to_string()))?;
+
+        let request = self
+            .client
+            .get_object()
+            .bucket(&self.bucket_name)
+            .key(Self::object_key(name))
+            .presigned(presigning_config)
+            .await
+            .map_err(|e| Error::Storage(e.to_string()))?;
+
+        Ok(request.uri().to_string())
+    }
+}
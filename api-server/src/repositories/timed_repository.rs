@@ -0,0 +1,580 @@
+use async_trait::async_trait;
+use bson::Uuid;
+use chrono::{DateTime, Utc};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::{
+    AuditRepository, DeliveryRepository, DeviceRepository, HealthRepository, HouseholdRepository,
+    InvitationRepository, OAuthStateRepository, PictureRepository, SessionRepository,
+    StatusRepository, StorageRepository, UserRepository, WebhookDeliveryRepository,
+    WebhookRepository,
+};
+use crate::errors::Error;
+use crate::metrics::Metrics;
+use crate::models::{
+    AuditEntry, AuditFilter, Delivery, Device, EventKind, Household, HouseholdMember,
+    HouseholdRole, Invitation, OAuthState, Picture, Session, Status, StatusState, User,
+    UserIdentity, Webhook, WebhookDelivery,
+};
+
+// Wraps a repository or a storage backend and times each of its calls, labelled by
+// `backend` and the operation.
+pub struct TimedRepository<R: ?Sized> {
+    inner: Arc<R>,
+    backend: &'static str,
+    metrics: Arc<Metrics>,
+}
+
+impl<R: ?Sized> TimedRepository<R> {
+    pub fn new(inner: Arc<R>, backend: &'static str, metrics: Arc<Metrics>) -> Self {
+        Self {
+            inner,
+            backend,
+            metrics,
+        }
+    }
+
+    async fn time<T>(
+        &self,
+        operation: &str,
+        call: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        let started = Instant::now();
+        let result = call.await;
+        self.metrics.observe_backend_call(
+            self.backend,
+            operation,
+            result.is_ok(),
+            started.elapsed(),
+        );
+
+        result
+    }
+}
+
+#[async_trait]
+impl<R: HealthRepository + ?Sized> HealthRepository for TimedRepository<R> {
+    async fn ping(&self) -> Result<(), Error> {
+        self.time("database.ping", self.inner.ping()).await
+    }
+}
+
+#[async_trait]
+impl<R: StatusRepository + ?Sized> StatusRepository for TimedRepository<R> {
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Status>, Error> {
+        self.time("status.find_by_id", self.inner.find_by_id(id))
+            .await
+    }
+
+    async fn insert(&self, status: &Status) -> Result<(), Error> {
+        self.time("status.insert", self.inner.insert(status)).await
+    }
+
+    async fn find_and_update_state(
+        &self,
+        status: &Status,
+        from: StatusState,
+    ) -> Result<Option<Status>, Error> {
+        self.time(
+            "status.find_and_update_state",
+            self.inner.find_and_update_state(status, from),
+        )
+        .await
+    }
+
+    async fn find_stale_pending(&self) -> Result<Vec<Status>, Error> {
+        self.time("status.find_stale_pending", self.inner.find_stale_pending())
+            .await
+    }
+
+    async fn find_pending_by_camera(&self, camera_id: Uuid) -> Result<Vec<Status>, Error> {
+        self.time(
+            "status.find_pending_by_camera",
+            self.inner.find_pending_by_camera(camera_id),
+        )
+        .await
+    }
+
+    async fn count_pending(&self) -> Result<u64, Error> {
+        self.time("status.count_pending", self.inner.count_pending())
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: PictureRepository + ?Sized> PictureRepository for TimedRepository<R> {
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Picture>, Error> {
+        self.time("picture.find_by_id", self.inner.find_by_id(id))
+            .await
+    }
+
+    async fn find_by_user_id(&self, user_id: Uuid) -> Result<Vec<Picture>, Error> {
+        self.time(
+            "picture.find_by_user_id",
+            self.inner.find_by_user_id(user_id),
+        )
+        .await
+    }
+
+    async fn find_by_device_ids(&self, device_ids: &[Uuid]) -> Result<Vec<Picture>, Error> {
+        self.time(
+            "picture.find_by_device_ids",
+            self.inner.find_by_device_ids(device_ids),
+        )
+        .await
+    }
+
+    async fn insert(&self, picture: &Picture) -> Result<(), Error> {
+        self.time("picture.insert", self.inner.insert(picture))
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: StorageRepository + ?Sized> StorageRepository for TimedRepository<R> {
+    async fn upload_file(&self, name: &str, data: Vec<u8>) -> Result<String, Error> {
+        self.time("storage.upload_file", self.inner.upload_file(name, data))
+            .await
+    }
+
+    async fn download(&self, name: &str) -> Result<Vec<u8>, Error> {
+        self.time("storage.download", self.inner.download(name))
+            .await
+    }
+
+    async fn delete(&self, name: &str) -> Result<(), Error> {
+        self.time("storage.delete", self.inner.delete(name)).await
+    }
+
+    async fn exists(&self, name: &str) -> Result<bool, Error> {
+        self.time("storage.exists", self.inner.exists(name)).await
+    }
+
+    async fn signed_url(&self, name: &str, expires_in: Duration) -> Result<String, Error> {
+        self.time(
+            "storage.signed_url",
+            self.inner.signed_url(name, expires_in),
+        )
+        .await
+    }
+
+    async fn ping(&self) -> Result<(), Error> {
+        self.time("storage.ping", self.inner.ping()).await
+    }
+}
+
+#[async_trait]
+impl<R: UserRepository + ?Sized> UserRepository for TimedRepository<R> {
+    async fn insert(&self, user: &User) -> Result<(), Error> {
+        self.time("user.insert", self.inner.insert(user)).await
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, Error> {
+        self.time("user.find_by_id", self.inner.find_by_id(id))
+            .await
+    }
+
+    async fn find_by_identity(&self, identity: &UserIdentity) -> Result<Option<User>, Error> {
+        self.time(
+            "user.find_by_identity",
+            self.inner.find_by_identity(identity),
+        )
+        .await
+    }
+
+    async fn get_by_google_id(&self, google_id: String) -> Result<Option<User>, Error> {
+        self.time(
+            "user.get_by_google_id",
+            self.inner.get_by_google_id(google_id),
+        )
+        .await
+    }
+
+    async fn add_identity(&self, id: Uuid, identity: &UserIdentity) -> Result<(), Error> {
+        self.time("user.add_identity", self.inner.add_identity(id, identity))
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: SessionRepository + ?Sized> SessionRepository for TimedRepository<R> {
+    async fn insert(&self, session: &Session) -> Result<(), Error> {
+        self.time("session.insert", self.inner.insert(session))
+            .await
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Session>, Error> {
+        self.time("session.find_by_id", self.inner.find_by_id(id))
+            .await
+    }
+
+    async fn find_by_token_hash(&self, token_hash: &str) -> Result<Option<Session>, Error> {
+        self.time(
+            "session.find_by_token_hash",
+            self.inner.find_by_token_hash(token_hash),
+        )
+        .await
+    }
+
+    async fn rotate(
+        &self,
+        id: Uuid,
+        from_hash: &str,
+        to_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<Session>, Error> {
+        self.time(
+            "session.rotate",
+            self.inner.rotate(id, from_hash, to_hash, expires_at),
+        )
+        .await
+    }
+
+    async fn revoke(&self, id: Uuid) -> Result<(), Error> {
+        self.time("session.revoke", self.inner.revoke(id)).await
+    }
+
+    async fn revoke_by_user_id(&self, user_id: Uuid) -> Result<u64, Error> {
+        self.time(
+            "session.revoke_by_user_id",
+            self.inner.revoke_by_user_id(user_id),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl<R: DeviceRepository + ?Sized> DeviceRepository for TimedRepository<R> {
+    async fn insert(&self, device: &Device) -> Result<(), Error> {
+        self.time("device.insert", self.inner.insert(device)).await
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Device>, Error> {
+        self.time("device.find_by_id", self.inner.find_by_id(id))
+            .await
+    }
+
+    async fn find_by_user_id(&self, user_id: Uuid) -> Result<Vec<Device>, Error> {
+        self.time(
+            "device.find_by_user_id",
+            self.inner.find_by_user_id(user_id),
+        )
+        .await
+    }
+
+    async fn find_by_household_id(&self, household_id: Uuid) -> Result<Vec<Device>, Error> {
+        self.time(
+            "device.find_by_household_id",
+            self.inner.find_by_household_id(household_id),
+        )
+        .await
+    }
+
+    async fn find_by_camera_id(&self, camera_id: Uuid) -> Result<Vec<Device>, Error> {
+        self.time(
+            "device.find_by_camera_id",
+            self.inner.find_by_camera_id(camera_id),
+        )
+        .await
+    }
+
+    async fn find_all(&self) -> Result<Vec<Device>, Error> {
+        self.time("device.find_all", self.inner.find_all()).await
+    }
+
+    async fn update_name(&self, id: Uuid, name: String) -> Result<Option<Device>, Error> {
+        self.time("device.update_name", self.inner.update_name(id, name))
+            .await
+    }
+
+    async fn update_camera_id(
+        &self,
+        id: Uuid,
+        camera_id: Option<Uuid>,
+    ) -> Result<Option<Device>, Error> {
+        self.time(
+            "device.update_camera_id",
+            self.inner.update_camera_id(id, camera_id),
+        )
+        .await
+    }
+
+    async fn update_callback_url(
+        &self,
+        id: Uuid,
+        callback_url: String,
+    ) -> Result<Option<Device>, Error> {
+        self.time(
+            "device.update_callback_url",
+            self.inner.update_callback_url(id, callback_url),
+        )
+        .await
+    }
+
+    async fn revoke(&self, id: Uuid) -> Result<Option<Device>, Error> {
+        self.time("device.revoke", self.inner.revoke(id)).await
+    }
+
+    async fn assign_household(&self, user_id: Uuid, household_id: Uuid) -> Result<u64, Error> {
+        self.time(
+            "device.assign_household",
+            self.inner.assign_household(user_id, household_id),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl<R: DeliveryRepository + ?Sized> DeliveryRepository for TimedRepository<R> {
+    async fn insert(&self, delivery: &Delivery) -> Result<(), Error> {
+        self.time("delivery.insert", self.inner.insert(delivery))
+            .await
+    }
+
+    async fn update_claimed(&self, delivery: &Delivery) -> Result<bool, Error> {
+        self.time(
+            "delivery.update_claimed",
+            self.inner.update_claimed(delivery),
+        )
+        .await
+    }
+
+    async fn find_by_status_id(&self, status_id: Uuid) -> Result<Vec<Delivery>, Error> {
+        self.time(
+            "delivery.find_by_status_id",
+            self.inner.find_by_status_id(status_id),
+        )
+        .await
+    }
+
+    async fn find_pending(
+        &self,
+        status_id: Uuid,
+        device_id: Uuid,
+    ) -> Result<Option<Delivery>, Error> {
+        self.time(
+            "delivery.find_pending",
+            self.inner.find_pending(status_id, device_id),
+        )
+        .await
+    }
+
+    async fn supersede_pending(&self, status_id: Uuid, device_id: Uuid) -> Result<u64, Error> {
+        self.time(
+            "delivery.supersede_pending",
+            self.inner.supersede_pending(status_id, device_id),
+        )
+        .await
+    }
+
+    async fn claim_due(&self, lease: Duration) -> Result<Option<Delivery>, Error> {
+        self.time("delivery.claim_due", self.inner.claim_due(lease))
+            .await
+    }
+
+    async fn expire_stale(&self) -> Result<u64, Error> {
+        self.time("delivery.expire_stale", self.inner.expire_stale())
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: AuditRepository + ?Sized> AuditRepository for TimedRepository<R> {
+    async fn last(&self) -> Result<Option<AuditEntry>, Error> {
+        self.time("audit.last", self.inner.last()).await
+    }
+
+    async fn append(&self, entry: &AuditEntry) -> Result<(), Error> {
+        self.time("audit.append", self.inner.append(entry)).await
+    }
+
+    async fn find(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, Error> {
+        self.time("audit.find", self.inner.find(filter)).await
+    }
+
+    async fn find_from(&self, sequence: i64, limit: i64) -> Result<Vec<AuditEntry>, Error> {
+        self.time("audit.find_from", self.inner.find_from(sequence, limit))
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: WebhookRepository + ?Sized> WebhookRepository for TimedRepository<R> {
+    async fn insert(&self, webhook: &Webhook) -> Result<(), Error> {
+        self.time("webhook.insert", self.inner.insert(webhook))
+            .await
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Webhook>, Error> {
+        self.time("webhook.find_by_id", self.inner.find_by_id(id))
+            .await
+    }
+
+    async fn find_by_user_id(&self, user_id: Uuid) -> Result<Vec<Webhook>, Error> {
+        self.time(
+            "webhook.find_by_user_id",
+            self.inner.find_by_user_id(user_id),
+        )
+        .await
+    }
+
+    async fn find_subscribed(
+        &self,
+        user_id: Uuid,
+        event: EventKind,
+    ) -> Result<Vec<Webhook>, Error> {
+        self.time(
+            "webhook.find_subscribed",
+            self.inner.find_subscribed(user_id, event),
+        )
+        .await
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), Error> {
+        self.time("webhook.delete", self.inner.delete(id)).await
+    }
+}
+
+#[async_trait]
+impl<R: WebhookDeliveryRepository + ?Sized> WebhookDeliveryRepository for TimedRepository<R> {
+    async fn insert(&self, delivery: &WebhookDelivery) -> Result<(), Error> {
+        self.time("webhook_delivery.insert", self.inner.insert(delivery))
+            .await
+    }
+
+    async fn update_claimed(&self, delivery: &WebhookDelivery) -> Result<bool, Error> {
+        self.time(
+            "webhook_delivery.update_claimed",
+            self.inner.update_claimed(delivery),
+        )
+        .await
+    }
+
+    async fn find_by_webhook_id(
+        &self,
+        webhook_id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, Error> {
+        self.time(
+            "webhook_delivery.find_by_webhook_id",
+            self.inner.find_by_webhook_id(webhook_id, limit),
+        )
+        .await
+    }
+
+    async fn claim_due(&self, lease: Duration) -> Result<Option<WebhookDelivery>, Error> {
+        self.time("webhook_delivery.claim_due", self.inner.claim_due(lease))
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: OAuthStateRepository + ?Sized> OAuthStateRepository for TimedRepository<R> {
+    async fn insert(&self, state: &OAuthState) -> Result<(), Error> {
+        self.time("oauth_state.insert", self.inner.insert(state))
+            .await
+    }
+
+    async fn take(&self, state: &str) -> Result<Option<OAuthState>, Error> {
+        self.time("oauth_state.take", self.inner.take(state)).await
+    }
+
+    async fn delete_expired(&self) -> Result<u64, Error> {
+        self.time("oauth_state.delete_expired", self.inner.delete_expired())
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: HouseholdRepository + ?Sized> HouseholdRepository for TimedRepository<R> {
+    async fn insert(&self, household: &Household) -> Result<(), Error> {
+        self.time("household.insert", self.inner.insert(household))
+            .await
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Household>, Error> {
+        self.time("household.find_by_id", self.inner.find_by_id(id))
+            .await
+    }
+
+    async fn find_by_member(&self, user_id: Uuid) -> Result<Vec<Household>, Error> {
+        self.time(
+            "household.find_by_member",
+            self.inner.find_by_member(user_id),
+        )
+        .await
+    }
+
+    async fn add_member(
+        &self,
+        id: Uuid,
+        member: &HouseholdMember,
+    ) -> Result<Option<Household>, Error> {
+        self.time("household.add_member", self.inner.add_member(id, member))
+            .await
+    }
+
+    async fn update_member_role(
+        &self,
+        id: Uuid,
+        user_id: Uuid,
+        role: HouseholdRole,
+    ) -> Result<Option<Household>, Error> {
+        self.time(
+            "household.update_member_role",
+            self.inner.update_member_role(id, user_id, role),
+        )
+        .await
+    }
+
+    async fn remove_member(&self, id: Uuid, user_id: Uuid) -> Result<Option<Household>, Error> {
+        self.time(
+            "household.remove_member",
+            self.inner.remove_member(id, user_id),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl<R: InvitationRepository + ?Sized> InvitationRepository for TimedRepository<R> {
+    async fn insert(&self, invitation: &Invitation) -> Result<(), Error> {
+        self.time("invitation.insert", self.inner.insert(invitation))
+            .await
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Invitation>, Error> {
+        self.time("invitation.find_by_id", self.inner.find_by_id(id))
+            .await
+    }
+
+    async fn find_by_code_hash(&self, code_hash: &str) -> Result<Option<Invitation>, Error> {
+        self.time(
+            "invitation.find_by_code_hash",
+            self.inner.find_by_code_hash(code_hash),
+        )
+        .await
+    }
+
+    async fn find_pending_by_household_id(
+        &self,
+        household_id: Uuid,
+    ) -> Result<Vec<Invitation>, Error> {
+        self.time(
+            "invitation.find_pending_by_household_id",
+            self.inner.find_pending_by_household_id(household_id),
+        )
+        .await
+    }
+
+    async fn accept(&self, id: Uuid, user_id: Uuid) -> Result<Option<Invitation>, Error> {
+        self.time("invitation.accept", self.inner.accept(id, user_id))
+            .await
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), Error> {
+        self.time("invitation.delete", self.inner.delete(id)).await
+    }
+}
@@ -0,0 +1,35 @@
+use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region};
+use aws_sdk_s3::{Client, Config};
+
+use crate::errors::Error;
+
+pub fn init_s3_client(
+    endpoint: Option<String>,
+    region: String,
+    access_key_id: Option<String>,
+    secret_access_key: Option<String>,
+) -> Result<Client, Error> {
+    let (access_key_id, secret_access_key) = match (access_key_id, secret_access_key) {
+        (Some(key_id), Some(secret)) => (key_id, secret),
+        _ => {
+            tracing::error!("S3 credentials not found");
+            return Err(Error::WithText("S3 credentials not found".into()));
+        }
+    };
+
+    let credentials = Credentials::new(access_key_id, secret_access_key, None, None, "config");
+
+    let mut builder = Config::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .region(Region::new(region))
+        .credentials_provider(credentials);
+
+    // Self-hosted stores (MinIO, Garage...) don't do virtual-hosted buckets.
+    if let Some(endpoint) = endpoint {
+        builder = builder.endpoint_url(endpoint).force_path_style(true);
+    }
+
+    tracing::info!("S3 client initialised");
+
+    Ok(Client::from_conf(builder.build()))
+}
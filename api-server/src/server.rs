@@ -1,5 +1,9 @@
 use actix_web::{web, App, HttpServer};
 use dotenv::dotenv;
+use rand::RngCore;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
 use std::{net::UdpSocket, sync::Arc};
 
 mod models;
@@ -10,14 +14,17 @@ mod app_state;
 use app_state::AppState;
 
 mod handlers;
-use handlers::{get_status, patch_authorised, post_picture};
+use handlers::{
+    get_health, get_metrics, get_readiness, get_status, get_upload, patch_status, post_picture,
+};
 
 mod middlewares;
-use middlewares::CheckAuthToken;
+use middlewares::{AuditTrail, CheckAuthToken, RequestMetrics, RequestTrace};
 
 mod repositories;
 use repositories::{
-    GcsRepository, MongoRepository, PictureRepository, StatusRepository, StorageRepository,
+    GcsRepository, HealthRepository, InMemoryRepository, LocalStorageRepository, MongoRepository,
+    PictureRepository, S3Repository, StatusRepository, StorageRepository, TimedRepository,
 };
 
 mod mongo_client;
@@ -26,77 +33,473 @@ use mongo_client::init_mongo_client;
 mod google_storage_client;
 use google_storage_client::init_google_storage_client;
 
+mod s3_client;
+use s3_client::init_s3_client;
+
+mod mqtt_client;
+use mqtt_client::init_mqtt_client;
+
 mod config;
-use config::Config;
-use services::{PictureServiceImpl, StatusServiceImpl};
+
+mod logging;
+
+mod heartbeat;
+use heartbeat::Heartbeats;
+
+mod metrics;
+
+mod url_guard;
+use url_guard::Reach;
+
+use config::{Config, MqttDecisions, RepositoryBackend, StorageBackend};
+use logging::init_logging;
+use metrics::Metrics;
+use services::{PictureServiceImpl, StatusServiceImpl, StatusTimeouts};
 
 use crate::{
-    handlers::{auth_url, callback, get_by_google_id},
-    repositories::UserRepository,
-    services::{AuthServiceImpl, UserServiceImpl},
+    handlers::{
+        auth_providers, auth_url, callback, change_member_role, create_household,
+        create_invitation, delete_webhook, get_all_picture, get_audit_entries, get_events,
+        get_household, get_me, link_camera, list_devices, list_households, list_invitations,
+        list_members, list_webhook_deliveries, list_webhooks, login, logout, put_device_callback,
+        redeem_invitation, refresh, register_device, register_webhook, remove_member,
+        rename_device, revoke_device, revoke_invitation, verify_audit_log,
+    },
+    repositories::{
+        AuditRepository, DeliveryRepository, DeviceRepository, HouseholdRepository,
+        InvitationRepository, OAuthStateRepository, SessionRepository, UserRepository,
+        WebhookDeliveryRepository, WebhookRepository,
+    },
+    services::{
+        AuditServiceImpl, AuthServiceImpl, DeviceServiceImpl, EventServiceImpl, HealthServiceImpl,
+        HouseholdServiceImpl, OidcProvider, SessionServiceImpl, UserServiceImpl,
+        WebhookServiceImpl,
+    },
 };
 
 mod errors;
 
 mod services;
 
-fn get_local_ip() -> Result<String, Box<dyn std::error::Error>> {
+mod workers;
+use workers::{
+    availability_topic, HomeAssistant, MqttBridge, OutboxWorker, StatusExpiryWorker, WebhookWorker,
+};
+
+struct Repositories {
+    health: Arc<dyn HealthRepository>,
+    status: Arc<dyn StatusRepository>,
+    picture: Arc<dyn PictureRepository>,
+    user: Arc<dyn UserRepository>,
+    device: Arc<dyn DeviceRepository>,
+    delivery: Arc<dyn DeliveryRepository>,
+    audit: Arc<dyn AuditRepository>,
+    webhook: Arc<dyn WebhookRepository>,
+    webhook_delivery: Arc<dyn WebhookDeliveryRepository>,
+    session: Arc<dyn SessionRepository>,
+    oauth_state: Arc<dyn OAuthStateRepository>,
+    household: Arc<dyn HouseholdRepository>,
+    invitation: Arc<dyn InvitationRepository>,
+}
+
+impl Repositories {
+    // Every repository is the same backend, timed under the `backend` label.
+    fn timed<R>(repo: Arc<R>, backend: &'static str, metrics: Arc<Metrics>) -> Self
+    where
+        R: HealthRepository
+            + StatusRepository
+            + PictureRepository
+            + UserRepository
+            + DeviceRepository
+            + DeliveryRepository
+            + AuditRepository
+            + WebhookRepository
+            + WebhookDeliveryRepository
+            + SessionRepository
+            + OAuthStateRepository
+            + HouseholdRepository
+            + InvitationRepository
+            + 'static,
+    {
+        let timed = Arc::new(TimedRepository::new(repo, backend, metrics));
+        Repositories {
+            health: timed.clone(),
+            status: timed.clone(),
+            picture: timed.clone(),
+            user: timed.clone(),
+            device: timed.clone(),
+            delivery: timed.clone(),
+            audit: timed.clone(),
+            webhook: timed.clone(),
+            webhook_delivery: timed.clone(),
+            session: timed.clone(),
+            oauth_state: timed.clone(),
+            household: timed.clone(),
+            invitation: timed,
+        }
+    }
+}
+
+// Connecting a UDP socket sends nothing, it only picks the interface routing to `probe`.
+fn get_local_ip(probe: &str) -> Result<String, Box<dyn std::error::Error>> {
     let socket = UdpSocket::bind("0.0.0.0:0")?;
-    socket.connect("8.8.8.8:80")?;
+    socket.connect(probe)?;
     let local_address = socket.local_addr()?;
     Ok(local_address.ip().to_string())
 }
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    let local_ip = get_local_ip().unwrap();
-    println!("local ip: {}", local_ip);
+struct Args {
+    config_file: Option<PathBuf>,
+    check_config: bool,
+}
+
+impl Args {
+    fn parse() -> Result<Self, String> {
+        let mut args = Args {
+            config_file: None,
+            check_config: false,
+        };
+
+        let mut values = std::env::args().skip(1);
+        while let Some(arg) = values.next() {
+            match arg.as_str() {
+                "--config" => {
+                    let path = values.next().ok_or("--config needs a path")?;
+                    args.config_file = Some(PathBuf::from(path));
+                }
+                "--check-config" => args.check_config = true,
+                other => return Err(format!("unknown argument {}", other)),
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(e) => {
+            println!("{}", e);
+            println!("usage: api-server [--config <path>] [--check-config]");
+            return ExitCode::FAILURE;
+        }
+    };
 
     dotenv().ok();
 
-    let config = Config::new();
+    let config = match Config::load(args.config_file) {
+        Ok(config) => config,
+        Err(problems) => {
+            println!("Invalid configuration:");
+            for problem in problems {
+                println!("  - {}", problem);
+            }
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if args.check_config {
+        match &config.file {
+            Some(path) => println!("Configuration from {} is valid", path.display()),
+            None => println!("Configuration is valid"),
+        }
+        return ExitCode::SUCCESS;
+    }
 
-    let database_name = config.database_name;
-    let database_name_copy = database_name.clone();
+    init_logging(&config.logging);
 
-    let mongo_client = match init_mongo_client(config.mongodb_url, database_name).await {
-        Ok(client) => client,
+    match actix_web::rt::System::new().block_on(run(config)) {
+        Ok(()) => ExitCode::SUCCESS,
         Err(e) => {
-            return Err(std::io::Error::new(std::io::ErrorKind::Other, e));
+            tracing::error!(error = %e, "Server error");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(config: Config) -> std::io::Result<()> {
+    if let Some(path) = &config.file {
+        tracing::info!(path = %path.display(), "Configuration read");
+    }
+
+    if let Some(probe) = &config.server.local_ip_probe {
+        match get_local_ip(probe) {
+            Ok(local_ip) => tracing::info!(local_ip = %local_ip, "Local ip"),
+            Err(e) => tracing::warn!(probe = %probe, error = %e, "Failed to find the local ip"),
+        }
+    }
+
+    let metrics = Arc::new(Metrics::new().map_err(std::io::Error::other)?);
+
+    let repositories = match config.database.backend {
+        RepositoryBackend::Mongo => {
+            let database_name = config.database.name;
+            let database_name_copy = database_name.clone();
+
+            let mongo_client = match init_mongo_client(config.database.url, database_name).await {
+                Ok(client) => client,
+                Err(e) => {
+                    return Err(std::io::Error::other(e));
+                }
+            };
+
+            let mongo_repo = Arc::new(MongoRepository::new(mongo_client, database_name_copy));
+            mongo_repo
+                .create_indexes()
+                .await
+                .map_err(std::io::Error::other)?;
+            let migrated = mongo_repo
+                .migrate_statuses(Duration::from_secs(
+                    config.notifications.status_pending_timeout_secs,
+                ))
+                .await
+                .map_err(std::io::Error::other)?;
+            if migrated > 0 {
+                tracing::info!(migrated, "Migrated statuses to their lifecycle");
+            }
+            Repositories::timed(mongo_repo, "mongo", metrics.clone())
+        }
+        RepositoryBackend::Memory => {
+            tracing::warn!("Using in-memory repositories, data will be lost on restart");
+            let memory_repo = Arc::new(InMemoryRepository::new());
+            Repositories::timed(memory_repo, "memory", metrics.clone())
         }
     };
 
-    let storage_client = init_google_storage_client(config.credentials_path)
-        .await
-        .unwrap();
+    // The local backend is kept aside as well since the server also serves its files.
+    let mut local_storage: Option<Arc<LocalStorageRepository>> = None;
 
-    let mongo_repo = Arc::new(MongoRepository::new(mongo_client, database_name_copy));
-    let gcp_repo = Arc::new(GcsRepository::new(storage_client, config.bucket_name));
+    let (storage_repository, storage_backend): (Arc<dyn StorageRepository>, &'static str) =
+        match config.storage.backend {
+            StorageBackend::Gcs => {
+                let storage_client = init_google_storage_client(config.storage.credentials_path)
+                    .await
+                    .map_err(std::io::Error::other)?;
+                (
+                    Arc::new(GcsRepository::new(
+                        storage_client,
+                        config.storage.bucket_name,
+                    )),
+                    "gcs",
+                )
+            }
+            StorageBackend::S3 => {
+                let s3_client = init_s3_client(
+                    config.storage.s3_endpoint.clone(),
+                    config.storage.s3_region.clone(),
+                    config.storage.s3_access_key_id,
+                    config.storage.s3_secret_access_key,
+                )
+                .map_err(std::io::Error::other)?;
+                (
+                    Arc::new(S3Repository::new(
+                        s3_client,
+                        config.storage.bucket_name,
+                        config.storage.s3_region,
+                        config.storage.s3_endpoint,
+                    )),
+                    "s3",
+                )
+            }
+            StorageBackend::Local => {
+                let signing_secret = match config.storage.url_signing_secret {
+                    Some(secret) => secret.into_bytes(),
+                    None => {
+                        tracing::warn!(
+                            "URL_SIGNING_SECRET not set, picture links won't survive a restart"
+                        );
+                        let mut secret = vec![0u8; 32];
+                        rand::thread_rng().fill_bytes(&mut secret);
+                        secret
+                    }
+                };
+                let local_repo = Arc::new(
+                    LocalStorageRepository::new(
+                        PathBuf::from(config.storage.local_path),
+                        config.server.public_base_url,
+                        signing_secret,
+                    )
+                    .map_err(std::io::Error::other)?,
+                );
+                local_storage = Some(local_repo.clone());
+                (local_repo, "local")
+            }
+            StorageBackend::Memory => {
+                tracing::warn!("Using in-memory storage, pictures will be lost on restart");
+                (Arc::new(InMemoryRepository::new()), "memory")
+            }
+        };
+    let storage_repository: Arc<dyn StorageRepository> = Arc::new(TimedRepository::new(
+        storage_repository,
+        storage_backend,
+        metrics.clone(),
+    ));
 
-    let status_repository: Arc<dyn StatusRepository> = mongo_repo.clone();
-    let picture_repository: Arc<dyn PictureRepository> = mongo_repo.clone();
-    let user_repository: Arc<dyn UserRepository> = mongo_repo.clone();
-    let storage_repository: Arc<dyn StorageRepository> = gcp_repo;
+    let signed_url_ttl = Duration::from_secs(config.storage.signed_url_ttl_secs);
 
+    let audit_service = Arc::new(AuditServiceImpl::new(
+        repositories.audit.clone(),
+        repositories.household.clone(),
+        config.auth.admin_user_ids.clone(),
+    ));
+    let event_service = Arc::new(EventServiceImpl::new(
+        config.notifications.event_history_size,
+    ));
+    let webhook_service = Arc::new(WebhookServiceImpl::new(
+        repositories.webhook.clone(),
+        repositories.webhook_delivery.clone(),
+        repositories.household.clone(),
+    ));
     let status_service = Arc::new(StatusServiceImpl::new(
-        status_repository.clone(),
-        picture_repository.clone(),
-        config.http_server_address,
+        repositories.status.clone(),
+        repositories.picture.clone(),
+        storage_repository.clone(),
+        repositories.device.clone(),
+        repositories.delivery.clone(),
+        audit_service.clone(),
+        event_service.clone(),
+        webhook_service.clone(),
+        metrics.clone(),
+        StatusTimeouts {
+            signed_url_ttl,
+            delivery_ttl: Duration::from_secs(config.notifications.outbox_ttl_secs),
+            pending_timeout: Duration::from_secs(config.notifications.status_pending_timeout_secs),
+        },
+    ));
+    let household_service = Arc::new(HouseholdServiceImpl::new(
+        repositories.household.clone(),
+        repositories.invitation.clone(),
+        repositories.user.clone(),
+        repositories.device.clone(),
+        repositories.status.clone(),
+        repositories.picture.clone(),
+        Duration::from_secs(config.auth.invitation_ttl_secs),
     ));
     let picture_service = Arc::new(PictureServiceImpl::new(
-        picture_repository,
-        storage_repository,
+        repositories.picture.clone(),
+        storage_repository.clone(),
         status_service.clone(),
+        household_service.clone(),
+        metrics.clone(),
+        signed_url_ttl,
     ));
-    let user_service = Arc::new(UserServiceImpl::new(user_repository.clone()));
+    let user_service = Arc::new(UserServiceImpl::new(repositories.user.clone()));
+    // Cameras and controllers are called on the LAN, never on our host nor the metadata service.
+    let device_client = url_guard::client(Reach::Lan).map_err(std::io::Error::other)?;
+    let device_service = Arc::new(DeviceServiceImpl::new(
+        repositories.device.clone(),
+        household_service.clone(),
+        device_client.clone(),
+        Duration::from_secs(config.auth.device_auth_window_secs),
+    ));
+    let session_signing_secret = match config.auth.session_signing_secret {
+        Some(secret) => secret.into_bytes(),
+        None => {
+            tracing::warn!("SESSION_SIGNING_SECRET not set, sessions won't survive a restart");
+            let mut secret = vec![0u8; 32];
+            rand::thread_rng().fill_bytes(&mut secret);
+            secret
+        }
+    };
+    let session_service = Arc::new(SessionServiceImpl::new(
+        repositories.session.clone(),
+        repositories.user.clone(),
+        &session_signing_secret,
+        Duration::from_secs(config.auth.access_token_ttl_secs),
+        Duration::from_secs(config.auth.refresh_token_ttl_secs),
+    ));
+    let oidc_providers = config
+        .auth
+        .providers
+        .into_iter()
+        .map(|provider| {
+            OidcProvider::new(
+                provider.name,
+                provider.issuer_url,
+                provider.client_id,
+                provider.client_secret,
+                provider.redirect_url,
+                provider.scope,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(std::io::Error::other)?;
     let auth_service = Arc::new(AuthServiceImpl::new(
-        config.google_auth_client_id,
-        config.google_auth_client_secret,
-        config.google_auth_redirect_url,
-        config.google_auth_scope,
+        oidc_providers,
+        repositories.oauth_state.clone(),
+        Duration::from_secs(config.auth.oauth_state_ttl_secs),
     ));
 
-    println!("Starting API server on 0.0.0.0:8080");
+    let heartbeats = Arc::new(Heartbeats::default());
+    let health_service = Arc::new(HealthServiceImpl::new(
+        repositories.health.clone(),
+        storage_repository.clone(),
+        heartbeats.clone(),
+    ));
+
+    OutboxWorker::new(
+        repositories.delivery.clone(),
+        metrics.clone(),
+        device_client,
+        Duration::from_secs(config.notifications.outbox_poll_interval_secs),
+        config.notifications.outbox_max_attempts,
+        &heartbeats,
+    )
+    .start();
+    StatusExpiryWorker::new(status_service.clone(), &heartbeats).start();
+    // Webhooks only reach public endpoints.
+    WebhookWorker::new(
+        repositories.webhook.clone(),
+        repositories.webhook_delivery.clone(),
+        repositories.household.clone(),
+        storage_repository.clone(),
+        url_guard::client(Reach::Public).map_err(std::io::Error::other)?,
+        signed_url_ttl,
+        Duration::from_secs(config.notifications.webhook_poll_interval_secs),
+        config.notifications.webhook_max_attempts,
+        &heartbeats,
+    )
+    .start();
+
+    if let Some(mqtt) = config.notifications.mqtt {
+        tracing::info!(host = %mqtt.host, port = mqtt.port, "Bridging events to MQTT broker");
+        let (mqtt_client, mqtt_event_loop) = init_mqtt_client(
+            mqtt.host,
+            mqtt.port,
+            mqtt.client_id,
+            mqtt.username,
+            mqtt.password,
+            availability_topic(&mqtt.topic_prefix),
+        );
+        let mut bridge = MqttBridge::new(
+            mqtt_client.clone(),
+            mqtt.topic_prefix.clone(),
+            event_service.clone(),
+            status_service.clone(),
+            device_service.clone(),
+            household_service.clone(),
+            mqtt.decision_commands,
+            &heartbeats,
+        );
+        if mqtt.home_assistant_discovery {
+            tracing::info!("Publishing Home Assistant discovery configs");
+            bridge = bridge.with_home_assistant(HomeAssistant::new(
+                mqtt_client,
+                mqtt.home_assistant_discovery_prefix,
+                mqtt.topic_prefix,
+                Duration::from_secs(mqtt.home_assistant_refresh_interval_secs),
+                mqtt.decision_commands == MqttDecisions::Unsigned,
+                repositories.device.clone(),
+                storage_repository,
+                status_service.clone(),
+            ));
+        }
+        bridge.start(mqtt_event_loop);
+    }
+
+    let bind_address = config.server.bind_address;
+    tracing::info!(bind_address = %bind_address, "Starting API server");
 
     HttpServer::new(move || {
         let app_state = AppState {
@@ -104,23 +507,117 @@ async fn main() -> std::io::Result<()> {
             picture_service: picture_service.clone(),
             user_service: user_service.clone(),
             auth_service: auth_service.clone(),
+            device_service: device_service.clone(),
+            audit_service: audit_service.clone(),
+            event_service: event_service.clone(),
+            webhook_service: webhook_service.clone(),
+            session_service: session_service.clone(),
+            household_service: household_service.clone(),
+            health_service: health_service.clone(),
+            metrics: metrics.clone(),
+            metrics_token: config.server.metrics_token.clone(),
         };
 
-        App::new()
+        let mut app = App::new()
+            .wrap(RequestMetrics)
+            .wrap(RequestTrace)
             .app_data(web::Data::new(app_state))
+            .service(get_health)
+            .service(get_readiness)
+            .service(get_metrics)
             .service(post_picture)
-            .service(get_status)
-            .service(patch_authorised)
+            .service(put_device_callback)
+            .service(auth_providers)
             .service(auth_url)
+            .service(login)
             .service(callback)
+            .service(refresh)
+            .service(logout)
+            // Every route used by people needs a session, the device ones above are signed. The
+            // last wrap runs first, so calls rejected by `CheckAuthToken` are audited as well.
+            .service(
+                web::scope("/status")
+                    .wrap(CheckAuthToken)
+                    .wrap(AuditTrail)
+                    .service(get_status)
+                    .service(patch_status),
+            )
+            .service(
+                web::scope("/api/user")
+                    .wrap(CheckAuthToken)
+                    .wrap(AuditTrail)
+                    .service(get_me),
+            )
+            .service(
+                web::scope("/api/picture")
+                    .wrap(CheckAuthToken)
+                    .wrap(AuditTrail)
+                    .service(get_all_picture),
+            )
             .service(
-                // TODO: validate the logic with the middleware
-                web::scope("/api/admin")
+                web::scope("/api/devices")
                     .wrap(CheckAuthToken)
-                    .service(get_by_google_id),
+                    .wrap(AuditTrail)
+                    .service(register_device)
+                    .service(list_devices)
+                    .service(rename_device)
+                    .service(link_camera)
+                    .service(revoke_device),
             )
+            .service(
+                web::scope("/api/households")
+                    .wrap(CheckAuthToken)
+                    .wrap(AuditTrail)
+                    .service(create_household)
+                    .service(list_households)
+                    .service(get_household)
+                    .service(list_members)
+                    .service(change_member_role)
+                    .service(remove_member)
+                    .service(create_invitation)
+                    .service(list_invitations)
+                    .service(revoke_invitation),
+            )
+            .service(
+                web::scope("/api/invitations")
+                    .wrap(CheckAuthToken)
+                    .wrap(AuditTrail)
+                    .service(redeem_invitation),
+            )
+            .service(
+                // Recorded once when the stream opens, not per event sent.
+                web::scope("/api/events")
+                    .wrap(CheckAuthToken)
+                    .wrap(AuditTrail)
+                    .service(get_events),
+            )
+            .service(
+                web::scope("/api/webhooks")
+                    .wrap(CheckAuthToken)
+                    .wrap(AuditTrail)
+                    .service(register_webhook)
+                    .service(list_webhooks)
+                    .service(delete_webhook)
+                    .service(list_webhook_deliveries),
+            )
+            .service(
+                web::scope("/api/audit")
+                    .wrap(CheckAuthToken)
+                    .wrap(AuditTrail)
+                    .service(get_audit_entries)
+                    .service(verify_audit_log),
+            );
+
+        // Local files are protected by the signature of their url.
+        if let Some(local_repo) = &local_storage {
+            app = app
+                .app_data(web::Data::from(local_repo.clone()))
+                .service(get_upload);
+        }
+
+        app
     })
-    .bind("0.0.0.0:8080")?
+    .bind(bind_address)?
     .run()
     .await
 }
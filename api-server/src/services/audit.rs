@@ -0,0 +1,366 @@
+use async_trait::async_trait;
+use bson::Uuid;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use super::AuditService;
+use crate::errors::Error;
+use crate::models::{AuditEntry, AuditEvent, AuditFilter, AuditScope, HouseholdRole, GENESIS_HASH};
+use crate::payloads::{AuditEntryResponse, AuditQuery, AuditVerificationResponse};
+use crate::repositories::{AuditRepository, HouseholdRepository};
+
+const DEFAULT_QUERY_LIMIT: i64 = 100;
+const MAX_QUERY_LIMIT: i64 = 1000;
+const VERIFY_BATCH_SIZE: i64 = 500;
+const MAX_APPEND_ATTEMPTS: u32 = 5;
+
+pub struct AuditServiceImpl {
+    audit_repo: Arc<dyn AuditRepository>,
+    household_repo: Arc<dyn HouseholdRepository>,
+
+    // Read the whole log and verify the chain, which spans every household.
+    admin_user_ids: Vec<Uuid>,
+    // Appends from this process are serialised, other processes are caught by `append`.
+    append_lock: Mutex<()>,
+}
+
+impl AuditServiceImpl {
+    pub fn new(
+        audit_repo: Arc<dyn AuditRepository>,
+        household_repo: Arc<dyn HouseholdRepository>,
+        admin_user_ids: Vec<Uuid>,
+    ) -> Self {
+        Self {
+            audit_repo,
+            household_repo,
+            admin_user_ids,
+            append_lock: Mutex::new(()),
+        }
+    }
+
+    fn is_admin(&self, user_id: Uuid) -> bool {
+        self.admin_user_ids.contains(&user_id)
+    }
+
+    // Owners read what happened in their households, everyone reads what they did.
+    async fn scope_of(&self, user_id: Uuid) -> Result<Option<AuditScope>, Error> {
+        if self.is_admin(user_id) {
+            return Ok(None);
+        }
+
+        let household_ids = self
+            .household_repo
+            .find_by_member(user_id)
+            .await?
+            .into_iter()
+            .filter(|household| household.role_of(user_id) == Some(HouseholdRole::Owner))
+            .map(|household| household.id)
+            .collect();
+
+        Ok(Some(AuditScope {
+            actor: user_id,
+            household_ids,
+        }))
+    }
+
+    fn parse_uuid(value: Option<String>, name: &str) -> Result<Option<Uuid>, Error> {
+        value
+            .map(|value| {
+                Uuid::parse_str(&value)
+                    .map_err(|_| Error::UuidFormat(format!("Invalid {} format", name)))
+            })
+            .transpose()
+    }
+}
+
+#[async_trait]
+impl AuditService for AuditServiceImpl {
+    async fn record(&self, event: AuditEvent) -> Result<(), Error> {
+        let _guard = self.append_lock.lock().await;
+
+        for _ in 0..MAX_APPEND_ATTEMPTS {
+            let (sequence, previous_hash) = match self.audit_repo.last().await? {
+                Some(last) => (last.sequence + 1, last.hash),
+                None => (1, GENESIS_HASH.to_string()),
+            };
+
+            let entry = AuditEntry::new(sequence, previous_hash, event.clone());
+            match self.audit_repo.append(&entry).await {
+                Err(Error::Conflict(_)) => continue,
+                result => return result,
+            }
+        }
+
+        Err(Error::Conflict(
+            "Audit log is busy, entry was not recorded".to_string(),
+        ))
+    }
+
+    async fn query(
+        &self,
+        user_id: Uuid,
+        query: AuditQuery,
+    ) -> Result<Vec<AuditEntryResponse>, Error> {
+        let filter = AuditFilter {
+            scope: self.scope_of(user_id).await?,
+            action: query.action,
+            actor: Self::parse_uuid(query.actor, "actor")?,
+            device_id: Self::parse_uuid(query.device_id, "device ID")?,
+            target: query.target,
+            from: query.from,
+            to: query.to,
+            limit: query
+                .limit
+                .unwrap_or(DEFAULT_QUERY_LIMIT)
+                .clamp(1, MAX_QUERY_LIMIT),
+        };
+
+        let entries = self.audit_repo.find(&filter).await?;
+
+        Ok(entries.into_iter().map(AuditEntryResponse::new).collect())
+    }
+
+    // Walks the whole chain, a missing sequence number means an entry was removed.
+    async fn verify(&self, user_id: Uuid) -> Result<AuditVerificationResponse, Error> {
+        if !self.is_admin(user_id) {
+            return Err(Error::Forbidden(
+                "the audit log can only be verified by an admin".to_string(),
+            ));
+        }
+
+        let mut expected_sequence = 1;
+        let mut previous_hash = GENESIS_HASH.to_string();
+        let mut checked = 0;
+
+        loop {
+            let entries = self
+                .audit_repo
+                .find_from(expected_sequence, VERIFY_BATCH_SIZE)
+                .await?;
+            if entries.is_empty() {
+                break;
+            }
+
+            for entry in entries {
+                let reason = if entry.sequence != expected_sequence {
+                    Some(format!("entry {} is missing", expected_sequence))
+                } else if entry.previous_hash != previous_hash {
+                    Some("previous hash doesn't match the chain".to_string())
+                } else if entry.compute_hash() != entry.hash {
+                    Some("entry content doesn't match its hash".to_string())
+                } else {
+                    None
+                };
+
+                if let Some(reason) = reason {
+                    return Ok(AuditVerificationResponse {
+                        valid: false,
+                        checked,
+                        last_sequence: None,
+                        last_hash: None,
+                        broken_at: Some(expected_sequence),
+                        reason: Some(reason),
+                    });
+                }
+
+                checked += 1;
+                expected_sequence = entry.sequence + 1;
+                previous_hash = entry.hash;
+            }
+        }
+
+        Ok(AuditVerificationResponse {
+            valid: true,
+            checked,
+            last_sequence: (checked > 0).then_some(expected_sequence - 1),
+            last_hash: (checked > 0).then_some(previous_hash),
+            broken_at: None,
+            reason: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AuditAction, AuditContext, Household, HouseholdMember};
+    use crate::repositories::InMemoryRepository;
+    use async_trait::async_trait;
+
+    const ADMIN: Uuid = Uuid::from_bytes([1; 16]);
+
+    fn service(audit_repo: Arc<dyn AuditRepository>) -> AuditServiceImpl {
+        AuditServiceImpl::new(audit_repo, Arc::new(InMemoryRepository::new()), vec![ADMIN])
+    }
+
+    fn event(target: &str) -> AuditEvent {
+        AuditEvent {
+            action: AuditAction::StatusDecided,
+            context: AuditContext::new(Some(Uuid::new()), None, None),
+            household_id: None,
+            target: Some(target.to_string()),
+            before: None,
+            after: None,
+        }
+    }
+
+    // Serves a log as stored, whatever was done to it.
+    struct StoredLog(Vec<AuditEntry>);
+
+    #[async_trait]
+    impl AuditRepository for StoredLog {
+        async fn last(&self) -> Result<Option<AuditEntry>, Error> {
+            Ok(self.0.last().cloned())
+        }
+
+        async fn append(&self, _entry: &AuditEntry) -> Result<(), Error> {
+            Err(Error::Internal("the stored log is read only".to_string()))
+        }
+
+        async fn find(&self, _filter: &AuditFilter) -> Result<Vec<AuditEntry>, Error> {
+            Err(Error::Internal(
+                "the stored log is only verified".to_string(),
+            ))
+        }
+
+        async fn find_from(&self, sequence: i64, limit: i64) -> Result<Vec<AuditEntry>, Error> {
+            Ok(self
+                .0
+                .iter()
+                .filter(|entry| entry.sequence >= sequence)
+                .take(limit as usize)
+                .cloned()
+                .collect())
+        }
+    }
+
+    fn chain(length: i64) -> Vec<AuditEntry> {
+        let mut entries: Vec<AuditEntry> = Vec::new();
+        for sequence in 1..=length {
+            let previous_hash = entries
+                .last()
+                .map_or(GENESIS_HASH.to_string(), |entry| entry.hash.clone());
+            entries.push(AuditEntry::new(
+                sequence,
+                previous_hash,
+                event(&sequence.to_string()),
+            ));
+        }
+        entries
+    }
+
+    #[actix_web::test]
+    async fn recorded_entries_form_a_valid_chain() {
+        let service = service(Arc::new(InMemoryRepository::new()));
+        for target in ["a", "b", "c"] {
+            service.record(event(target)).await.unwrap();
+        }
+
+        let verification = service.verify(ADMIN).await.unwrap();
+        assert!(verification.valid);
+        assert_eq!(verification.checked, 3);
+        assert_eq!(verification.last_sequence, Some(3));
+    }
+
+    #[actix_web::test]
+    async fn edited_entry_breaks_the_chain() {
+        let mut entries = chain(3);
+        entries[1].target = Some("edited".to_string());
+
+        let verification = service(Arc::new(StoredLog(entries)))
+            .verify(ADMIN)
+            .await
+            .unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.broken_at, Some(2));
+        assert_eq!(verification.checked, 1);
+    }
+
+    #[actix_web::test]
+    async fn removed_entry_breaks_the_chain() {
+        let mut entries = chain(3);
+        entries.remove(1);
+
+        let verification = service(Arc::new(StoredLog(entries)))
+            .verify(ADMIN)
+            .await
+            .unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.broken_at, Some(2));
+        assert_eq!(verification.reason.as_deref(), Some("entry 2 is missing"));
+    }
+
+    #[actix_web::test]
+    async fn rehashed_entry_breaks_the_link_to_the_next_one() {
+        let mut entries = chain(3);
+        entries[1].target = Some("edited".to_string());
+        entries[1].hash = entries[1].compute_hash();
+
+        let verification = service(Arc::new(StoredLog(entries)))
+            .verify(ADMIN)
+            .await
+            .unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.broken_at, Some(3));
+    }
+
+    #[actix_web::test]
+    async fn only_admins_verify_the_chain() {
+        let service = service(Arc::new(InMemoryRepository::new()));
+
+        let verification = service.verify(Uuid::new()).await;
+        assert!(matches!(verification, Err(Error::Forbidden(_))));
+    }
+
+    #[actix_web::test]
+    async fn owners_read_the_entries_of_their_households() {
+        let repo = Arc::new(InMemoryRepository::new());
+        let service = AuditServiceImpl::new(repo.clone(), repo.clone(), vec![ADMIN]);
+        let (owner, approver) = (Uuid::new(), Uuid::new());
+        let household = Household::new("home".to_string(), owner);
+        HouseholdRepository::insert(repo.as_ref(), &household)
+            .await
+            .unwrap();
+        let member = HouseholdMember {
+            user_id: approver,
+            role: HouseholdRole::Approver,
+            joined_at: chrono::Local::now(),
+        };
+        repo.add_member(household.id, &member).await.unwrap();
+
+        let mut decided = event("decided");
+        decided.context.actor = Some(approver);
+        decided.household_id = Some(household.id);
+        let mut elsewhere = event("elsewhere");
+        elsewhere.household_id = Some(Uuid::new());
+        for event in [decided, elsewhere, event("unrelated")] {
+            service.record(event).await.unwrap();
+        }
+
+        let targets = |entries: Vec<AuditEntryResponse>| {
+            entries
+                .into_iter()
+                .filter_map(|entry| entry.target)
+                .collect::<Vec<_>>()
+        };
+        let query = || AuditQuery {
+            action: None,
+            actor: None,
+            device_id: None,
+            target: None,
+            from: None,
+            to: None,
+            limit: None,
+        };
+
+        // The owner sees who let someone in, the approver only what they did.
+        let entries = service.query(owner, query()).await.unwrap();
+        assert_eq!(targets(entries), vec!["decided"]);
+        let entries = service.query(approver, query()).await.unwrap();
+        assert_eq!(targets(entries), vec!["decided"]);
+        let entries = service.query(Uuid::new(), query()).await.unwrap();
+        assert!(entries.is_empty());
+        let entries = service.query(ADMIN, query()).await.unwrap();
+        assert_eq!(entries.len(), 3);
+    }
+}
@@ -1,114 +1,126 @@
 use async_trait::async_trait;
-use chrono::TimeDelta;
-use oauth2::basic::BasicClient;
-use oauth2::{
-    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, RedirectUrl, RevocationUrl,
-    Scope, TokenResponse, TokenUrl,
-};
-
-use super::AuthService;
+use chrono::{TimeDelta, Utc};
+use oauth2::{CsrfToken, PkceCodeChallenge, TokenResponse};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{AuthService, OidcProvider};
 use crate::errors::Error;
-use crate::models::{Token, User};
+use crate::models::{LoginResponseType, OAuthState, Token};
 use crate::payloads::UserInfo;
+use crate::repositories::OAuthStateRepository;
 
 pub struct AuthServiceImpl {
-    client: reqwest::Client,
-    oauth_client: BasicClient,
-    scope: String,
-    auth_url: String,
-    token_url: String,
-    revocation_url: String,
-    user_info_url: String,
+    // The first one is used when a login doesn't name its provider.
+    providers: Vec<OidcProvider>,
+    state_repo: Arc<dyn OAuthStateRepository>,
+    // How long a user has to complete the login once the url is issued.
+    state_ttl: Duration,
 }
 
 impl AuthServiceImpl {
     pub fn new(
-        client_id: String,
-        client_secret: String,
-        redirect_url: String,
-        scope: String,
+        providers: Vec<OidcProvider>,
+        state_repo: Arc<dyn OAuthStateRepository>,
+        state_ttl: Duration,
     ) -> Self {
-        let oauth_client = BasicClient::new(ClientId::new(client_id))
-            .set_client_secret(ClientSecret::new(client_secret))
-            .set_redirect_uri(
-                RedirectUrl::new(redirect_url).expect("Error while trying to set the redirect url"),
-            );
         Self {
-            client: reqwest::Client::new(),
-            oauth_client,
-            scope,
-            auth_url: "https://accounts.google.com/o/oauth2/v2/auth".to_string(),
-            token_url: "https://www.googleapis.com/oauth2/v3/token".to_string(),
-            revocation_url: "https://oauth2.googleapis.com/revoke".to_string(),
-            user_info_url: "https://www.googleapis.com/oauth2/v3/userinfo".to_string(),
+            providers,
+            state_repo,
+            state_ttl,
+        }
+    }
+
+    fn find_provider(&self, name: Option<&str>) -> Result<&OidcProvider, Error> {
+        match name {
+            Some(name) => self
+                .providers
+                .iter()
+                .find(|provider| provider.name() == name)
+                .ok_or_else(|| Error::BadRequest(format!("unknown provider: {}", name))),
+            None => self
+                .providers
+                .first()
+                .ok_or_else(|| Error::Internal("no identity provider configured".to_string())),
         }
     }
 }
 
 #[async_trait]
 impl AuthService for AuthServiceImpl {
+    fn providers(&self) -> Vec<String> {
+        self.providers
+            .iter()
+            .map(|provider| provider.name().to_string())
+            .collect()
+    }
+
     async fn get_authorisation_url(
         &self,
-        response_type: Option<String>,
+        provider: Option<String>,
+        response_type: LoginResponseType,
+        invitation: Option<String>,
     ) -> Result<(String, String), Error> {
-        let auth_url = AuthUrl::new(self.auth_url.clone()).map_err(|e| Error::Parse(e.to_string()));
-
-        let token_url =
-            TokenUrl::new(self.token_url.clone()).map_err(|e| Error::Parse(e.to_string()));
-
-        let client = self
-            .oauth_client
-            .clone()
-            .set_auth_uri(auth_url?)
-            .set_token_uri(token_url?)
-            .set_revocation_url(
-                RevocationUrl::new(self.revocation_url.clone())
-                    .expect("Invalid revocation endpoints URL"),
-            );
-
-        let random_token = CsrfToken::new_random();
-        let final_token = if let Some(value) = response_type {
-            CsrfToken::new(format!("{}:{}", random_token.secret(), value))
-        } else {
-            random_token
-        };
-
-        let (authorize_url, crsf_state) = client
-            .authorize_url(|| final_token)
-            .add_scopes(
-                self.scope
-                    .split_whitespace()
-                    .map(|s| Scope::new(s.to_string())),
-            )
-            .url();
-
-        Ok((authorize_url.to_string(), crsf_state.secret().to_string()))
+        let provider = self.find_provider(provider.as_deref())?;
+
+        // Abandoned logins would pile up otherwise.
+        if let Err(e) = self.state_repo.delete_expired().await {
+            tracing::warn!(error = %e, "Failed to delete expired login states");
+        }
+
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        let nonce = CsrfToken::new_random();
+
+        let (authorize_url, crsf_state) = provider
+            .authorisation_url(pkce_challenge, nonce.secret())
+            .await?;
+
+        let expires_at = Utc::now()
+            + chrono::Duration::from_std(self.state_ttl)
+                .map_err(|e| Error::Parse(e.to_string()))?;
+        self.state_repo
+            .insert(&OAuthState::new(
+                crsf_state.secret().to_string(),
+                provider.name().to_string(),
+                pkce_verifier.secret().to_string(),
+                nonce.secret().to_string(),
+                response_type,
+                invitation,
+                expires_at,
+            ))
+            .await?;
+
+        Ok((authorize_url, crsf_state.secret().to_string()))
     }
 
     async fn exchange_code_for_token(
         &self,
         code: String,
-        _state: String,
-    ) -> Result<(UserInfo, Token), Error> {
-        let auth_url = AuthUrl::new(self.auth_url.clone());
-        let token_url =
-            TokenUrl::new(self.token_url.clone()).map_err(|e| Error::Parse(e.to_string()));
-
-        let client = self
-            .oauth_client
-            .clone()
-            .set_auth_uri(auth_url.map_err(|e| Error::Parse(e.to_string()))?)
-            .set_token_uri(token_url.map_err(|e| Error::Parse(e.to_string()))?);
-
-        let http_client = self.client.clone();
-
-        let token_response = client
-            .exchange_code(AuthorizationCode::new(code))
-            .request_async(&http_client)
-            .await
-            .map_err(|e| Error::Internal(e.to_string()))?;
-
+        state: String,
+    ) -> Result<(UserInfo, Token, OAuthState), Error> {
+        // Unknown, expired or already used: the callback isn't the end of a login we started.
+        let oauth_state = self
+            .state_repo
+            .take(&state)
+            .await?
+            .ok_or_else(|| Error::Unauthorized("invalid or expired login state".to_string()))?;
+        let provider = self.find_provider(Some(&oauth_state.provider))?;
+
+        let token_response = provider
+            .exchange_code(code, oauth_state.pkce_verifier.clone())
+            .await?;
+
+        let id_token = token_response
+            .extra_fields()
+            .id_token
+            .as_deref()
+            .ok_or_else(|| Error::Unauthorized("provider returned no ID token".to_string()))?;
         let access_token = token_response.access_token().secret();
+
+        let user_info = provider
+            .identify(id_token, access_token, &oauth_state.nonce)
+            .await?;
+
         let refresh_token = token_response
             .refresh_token()
             .map(|token| token.secret().to_string())
@@ -127,18 +139,6 @@ impl AuthService for AuthServiceImpl {
             })
             .unwrap_or_default();
 
-        let user_info_response: reqwest::Response = http_client
-            .get(&self.user_info_url)
-            .bearer_auth(access_token)
-            .send()
-            .await
-            .map_err(|e| Error::Internal(e.to_string()))?;
-
-        let user_info: UserInfo = user_info_response
-            .json()
-            .await
-            .map_err(|e| Error::JSONUnmarshall(e.to_string()))?;
-
         let token = Token::new(
             token_type,
             access_token.to_string(),
@@ -147,37 +147,6 @@ impl AuthService for AuthServiceImpl {
             Some(scopes),
         );
 
-        Ok((user_info, token))
-    }
-
-    async fn verify_token(&self, token: String) -> Result<(bool, User), Error> {
-        let res = self
-            .client
-            .get("https://oauth2.googleapis.com/tokeninfo")
-            .query(&[("access_token", &token)])
-            .send()
-            .await
-            .map_err(|e| Error::Service(e.to_string()))?;
-
-        let user_info_response: reqwest::Response = self
-            .client
-            .get(&self.user_info_url)
-            .bearer_auth(&token)
-            .send()
-            .await
-            .map_err(|e| Error::Internal(e.to_string()))?;
-
-        let user_info: UserInfo = user_info_response
-            .json()
-            .await
-            .map_err(|e| Error::JSONUnmarshall(e.to_string()))?;
-
-        let user = User::new(
-            user_info.id,
-            user_info.email,
-            user_info.name,
-            user_info.picture,
-        );
-        Ok((res.status().is_success(), user))
+        Ok((user_info, token, oauth_state))
     }
 }
@@ -1 +1,486 @@
+use async_trait::async_trait;
+use bson::Uuid;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+use super::{DeviceService, HouseholdService};
+use crate::errors::Error;
+use crate::models::{Device, DeviceType, HouseholdRole};
+use crate::repositories::DeviceRepository;
+use crate::url_guard::{self, Reach};
+
+const DEVICE_SECRET_LEN: usize = 32;
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(30);
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub struct DeviceServiceImpl {
+    device_repo: Arc<dyn DeviceRepository>,
+    household_service: Arc<dyn HouseholdService>,
+    // Guarded by `url_guard`, the capture url comes from the user.
+    client: reqwest::Client,
+
+    replay_window: Duration,
+    // MACs accepted within the replay window, with their timestamp. Keyed on the decoded
+    // bytes so a re-encoding of the same signature (e.g. upper-case hex) is still a replay.
+    seen_signatures: Mutex<HashMap<Vec<u8>, i64>>,
+}
+
+impl DeviceServiceImpl {
+    pub fn new(
+        device_repo: Arc<dyn DeviceRepository>,
+        household_service: Arc<dyn HouseholdService>,
+        client: reqwest::Client,
+        replay_window: Duration,
+    ) -> Self {
+        Self {
+            device_repo,
+            household_service,
+            client,
+            replay_window,
+            seen_signatures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn verify_signature(
+        device: &Device,
+        timestamp: i64,
+        signature: &str,
+        body: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        let signature = hex::decode(signature)
+            .map_err(|_| Error::Unauthorized("invalid signature".to_string()))?;
+
+        let mut mac = HmacSha256::new_from_slice(device.secret.as_bytes())
+            .map_err(|e| Error::Internal(e.to_string()))?;
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(body);
+
+        mac.verify_slice(&signature)
+            .map_err(|_| Error::Unauthorized("invalid signature".to_string()))?;
+
+        Ok(signature)
+    }
+
+    // NOTE: Kept in memory, which is enough as long as a single api-server runs.
+    fn check_replay(&self, timestamp: i64, signature: &[u8]) -> Result<(), Error> {
+        let oldest_allowed = Utc::now().timestamp() - self.replay_window.as_secs() as i64;
+
+        let mut seen_signatures = self
+            .seen_signatures
+            .lock()
+            .map_err(|_| Error::Internal("replay cache lock poisoned".to_string()))?;
+        seen_signatures.retain(|_, seen_at| *seen_at >= oldest_allowed);
+
+        if seen_signatures.contains_key(signature) {
+            return Err(Error::Unauthorized("replayed request".to_string()));
+        }
+        seen_signatures.insert(signature.to_vec(), timestamp);
+
+        Ok(())
+    }
+
+    fn generate_secret() -> String {
+        let mut secret = [0u8; DEVICE_SECRET_LEN];
+        rand::thread_rng().fill_bytes(&mut secret);
+        hex::encode(secret)
+    }
+
+    // A controller only follows a camera of its own household.
+    async fn validate_camera(
+        &self,
+        user_id: Uuid,
+        camera_id: Uuid,
+        household_id: Option<Uuid>,
+    ) -> Result<(), Error> {
+        let camera = self
+            .find_authorized(user_id, camera_id, HouseholdRole::Owner)
+            .await?;
+        if camera.device_type != DeviceType::Esp32Cam {
+            return Err(Error::BadRequest(format!(
+                "device {} is not a camera",
+                camera_id
+            )));
+        }
+        if camera.household_id != household_id {
+            return Err(Error::BadRequest(format!(
+                "camera {} belongs to another household",
+                camera_id
+            )));
+        }
+
+        Ok(())
+    }
+
+    // Devices of other households are reported as not found to avoid leaking their IDs.
+    async fn find_authorized(
+        &self,
+        user_id: Uuid,
+        device_id: Uuid,
+        required: HouseholdRole,
+    ) -> Result<Device, Error> {
+        let device = self
+            .device_repo
+            .find_by_id(device_id)
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("Device not found for ID: {}", device_id)))?;
+
+        self.household_service
+            .authorize_device(user_id, &device, required)
+            .await?;
+
+        Ok(device)
+    }
+}
+
+#[async_trait]
+impl DeviceService for DeviceServiceImpl {
+    async fn register(
+        &self,
+        user_id: Uuid,
+        household_id: Uuid,
+        name: String,
+        device_type: DeviceType,
+        camera_id: Option<Uuid>,
+        callback_url: Option<String>,
+        capture_url: Option<String>,
+    ) -> Result<Device, Error> {
+        if name.trim().is_empty() {
+            return Err(Error::Empty("Device name is empty".to_string()));
+        }
+
+        self.household_service
+            .authorize(user_id, household_id, HouseholdRole::Owner)
+            .await?;
+
+        if device_type != DeviceType::Esp32Main && (camera_id.is_some() || callback_url.is_some()) {
+            return Err(Error::BadRequest(
+                "only controllers have a camera and a callback url".to_string(),
+            ));
+        }
+        if device_type != DeviceType::Esp32Cam && capture_url.is_some() {
+            return Err(Error::BadRequest(
+                "only cameras have a capture url".to_string(),
+            ));
+        }
+
+        let mut device = Device::new(
+            user_id,
+            household_id,
+            name.trim().to_string(),
+            device_type,
+            Self::generate_secret(),
+        );
+
+        if let Some(camera_id) = camera_id {
+            self.validate_camera(user_id, camera_id, Some(household_id))
+                .await?;
+            device.camera_id = Some(camera_id);
+        }
+        if let Some(callback_url) = callback_url {
+            device.callback_url =
+                Some(url_guard::validate(&callback_url, "callback url", Reach::Lan).await?);
+        }
+        if let Some(capture_url) = capture_url {
+            device.capture_url =
+                Some(url_guard::validate(&capture_url, "capture url", Reach::Lan).await?);
+        }
+
+        self.device_repo.insert(&device).await?;
+
+        Ok(device)
+    }
+
+    async fn authenticate(
+        &self,
+        device_id: Uuid,
+        timestamp: i64,
+        signature: &str,
+        body: &[u8],
+    ) -> Result<Device, Error> {
+        let device = self
+            .device_repo
+            .find_by_id(device_id)
+            .await?
+            .ok_or_else(|| Error::Unauthorized("unknown device".to_string()))?;
+
+        if device.is_revoked() {
+            return Err(Error::Unauthorized("device revoked".to_string()));
+        }
+
+        let drift = (Utc::now().timestamp() - timestamp).unsigned_abs();
+        if drift > self.replay_window.as_secs() {
+            return Err(Error::Unauthorized(
+                "timestamp outside the allowed window".to_string(),
+            ));
+        }
+
+        let signature = Self::verify_signature(&device, timestamp, signature, body)?;
+        self.check_replay(timestamp, &signature)?;
+
+        Ok(device)
+    }
+
+    async fn list(&self, user_id: Uuid) -> Result<Vec<Device>, Error> {
+        self.household_service
+            .accessible_devices(user_id, HouseholdRole::Viewer)
+            .await
+    }
+
+    async fn rename(&self, user_id: Uuid, device_id: Uuid, name: String) -> Result<Device, Error> {
+        if name.trim().is_empty() {
+            return Err(Error::Empty("Device name is empty".to_string()));
+        }
+
+        self.find_authorized(user_id, device_id, HouseholdRole::Owner)
+            .await?;
+
+        self.device_repo
+            .update_name(device_id, name.trim().to_string())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("Device not found for ID: {}", device_id)))
+    }
+
+    async fn link_camera(
+        &self,
+        user_id: Uuid,
+        device_id: Uuid,
+        camera_id: Option<Uuid>,
+    ) -> Result<Device, Error> {
+        let device = self
+            .find_authorized(user_id, device_id, HouseholdRole::Owner)
+            .await?;
+        if device.device_type != DeviceType::Esp32Main {
+            return Err(Error::BadRequest(
+                "only controllers can be linked to a camera".to_string(),
+            ));
+        }
+
+        if let Some(camera_id) = camera_id {
+            self.validate_camera(user_id, camera_id, device.household_id)
+                .await?;
+        }
+
+        self.device_repo
+            .update_camera_id(device_id, camera_id)
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("Device not found for ID: {}", device_id)))
+    }
+
+    async fn update_callback_url(
+        &self,
+        device: &Device,
+        callback_url: String,
+    ) -> Result<Device, Error> {
+        if device.device_type != DeviceType::Esp32Main {
+            return Err(Error::BadRequest(
+                "only controllers have a callback url".to_string(),
+            ));
+        }
+
+        let callback_url = url_guard::validate(&callback_url, "callback url", Reach::Lan).await?;
+
+        self.device_repo
+            .update_callback_url(device.id, callback_url)
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("Device not found for ID: {}", device.id)))
+    }
+
+    async fn revoke(&self, user_id: Uuid, device_id: Uuid) -> Result<Device, Error> {
+        let device = self
+            .find_authorized(user_id, device_id, HouseholdRole::Owner)
+            .await?;
+        if device.is_revoked() {
+            return Ok(device);
+        }
+
+        self.device_repo
+            .revoke(device_id)
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("Device not found for ID: {}", device_id)))
+    }
+
+    async fn trigger_capture(&self, user_id: Uuid, camera_id: Uuid) -> Result<(), Error> {
+        let camera = self
+            .find_authorized(user_id, camera_id, HouseholdRole::Approver)
+            .await?;
+        if camera.device_type != DeviceType::Esp32Cam || camera.is_revoked() {
+            return Err(Error::BadRequest(format!(
+                "device {} is not an active camera",
+                camera_id
+            )));
+        }
+        let capture_url = camera
+            .capture_url
+            .ok_or_else(|| Error::BadRequest(format!("camera {} has no capture url", camera_id)))?;
+
+        // The camera uploads the picture before answering, the status comes through that upload.
+        let response = self
+            .client
+            .get(&capture_url)
+            .timeout(CAPTURE_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| Error::Service(format!("Failed to reach {}: {}", capture_url, e)))?;
+
+        if !response.status().is_success() {
+            return Err(Error::Service(format!(
+                "{} responded with error status: {}",
+                capture_url,
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::InMemoryRepository;
+    use crate::services::HouseholdServiceImpl;
+
+    const WINDOW: Duration = Duration::from_secs(300);
+
+    async fn setup() -> (DeviceServiceImpl, Arc<InMemoryRepository>, Device) {
+        let repo = Arc::new(InMemoryRepository::new());
+        let household_service = Arc::new(HouseholdServiceImpl::new(
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            Duration::from_secs(3600),
+        ));
+        let service = DeviceServiceImpl::new(
+            repo.clone(),
+            household_service,
+            reqwest::Client::new(),
+            WINDOW,
+        );
+
+        let device = Device::new(
+            Uuid::new(),
+            Uuid::new(),
+            "door".to_string(),
+            DeviceType::Esp32Cam,
+            DeviceServiceImpl::generate_secret(),
+        );
+        DeviceRepository::insert(repo.as_ref(), &device)
+            .await
+            .unwrap();
+
+        (service, repo, device)
+    }
+
+    fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{}.", timestamp).as_bytes());
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    #[actix_web::test]
+    async fn signed_request_is_accepted_once() {
+        let (service, _, device) = setup().await;
+        let timestamp = Utc::now().timestamp();
+        let signature = sign(&device.secret, timestamp, b"picture");
+
+        let authenticated = service
+            .authenticate(device.id, timestamp, &signature, b"picture")
+            .await
+            .unwrap();
+        assert_eq!(authenticated.id, device.id);
+
+        let replayed = service
+            .authenticate(device.id, timestamp, &signature, b"picture")
+            .await;
+        assert!(matches!(replayed, Err(Error::Unauthorized(_))));
+    }
+
+    #[actix_web::test]
+    async fn re_encoded_signature_is_a_replay() {
+        let (service, _, device) = setup().await;
+        let timestamp = Utc::now().timestamp();
+        let signature = sign(&device.secret, timestamp, b"picture");
+
+        service
+            .authenticate(device.id, timestamp, &signature, b"picture")
+            .await
+            .unwrap();
+
+        let replayed = service
+            .authenticate(device.id, timestamp, &signature.to_uppercase(), b"picture")
+            .await;
+        assert!(matches!(replayed, Err(Error::Unauthorized(_))));
+    }
+
+    #[actix_web::test]
+    async fn tampered_body_is_rejected() {
+        let (service, _, device) = setup().await;
+        let timestamp = Utc::now().timestamp();
+        let signature = sign(&device.secret, timestamp, b"picture");
+
+        let result = service
+            .authenticate(device.id, timestamp, &signature, b"another picture")
+            .await;
+        assert!(matches!(result, Err(Error::Unauthorized(_))));
+    }
+
+    #[actix_web::test]
+    async fn other_secret_is_rejected() {
+        let (service, _, device) = setup().await;
+        let timestamp = Utc::now().timestamp();
+        let signature = sign("not the secret", timestamp, b"picture");
+
+        let result = service
+            .authenticate(device.id, timestamp, &signature, b"picture")
+            .await;
+        assert!(matches!(result, Err(Error::Unauthorized(_))));
+    }
+
+    #[actix_web::test]
+    async fn timestamp_outside_the_window_is_rejected() {
+        let (service, _, device) = setup().await;
+        let window = WINDOW.as_secs() as i64;
+
+        for timestamp in [
+            Utc::now().timestamp() - window - 10,
+            Utc::now().timestamp() + window + 10,
+        ] {
+            let signature = sign(&device.secret, timestamp, b"picture");
+            let result = service
+                .authenticate(device.id, timestamp, &signature, b"picture")
+                .await;
+            assert!(matches!(result, Err(Error::Unauthorized(_))));
+        }
+    }
+
+    #[actix_web::test]
+    async fn revoked_or_unknown_device_is_rejected() {
+        let (service, repo, device) = setup().await;
+        let timestamp = Utc::now().timestamp();
+        let signature = sign(&device.secret, timestamp, b"picture");
+
+        let unknown = service
+            .authenticate(Uuid::new(), timestamp, &signature, b"picture")
+            .await;
+        assert!(matches!(unknown, Err(Error::Unauthorized(_))));
+
+        DeviceRepository::revoke(repo.as_ref(), device.id)
+            .await
+            .unwrap();
+        let revoked = service
+            .authenticate(device.id, timestamp, &signature, b"picture")
+            .await;
+        assert!(matches!(revoked, Err(Error::Unauthorized(_))));
+    }
+}
@@ -0,0 +1,177 @@
+use bson::Uuid;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+use super::EventService;
+use crate::models::EventKind;
+use crate::payloads::{Event, StatusResponse};
+
+const CHANNEL_CAPACITY: usize = 256;
+
+// Keeps the recent events in memory so a client can resume with `Last-Event-ID`.
+pub struct EventServiceImpl {
+    sender: broadcast::Sender<Event>,
+    history: Mutex<VecDeque<Event>>,
+    history_size: usize,
+    next_id: AtomicU64,
+}
+
+impl EventServiceImpl {
+    pub fn new(history_size: usize) -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+
+        // NOTE: Ids start from the current time so they keep growing across restarts,
+        // a client resuming after a restart then gets the new events instead of nothing.
+        let first_id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+
+        Self {
+            sender,
+            history: Mutex::new(VecDeque::with_capacity(history_size)),
+            history_size,
+            next_id: AtomicU64::new(first_id),
+        }
+    }
+}
+
+impl EventService for EventServiceImpl {
+    fn publish(
+        &self,
+        kind: EventKind,
+        user_id: Uuid,
+        household_id: Option<Uuid>,
+        status: StatusResponse,
+    ) -> Event {
+        let event = Event {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            kind,
+            user_id,
+            household_id,
+            status,
+        };
+
+        // Sent while holding the history so a subscriber never sees an event twice.
+        let mut history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        history.push_back(event.clone());
+        while history.len() > self.history_size {
+            history.pop_front();
+        }
+
+        // Only fails when nobody listens.
+        let _ = self.sender.send(event.clone());
+
+        event
+    }
+
+    fn subscribe(
+        &self,
+        user_id: Uuid,
+        household_ids: &[Uuid],
+        last_event_id: Option<u64>,
+    ) -> (Vec<Event>, broadcast::Receiver<Event>) {
+        let history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        let receiver = self.sender.subscribe();
+
+        let missed = match last_event_id {
+            Some(last_event_id) => history
+                .iter()
+                .filter(|event| {
+                    event.id > last_event_id && event.is_visible_to(user_id, household_ids)
+                })
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+
+        (missed, receiver)
+    }
+
+    fn subscribe_all(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Picture, Status};
+
+    fn status() -> StatusResponse {
+        let picture = Picture::new(Uuid::new(), Uuid::new(), "picture.jpg".to_string());
+        let status = Status::new(picture.id, chrono::Utc::now());
+        StatusResponse::new(status, picture, String::new(), Vec::new())
+    }
+
+    #[test]
+    fn household_events_go_to_its_members() {
+        let (user_id, household_id) = (Uuid::new(), Uuid::new());
+        let service = EventServiceImpl::new(10);
+
+        let own = service.publish(EventKind::CaptureCreated, user_id, None, status());
+        assert!(own.is_visible_to(user_id, &[]));
+        assert!(!own.is_visible_to(Uuid::new(), &[household_id]));
+
+        // Shown through the household only, to its user as to every other member.
+        let shared = service.publish(
+            EventKind::CaptureCreated,
+            user_id,
+            Some(household_id),
+            status(),
+        );
+        assert!(shared.is_visible_to(Uuid::new(), &[household_id]));
+        assert!(!shared.is_visible_to(user_id, &[]));
+    }
+
+    #[test]
+    fn resuming_replays_the_missed_visible_events() {
+        let (user_id, household_id) = (Uuid::new(), Uuid::new());
+        let service = EventServiceImpl::new(10);
+
+        let first = service.publish(
+            EventKind::CaptureCreated,
+            user_id,
+            Some(household_id),
+            status(),
+        );
+        let second = service.publish(
+            EventKind::StatusApproved,
+            user_id,
+            Some(household_id),
+            status(),
+        );
+        service.publish(
+            EventKind::CaptureCreated,
+            Uuid::new(),
+            Some(Uuid::new()),
+            status(),
+        );
+        service.publish(EventKind::CaptureCreated, Uuid::new(), None, status());
+
+        let (missed, _) = service.subscribe(Uuid::new(), &[household_id], Some(first.id));
+        let ids: Vec<u64> = missed.iter().map(|event| event.id).collect();
+        assert_eq!(ids, vec![second.id]);
+
+        // A fresh connection starts with the live events only.
+        let (missed, _) = service.subscribe(Uuid::new(), &[household_id], None);
+        assert!(missed.is_empty());
+    }
+
+    #[test]
+    fn history_keeps_the_latest_events() {
+        let user_id = Uuid::new();
+        let service = EventServiceImpl::new(2);
+
+        let first = service.publish(EventKind::CaptureCreated, user_id, None, status());
+        for _ in 0..3 {
+            service.publish(EventKind::CaptureCreated, user_id, None, status());
+        }
+
+        let (missed, _) = service.subscribe(user_id, &[], Some(first.id));
+        assert_eq!(missed.len(), 2);
+    }
+}
@@ -0,0 +1,150 @@
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::HealthService;
+use crate::errors::Error;
+use crate::heartbeat::Heartbeats;
+use crate::payloads::{ComponentHealth, HealthResponse};
+use crate::repositories::{HealthRepository, StorageRepository};
+
+// Kept under the usual probe timeout, a backend not answering by then is down for us.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct HealthServiceImpl {
+    database_repo: Arc<dyn HealthRepository>,
+    storage_repo: Arc<dyn StorageRepository>,
+    heartbeats: Arc<Heartbeats>,
+}
+
+impl HealthServiceImpl {
+    pub fn new(
+        database_repo: Arc<dyn HealthRepository>,
+        storage_repo: Arc<dyn StorageRepository>,
+        heartbeats: Arc<Heartbeats>,
+    ) -> Self {
+        Self {
+            database_repo,
+            storage_repo,
+            heartbeats,
+        }
+    }
+
+    async fn check<T>(check: impl Future<Output = Result<T, Error>>) -> ComponentHealth {
+        let started = Instant::now();
+        let result = match actix_web::rt::time::timeout(CHECK_TIMEOUT, check).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err(format!("no answer within {}s", CHECK_TIMEOUT.as_secs())),
+        };
+
+        ComponentHealth::new(result, Some(started.elapsed().as_millis() as u64))
+    }
+}
+
+#[async_trait]
+impl HealthService for HealthServiceImpl {
+    async fn readiness(&self) -> HealthResponse {
+        let (database, storage) = futures_util::join!(
+            Self::check(self.database_repo.ping()),
+            Self::check(self.storage_repo.ping()),
+        );
+
+        let mut components = BTreeMap::new();
+        components.insert("database".to_string(), database);
+        components.insert("storage".to_string(), storage);
+        for (worker, optional, result) in self.heartbeats.check() {
+            let mut component = ComponentHealth::new(result, None);
+            component.optional = optional;
+            components.insert(worker.to_string(), component);
+        }
+
+        HealthResponse::new(components)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payloads::HealthState;
+    use crate::repositories::{InMemoryRepository, LocalStorageRepository};
+
+    fn service(
+        storage_repo: Arc<dyn StorageRepository>,
+        heartbeats: Arc<Heartbeats>,
+    ) -> HealthServiceImpl {
+        HealthServiceImpl::new(
+            Arc::new(InMemoryRepository::new()),
+            storage_repo,
+            heartbeats,
+        )
+    }
+
+    #[actix_web::test]
+    async fn workers_are_ready_once_they_beat() {
+        let heartbeats = Arc::new(Heartbeats::default());
+        let worker = heartbeats.register("outbox_worker", Duration::from_secs(60));
+        let service = service(Arc::new(InMemoryRepository::new()), heartbeats);
+
+        let readiness = service.readiness().await;
+        assert_eq!(readiness.status, HealthState::Unavailable);
+        assert_eq!(
+            readiness.components["outbox_worker"].error.as_deref(),
+            Some("not started yet")
+        );
+
+        worker.beat();
+        let readiness = service.readiness().await;
+        assert_eq!(readiness.status, HealthState::Ok);
+        for component in ["database", "storage", "outbox_worker"] {
+            assert_eq!(readiness.components[component].status, HealthState::Ok);
+        }
+
+        worker.fail("controller unreachable");
+        assert_eq!(service.readiness().await.status, HealthState::Unavailable);
+    }
+
+    #[actix_web::test]
+    async fn broker_outage_is_reported_without_failing_readiness() {
+        let heartbeats = Arc::new(Heartbeats::default());
+        heartbeats
+            .register("outbox_worker", Duration::from_secs(60))
+            .beat();
+        let bridge = heartbeats.register_optional("mqtt_bridge", Duration::from_secs(60));
+        bridge.fail("connection refused");
+        let service = service(Arc::new(InMemoryRepository::new()), heartbeats);
+
+        let readiness = service.readiness().await;
+        assert_eq!(readiness.status, HealthState::Ok);
+        let bridge = &readiness.components["mqtt_bridge"];
+        assert_eq!(bridge.status, HealthState::Unavailable);
+        assert!(bridge.optional);
+    }
+
+    #[actix_web::test]
+    async fn local_storage_must_be_writable() {
+        let root = std::env::temp_dir().join(format!("rusty-secure-{}", bson::Uuid::new()));
+        let storage = LocalStorageRepository::new(
+            root.clone(),
+            "http://localhost:8080".to_string(),
+            b"signing secret".to_vec(),
+        )
+        .unwrap();
+        let service = service(Arc::new(storage), Arc::new(Heartbeats::default()));
+
+        let readiness = service.readiness().await;
+        assert_eq!(readiness.status, HealthState::Ok);
+        // The probe file doesn't stay behind.
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 0);
+
+        std::fs::remove_dir(&root).unwrap();
+        let readiness = service.readiness().await;
+        assert_eq!(readiness.status, HealthState::Unavailable);
+        assert_eq!(
+            readiness.components["storage"].status,
+            HealthState::Unavailable
+        );
+    }
+}
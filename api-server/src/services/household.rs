@@ -0,0 +1,636 @@
+use async_trait::async_trait;
+use bson::Uuid;
+use chrono::{Local, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::HouseholdService;
+use crate::errors::Error;
+use crate::models::{Device, Household, HouseholdMember, HouseholdRole, Invitation, User};
+use crate::repositories::{
+    DeviceRepository, HouseholdRepository, InvitationRepository, PictureRepository,
+    StatusRepository, UserRepository,
+};
+
+const INVITATION_CODE_LEN: usize = 16;
+
+pub struct HouseholdServiceImpl {
+    household_repo: Arc<dyn HouseholdRepository>,
+    invitation_repo: Arc<dyn InvitationRepository>,
+    user_repo: Arc<dyn UserRepository>,
+    device_repo: Arc<dyn DeviceRepository>,
+    status_repo: Arc<dyn StatusRepository>,
+    picture_repo: Arc<dyn PictureRepository>,
+
+    invitation_ttl: Duration,
+}
+
+impl HouseholdServiceImpl {
+    pub fn new(
+        household_repo: Arc<dyn HouseholdRepository>,
+        invitation_repo: Arc<dyn InvitationRepository>,
+        user_repo: Arc<dyn UserRepository>,
+        device_repo: Arc<dyn DeviceRepository>,
+        status_repo: Arc<dyn StatusRepository>,
+        picture_repo: Arc<dyn PictureRepository>,
+        invitation_ttl: Duration,
+    ) -> Self {
+        Self {
+            household_repo,
+            invitation_repo,
+            user_repo,
+            device_repo,
+            status_repo,
+            picture_repo,
+            invitation_ttl,
+        }
+    }
+
+    fn generate_code() -> String {
+        let mut code = [0u8; INVITATION_CODE_LEN];
+        rand::thread_rng().fill_bytes(&mut code);
+        hex::encode(code)
+    }
+
+    fn hash_code(code: &str) -> String {
+        hex::encode(Sha256::digest(code.trim().as_bytes()))
+    }
+
+    fn normalize_email(email: &str) -> Result<String, Error> {
+        let email = email.trim().to_lowercase();
+        match email.split_once('@') {
+            Some((local, domain)) if !local.is_empty() && !domain.is_empty() => Ok(email),
+            _ => Err(Error::BadRequest(format!("invalid email: {}", email))),
+        }
+    }
+
+    // A household without an owner couldn't be managed anymore.
+    fn check_owner_left(
+        household: &Household,
+        member_id: Uuid,
+        role: Option<HouseholdRole>,
+    ) -> Result<(), Error> {
+        let demoted = household.role_of(member_id) == Some(HouseholdRole::Owner)
+            && role != Some(HouseholdRole::Owner);
+        if demoted && household.owner_count() == 1 {
+            return Err(Error::Conflict(
+                "the last owner of a household can't leave it nor change role".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    // The role of the user over what belongs to the household, or to `legacy_owner` when it
+    // predates households.
+    async fn role_over(
+        &self,
+        user_id: Uuid,
+        household_id: Option<Uuid>,
+        legacy_owner: Uuid,
+    ) -> Result<Option<HouseholdRole>, Error> {
+        match household_id {
+            Some(household_id) => Ok(self
+                .household_repo
+                .find_by_id(household_id)
+                .await?
+                .and_then(|household| household.role_of(user_id))),
+            None if legacy_owner == user_id => Ok(Some(HouseholdRole::Owner)),
+            None => Ok(None),
+        }
+    }
+
+    fn check_role(role: HouseholdRole, required: HouseholdRole) -> Result<(), Error> {
+        if !role.grants(required) {
+            return Err(Error::Forbidden(format!(
+                "the {:?} role is required, you are {:?}",
+                required, role
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl HouseholdService for HouseholdServiceImpl {
+    async fn create(&self, user_id: Uuid, name: String) -> Result<Household, Error> {
+        if name.trim().is_empty() {
+            return Err(Error::Empty("Household name is empty".to_string()));
+        }
+
+        let household = Household::new(name.trim().to_string(), user_id);
+        self.household_repo.insert(&household).await?;
+
+        Ok(household)
+    }
+
+    async fn list(&self, user_id: Uuid) -> Result<Vec<Household>, Error> {
+        self.household_repo.find_by_member(user_id).await
+    }
+
+    async fn ensure_personal(&self, user: &User) -> Result<Household, Error> {
+        let owned = self
+            .household_repo
+            .find_by_member(user.id)
+            .await?
+            .into_iter()
+            .find(|household| household.role_of(user.id) == Some(HouseholdRole::Owner));
+
+        let household = match owned {
+            Some(household) => household,
+            None => {
+                self.create(user.id, format!("{}'s household", user.name))
+                    .await?
+            }
+        };
+
+        let assigned = self
+            .device_repo
+            .assign_household(user.id, household.id)
+            .await?;
+        if assigned > 0 {
+            tracing::info!(
+                assigned,
+                user_id = %user.id,
+                household_id = %household.id,
+                "Moved devices into household"
+            );
+        }
+
+        Ok(household)
+    }
+
+    async fn authorize(
+        &self,
+        user_id: Uuid,
+        household_id: Uuid,
+        required: HouseholdRole,
+    ) -> Result<Household, Error> {
+        // Households of others are reported as not found to avoid leaking their IDs.
+        let household = self
+            .household_repo
+            .find_by_id(household_id)
+            .await?
+            .ok_or_else(|| {
+                Error::NotFound(format!("Household not found for ID: {}", household_id))
+            })?;
+        let role = household.role_of(user_id).ok_or_else(|| {
+            Error::NotFound(format!("Household not found for ID: {}", household_id))
+        })?;
+
+        Self::check_role(role, required)?;
+
+        Ok(household)
+    }
+
+    async fn authorize_device(
+        &self,
+        user_id: Uuid,
+        device: &Device,
+        required: HouseholdRole,
+    ) -> Result<(), Error> {
+        let role = self
+            .role_over(user_id, device.household_id, device.user_id)
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("Device not found for ID: {}", device.id)))?;
+
+        Self::check_role(role, required)
+    }
+
+    async fn authorize_status(
+        &self,
+        user_id: Uuid,
+        status_id: Uuid,
+        required: HouseholdRole,
+    ) -> Result<(), Error> {
+        let not_found = || Error::NotFound(format!("Status not found for ID: {}", status_id));
+
+        let status = self
+            .status_repo
+            .find_by_id(status_id)
+            .await?
+            .ok_or_else(not_found)?;
+        let picture = self
+            .picture_repo
+            .find_by_id(status.picture_id)
+            .await?
+            .ok_or_else(not_found)?;
+
+        // Pictures from before devices only belong to their user.
+        let camera = match picture.device_id {
+            Some(device_id) => self.device_repo.find_by_id(device_id).await?,
+            None => None,
+        };
+        let role = match camera {
+            Some(camera) => {
+                self.role_over(user_id, camera.household_id, camera.user_id)
+                    .await?
+            }
+            None => self.role_over(user_id, None, picture.user_id).await?,
+        }
+        .ok_or_else(not_found)?;
+
+        Self::check_role(role, required)
+    }
+
+    async fn accessible_devices(
+        &self,
+        user_id: Uuid,
+        required: HouseholdRole,
+    ) -> Result<Vec<Device>, Error> {
+        let mut devices = Vec::new();
+
+        for household in self.household_repo.find_by_member(user_id).await? {
+            if household
+                .role_of(user_id)
+                .is_some_and(|role| role.grants(required))
+            {
+                devices.extend(self.device_repo.find_by_household_id(household.id).await?);
+            }
+        }
+
+        // Registered before households and not moved yet.
+        devices.extend(
+            self.device_repo
+                .find_by_user_id(user_id)
+                .await?
+                .into_iter()
+                .filter(|device| device.household_id.is_none()),
+        );
+
+        Ok(devices)
+    }
+
+    async fn invite(
+        &self,
+        user_id: Uuid,
+        household_id: Uuid,
+        email: String,
+        role: HouseholdRole,
+    ) -> Result<(Invitation, String), Error> {
+        self.authorize(user_id, household_id, HouseholdRole::Owner)
+            .await?;
+
+        let email = Self::normalize_email(&email)?;
+        let code = Self::generate_code();
+        let expires_at = Utc::now()
+            + chrono::Duration::from_std(self.invitation_ttl)
+                .map_err(|e| Error::Parse(e.to_string()))?;
+
+        let invitation = Invitation::new(
+            household_id,
+            email,
+            role,
+            Self::hash_code(&code),
+            user_id,
+            expires_at,
+        );
+        self.invitation_repo.insert(&invitation).await?;
+
+        Ok((invitation, code))
+    }
+
+    async fn pending_invitations(
+        &self,
+        user_id: Uuid,
+        household_id: Uuid,
+    ) -> Result<Vec<Invitation>, Error> {
+        self.authorize(user_id, household_id, HouseholdRole::Owner)
+            .await?;
+
+        self.invitation_repo
+            .find_pending_by_household_id(household_id)
+            .await
+    }
+
+    async fn revoke_invitation(
+        &self,
+        user_id: Uuid,
+        household_id: Uuid,
+        invitation_id: Uuid,
+    ) -> Result<(), Error> {
+        self.authorize(user_id, household_id, HouseholdRole::Owner)
+            .await?;
+
+        self.invitation_repo
+            .find_by_id(invitation_id)
+            .await?
+            .filter(|invitation| invitation.household_id == household_id)
+            .ok_or_else(|| {
+                Error::NotFound(format!("Invitation not found for ID: {}", invitation_id))
+            })?;
+
+        self.invitation_repo.delete(invitation_id).await
+    }
+
+    async fn redeem(
+        &self,
+        user: &User,
+        verified_email: Option<&str>,
+        code: &str,
+    ) -> Result<Household, Error> {
+        let invalid = || Error::NotFound("invalid or expired invitation".to_string());
+
+        let invitation = self
+            .invitation_repo
+            .find_by_code_hash(&Self::hash_code(code))
+            .await?
+            .filter(|invitation| invitation.is_pending())
+            .ok_or_else(invalid)?;
+
+        // The code alone isn't enough, it could have been forwarded. Any provider would sign
+        // in an unverified address matching the invitation.
+        let email = verified_email.ok_or_else(|| {
+            Error::Forbidden(
+                "the identity provider hasn't verified your email, the invitation can't be accepted"
+                    .to_string(),
+            )
+        })?;
+        if invitation.email != email.trim().to_lowercase() {
+            return Err(Error::Forbidden(
+                "the invitation was sent to another email".to_string(),
+            ));
+        }
+
+        let household = self
+            .household_repo
+            .find_by_id(invitation.household_id)
+            .await?
+            .ok_or_else(invalid)?;
+        if household.role_of(user.id).is_some() {
+            return Err(Error::Conflict(
+                "already a member of the household".to_string(),
+            ));
+        }
+
+        // Joined first so a failure can't leave the invitation used without a member. Two
+        // redemptions of the same code both try to join, only the first one does.
+        let member = HouseholdMember {
+            user_id: user.id,
+            role: invitation.role,
+            joined_at: Local::now(),
+        };
+        let household = self
+            .household_repo
+            .add_member(household.id, &member)
+            .await?
+            .ok_or_else(|| Error::Conflict("already a member of the household".to_string()))?;
+
+        // Revoked or expired meanwhile, the member leaves again.
+        let accepted = self
+            .invitation_repo
+            .accept(invitation.id, user.id)
+            .await
+            .and_then(|accepted| accepted.ok_or_else(invalid));
+        if let Err(e) = accepted {
+            if let Err(e) = self
+                .household_repo
+                .remove_member(household.id, user.id)
+                .await
+            {
+                tracing::error!(household_id = %household.id, user_id = %user.id, error = %e, "Failed to roll back a member whose invitation wasn't accepted");
+            }
+            return Err(e);
+        }
+
+        Ok(household)
+    }
+
+    async fn members(
+        &self,
+        user_id: Uuid,
+        household_id: Uuid,
+    ) -> Result<Vec<(HouseholdMember, Option<User>)>, Error> {
+        let household = self
+            .authorize(user_id, household_id, HouseholdRole::Owner)
+            .await?;
+
+        let mut members = Vec::with_capacity(household.members.len());
+        for member in household.members {
+            let user = self.user_repo.find_by_id(member.user_id).await?;
+            members.push((member, user));
+        }
+
+        Ok(members)
+    }
+
+    async fn change_role(
+        &self,
+        user_id: Uuid,
+        household_id: Uuid,
+        member_id: Uuid,
+        role: HouseholdRole,
+    ) -> Result<Household, Error> {
+        let household = self
+            .authorize(user_id, household_id, HouseholdRole::Owner)
+            .await?;
+        if household.role_of(member_id).is_none() {
+            return Err(Error::NotFound(format!(
+                "Member not found for ID: {}",
+                member_id
+            )));
+        }
+        Self::check_owner_left(&household, member_id, Some(role))?;
+
+        self.household_repo
+            .update_member_role(household_id, member_id, role)
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("Member not found for ID: {}", member_id)))
+    }
+
+    async fn remove_member(
+        &self,
+        user_id: Uuid,
+        household_id: Uuid,
+        member_id: Uuid,
+    ) -> Result<(), Error> {
+        // Anyone may leave, only owners remove someone else.
+        let required = if member_id == user_id {
+            HouseholdRole::Viewer
+        } else {
+            HouseholdRole::Owner
+        };
+        let household = self.authorize(user_id, household_id, required).await?;
+        if household.role_of(member_id).is_none() {
+            return Err(Error::NotFound(format!(
+                "Member not found for ID: {}",
+                member_id
+            )));
+        }
+        Self::check_owner_left(&household, member_id, None)?;
+
+        self.household_repo
+            .remove_member(household_id, member_id)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DeviceType, Picture, Status, UserIdentity};
+    use crate::repositories::InMemoryRepository;
+
+    fn service(repo: &Arc<InMemoryRepository>) -> HouseholdServiceImpl {
+        HouseholdServiceImpl::new(
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            Duration::from_secs(3600),
+        )
+    }
+
+    fn user(email: &str) -> User {
+        let identity = UserIdentity {
+            provider: "google".to_string(),
+            subject: Uuid::new().to_string(),
+        };
+        User::new(identity, email.to_string(), true, "user".to_string(), None)
+    }
+
+    // A household with a member of each role and a camera that took a picture.
+    async fn household(
+        repo: &Arc<InMemoryRepository>,
+        service: &HouseholdServiceImpl,
+    ) -> (Household, [Uuid; 3], Uuid) {
+        let [owner, approver, viewer] = [Uuid::new(), Uuid::new(), Uuid::new()];
+        let household = service.create(owner, "home".to_string()).await.unwrap();
+        for (user_id, role) in [
+            (approver, HouseholdRole::Approver),
+            (viewer, HouseholdRole::Viewer),
+        ] {
+            let member = HouseholdMember {
+                user_id,
+                role,
+                joined_at: Local::now(),
+            };
+            repo.add_member(household.id, &member).await.unwrap();
+        }
+
+        let camera = Device::new(
+            owner,
+            household.id,
+            "camera".to_string(),
+            DeviceType::Esp32Cam,
+            "secret".to_string(),
+        );
+        DeviceRepository::insert(repo.as_ref(), &camera)
+            .await
+            .unwrap();
+        let picture = Picture::new(owner, camera.id, "picture.jpg".to_string());
+        PictureRepository::insert(repo.as_ref(), &picture)
+            .await
+            .unwrap();
+        let status = Status::new(picture.id, Utc::now());
+        StatusRepository::insert(repo.as_ref(), &status)
+            .await
+            .unwrap();
+
+        (household, [owner, approver, viewer], status.id)
+    }
+
+    #[actix_web::test]
+    async fn viewers_cannot_decide() {
+        let repo = Arc::new(InMemoryRepository::new());
+        let service = service(&repo);
+        let (_, [owner, approver, viewer], status_id) = household(&repo, &service).await;
+
+        for user_id in [owner, approver] {
+            service
+                .authorize_status(user_id, status_id, HouseholdRole::Approver)
+                .await
+                .unwrap();
+        }
+        let result = service
+            .authorize_status(viewer, status_id, HouseholdRole::Approver)
+            .await;
+        assert!(matches!(result, Err(Error::Forbidden(_))));
+        service
+            .authorize_status(viewer, status_id, HouseholdRole::Viewer)
+            .await
+            .unwrap();
+        // Outsiders don't learn the status exists.
+        let result = service
+            .authorize_status(Uuid::new(), status_id, HouseholdRole::Viewer)
+            .await;
+        assert!(matches!(result, Err(Error::NotFound(_))));
+    }
+
+    #[actix_web::test]
+    async fn only_owners_invite_and_remove_members() {
+        let repo = Arc::new(InMemoryRepository::new());
+        let service = service(&repo);
+        let (household, [owner, approver, viewer], _) = household(&repo, &service).await;
+
+        for user_id in [approver, viewer] {
+            let result = service
+                .invite(
+                    user_id,
+                    household.id,
+                    "guest@example.org".to_string(),
+                    HouseholdRole::Viewer,
+                )
+                .await;
+            assert!(matches!(result, Err(Error::Forbidden(_))));
+        }
+        let result = service.remove_member(approver, household.id, viewer).await;
+        assert!(matches!(result, Err(Error::Forbidden(_))));
+        let result = service.remove_member(viewer, household.id, approver).await;
+        assert!(matches!(result, Err(Error::Forbidden(_))));
+
+        // Anyone may leave, owners remove the others.
+        service
+            .remove_member(viewer, household.id, viewer)
+            .await
+            .unwrap();
+        service
+            .remove_member(owner, household.id, approver)
+            .await
+            .unwrap();
+        let household = service
+            .authorize(owner, household.id, HouseholdRole::Owner)
+            .await
+            .unwrap();
+        assert_eq!(household.members.len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn redeem_requires_the_verified_invited_email() {
+        let repo = Arc::new(InMemoryRepository::new());
+        let service = service(&repo);
+        let owner = Uuid::new();
+        let household = service.create(owner, "home".to_string()).await.unwrap();
+        let (_, code) = service
+            .invite(
+                owner,
+                household.id,
+                "guest@example.org".to_string(),
+                HouseholdRole::Viewer,
+            )
+            .await
+            .unwrap();
+        let guest = user("guest@example.org");
+
+        // Unverified by the provider, even though it's the invited address.
+        let result = service.redeem(&guest, None, &code).await;
+        assert!(matches!(result, Err(Error::Forbidden(_))));
+        let result = service
+            .redeem(&guest, Some("other@example.org"), &code)
+            .await;
+        assert!(matches!(result, Err(Error::Forbidden(_))));
+
+        let household = service
+            .redeem(&guest, Some(" Guest@Example.org"), &code)
+            .await
+            .unwrap();
+        assert_eq!(household.role_of(guest.id), Some(HouseholdRole::Viewer));
+    }
+}
@@ -1,5 +1,5 @@
 mod status;
-pub use status::StatusServiceImpl;
+pub use status::{StatusServiceImpl, StatusTimeouts};
 
 mod picture;
 pub use picture::PictureServiceImpl;
@@ -10,23 +10,64 @@ pub use auth::AuthServiceImpl;
 mod user;
 pub use user::UserServiceImpl;
 
+mod device;
+pub use device::DeviceServiceImpl;
+
+mod audit;
+pub use audit::AuditServiceImpl;
+
+mod event;
+pub use event::EventServiceImpl;
+
+mod webhook;
+pub use webhook::WebhookServiceImpl;
+
+mod session;
+pub use session::SessionServiceImpl;
+
+mod oidc;
+pub use oidc::OidcProvider;
+
+mod household;
+pub use household::HouseholdServiceImpl;
+
+mod health;
+pub use health::HealthServiceImpl;
+
 use async_trait::async_trait;
 use bson::Uuid;
+use tokio::sync::broadcast;
 
 use crate::errors::Error;
-use crate::models::{Device, Picture, Token, User};
-use crate::payloads::{StatusResponse, UserInfo};
+use crate::models::{
+    AuditContext, AuditEvent, Device, DeviceType, EventKind, Household, HouseholdMember,
+    HouseholdRole, Invitation, LoginResponseType, OAuthState, Session, Status, StatusState, Token,
+    User, UserIdentity, Webhook, WebhookDelivery,
+};
+use crate::payloads::{
+    AuditEntryResponse, AuditQuery, AuditVerificationResponse, Event, HealthResponse,
+    PictureResponse, SessionTokens, StatusResponse, UserInfo,
+};
 
 // NOTE: Service should return a model then the API layer convert to payload..
 #[async_trait]
 pub trait StatusService: Send + Sync {
     async fn get_status_details(&self, status_id: Uuid) -> Result<StatusResponse, Error>;
-    async fn update_authorisation(
+    async fn decide(
         &self,
         status_id: Uuid,
-        authorised: bool,
+        context: AuditContext,
+        state: StatusState,
+        reason: Option<String>,
     ) -> Result<StatusResponse, Error>;
     async fn create_initial_status(&self, picture_id: Uuid) -> Result<StatusResponse, Error>;
+    // Statuses of the camera still waiting for a decision, most recent first.
+    async fn find_pending_by_camera(&self, camera_id: Uuid) -> Result<Vec<Status>, Error>;
+    // Statuses of every camera still waiting for a decision.
+    async fn count_pending(&self) -> Result<u64, Error>;
+    // Moves the pending statuses past their timeout to Expired.
+    async fn expire_stale(&self) -> Result<u64, Error>;
+    // Queues the current decision in the outbox, the outbox worker delivers it.
     async fn send_status(&self, status_id: Uuid) -> Result<bool, Error>;
 }
 
@@ -35,32 +76,236 @@ pub trait PictureService: Send + Sync {
     async fn upload_and_register_picture(
         &self,
         user_id: Uuid,
+        device_id: Uuid,
         image_data: Vec<u8>,
     ) -> Result<StatusResponse, Error>;
-    async fn get_all(&self, user_id: Uuid) -> Result<Vec<Picture>, Error>;
+    // Pictures of the cameras the user can see.
+    async fn get_all(&self, user_id: Uuid) -> Result<Vec<PictureResponse>, Error>;
 }
 
 #[async_trait]
 pub trait AuthService: Send + Sync {
+    // Names of the configured identity providers.
+    fn providers(&self) -> Vec<String>;
+    // The url to send the user to, and the state it carries. Without a provider the first
+    // configured one is used. The invitation is kept with the state until the callback.
     async fn get_authorisation_url(
         &self,
-        response_type: Option<String>,
+        provider: Option<String>,
+        response_type: LoginResponseType,
+        invitation: Option<String>,
     ) -> Result<(String, String), Error>;
+    // Consumes the state issued with the url, it can't be replayed.
     async fn exchange_code_for_token(
         &self,
         code: String,
         state: String,
-    ) -> Result<(UserInfo, Token), Error>;
-    async fn verify_token(&self, token: String) -> Result<(bool, User), Error>;
+    ) -> Result<(UserInfo, Token, OAuthState), Error>;
+}
+
+// Our own tokens: a signed access token checked without any call to the identity provider,
+// and a refresh token rotated on each use.
+#[async_trait]
+pub trait SessionService: Send + Sync {
+    async fn create(&self, user: &User) -> Result<SessionTokens, Error>;
+    async fn refresh(&self, refresh_token: String) -> Result<SessionTokens, Error>;
+    // The user and session of a valid access token whose session is still active.
+    async fn verify(&self, access_token: &str) -> Result<(User, Session), Error>;
+    // Revokes the session of the refresh token, or all of its user's, returns the user ID.
+    async fn logout(&self, refresh_token: String, all: bool) -> Result<Uuid, Error>;
 }
 
 #[async_trait]
 pub trait UserService: Send + Sync {
-    async fn get_by_google_id(&self, google_id: String) -> Result<Option<User>, Error>;
+    async fn find_by_identity(&self, identity: UserIdentity) -> Result<Option<User>, Error>;
     async fn register(&self, user: User) -> Result<Option<User>, Error>;
 }
 
 #[async_trait]
 pub trait DeviceService: Send + Sync {
-    async fn register(&self, device: Device) -> Result<Option<Device>, Error>;
+    // Owners only, as for every change below.
+    #[allow(clippy::too_many_arguments)]
+    async fn register(
+        &self,
+        user_id: Uuid,
+        household_id: Uuid,
+        name: String,
+        device_type: DeviceType,
+        camera_id: Option<Uuid>,
+        callback_url: Option<String>,
+        capture_url: Option<String>,
+    ) -> Result<Device, Error>;
+    // Checks an HMAC-SHA256 of `<timestamp>.<body>` made with the device secret.
+    async fn authenticate(
+        &self,
+        device_id: Uuid,
+        timestamp: i64,
+        signature: &str,
+        body: &[u8],
+    ) -> Result<Device, Error>;
+    // Devices of every household of the user.
+    async fn list(&self, user_id: Uuid) -> Result<Vec<Device>, Error>;
+    async fn rename(&self, user_id: Uuid, device_id: Uuid, name: String) -> Result<Device, Error>;
+    async fn link_camera(
+        &self,
+        user_id: Uuid,
+        device_id: Uuid,
+        camera_id: Option<Uuid>,
+    ) -> Result<Device, Error>;
+    // Called by the controller itself (already authenticated), e.g. when its DHCP lease changes.
+    async fn update_callback_url(
+        &self,
+        device: &Device,
+        callback_url: String,
+    ) -> Result<Device, Error>;
+    async fn revoke(&self, user_id: Uuid, device_id: Uuid) -> Result<Device, Error>;
+    // Asks the camera to take a picture through its capture url, approvers may.
+    async fn trigger_capture(&self, user_id: Uuid, camera_id: Uuid) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait AuditService: Send + Sync {
+    async fn record(&self, event: AuditEvent) -> Result<(), Error>;
+    // Admins read every entry, the others the entries of the households they own and
+    // the ones they are the actor of.
+    async fn query(
+        &self,
+        user_id: Uuid,
+        query: AuditQuery,
+    ) -> Result<Vec<AuditEntryResponse>, Error>;
+    // Admins only.
+    async fn verify(&self, user_id: Uuid) -> Result<AuditVerificationResponse, Error>;
+}
+
+// In-process fan-out of what happens to statuses.
+pub trait EventService: Send + Sync {
+    // Returns the event as published.
+    fn publish(
+        &self,
+        kind: EventKind,
+        user_id: Uuid,
+        household_id: Option<Uuid>,
+        status: StatusResponse,
+    ) -> Event;
+    // Events newer than `last_event_id` still in memory, then the live ones. A user sees the
+    // events of the households they're a member of.
+    fn subscribe(
+        &self,
+        user_id: Uuid,
+        household_ids: &[Uuid],
+        last_event_id: Option<u64>,
+    ) -> (Vec<Event>, broadcast::Receiver<Event>);
+    // Every event, whoever it belongs to, for the server's own consumers.
+    fn subscribe_all(&self) -> broadcast::Receiver<Event>;
+}
+
+#[async_trait]
+pub trait WebhookService: Send + Sync {
+    async fn register(
+        &self,
+        user_id: Uuid,
+        url: String,
+        events: Vec<EventKind>,
+    ) -> Result<Webhook, Error>;
+    async fn list(&self, user_id: Uuid) -> Result<Vec<Webhook>, Error>;
+    async fn delete(&self, user_id: Uuid, webhook_id: Uuid) -> Result<(), Error>;
+    async fn deliveries(
+        &self,
+        user_id: Uuid,
+        webhook_id: Uuid,
+    ) -> Result<Vec<WebhookDelivery>, Error>;
+    // Queues a delivery for each webhook of the camera owner subscribed to the event, called
+    // where the status changes so the deliveries don't depend on the in-memory events.
+    async fn enqueue(&self, event: Event) -> Result<usize, Error>;
+}
+
+// Households own the devices, what a user may do with a device, its pictures and statuses
+// depends on their role in the device's household.
+#[async_trait]
+pub trait HouseholdService: Send + Sync {
+    // The user becomes its owner.
+    async fn create(&self, user_id: Uuid, name: String) -> Result<Household, Error>;
+    async fn list(&self, user_id: Uuid) -> Result<Vec<Household>, Error>;
+    // The first household the user owns, created when there's none, with the devices they
+    // registered before households moved into it.
+    async fn ensure_personal(&self, user: &User) -> Result<Household, Error>;
+    // Fails with `Error::NotFound` for a non member, `Error::Forbidden` for a lower role.
+    async fn authorize(
+        &self,
+        user_id: Uuid,
+        household_id: Uuid,
+        required: HouseholdRole,
+    ) -> Result<Household, Error>;
+    async fn authorize_device(
+        &self,
+        user_id: Uuid,
+        device: &Device,
+        required: HouseholdRole,
+    ) -> Result<(), Error>;
+    // Through the camera that took the picture of the status.
+    async fn authorize_status(
+        &self,
+        user_id: Uuid,
+        status_id: Uuid,
+        required: HouseholdRole,
+    ) -> Result<(), Error>;
+    // Devices of the households where the user holds at least `required`.
+    async fn accessible_devices(
+        &self,
+        user_id: Uuid,
+        required: HouseholdRole,
+    ) -> Result<Vec<Device>, Error>;
+    // Owners only. Returns the invitation and its code, which isn't kept.
+    async fn invite(
+        &self,
+        user_id: Uuid,
+        household_id: Uuid,
+        email: String,
+        role: HouseholdRole,
+    ) -> Result<(Invitation, String), Error>;
+    async fn pending_invitations(
+        &self,
+        user_id: Uuid,
+        household_id: Uuid,
+    ) -> Result<Vec<Invitation>, Error>;
+    async fn revoke_invitation(
+        &self,
+        user_id: Uuid,
+        household_id: Uuid,
+        invitation_id: Uuid,
+    ) -> Result<(), Error>;
+    // Joins the household with the invited role. `verified_email` is the email the provider
+    // verified for this login, `None` when it didn't, and must be the invited one.
+    async fn redeem(
+        &self,
+        user: &User,
+        verified_email: Option<&str>,
+        code: &str,
+    ) -> Result<Household, Error>;
+    // Owners only, each member with their account when it still exists.
+    async fn members(
+        &self,
+        user_id: Uuid,
+        household_id: Uuid,
+    ) -> Result<Vec<(HouseholdMember, Option<User>)>, Error>;
+    async fn change_role(
+        &self,
+        user_id: Uuid,
+        household_id: Uuid,
+        member_id: Uuid,
+        role: HouseholdRole,
+    ) -> Result<Household, Error>;
+    // Owners remove anyone, other members only themselves. The last owner can't leave.
+    async fn remove_member(
+        &self,
+        user_id: Uuid,
+        household_id: Uuid,
+        member_id: Uuid,
+    ) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait HealthService: Send + Sync {
+    // Checks the database, the storage and the background workers.
+    async fn readiness(&self) -> HealthResponse;
 }
@@ -1,19 +1,23 @@
 use async_trait::async_trait;
 use bson::Uuid;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::Duration;
 
-use super::PictureService;
-use super::StatusService;
+use super::{HouseholdService, PictureService, StatusService};
 use crate::errors::Error;
-use crate::models::Picture;
-use crate::payloads::StatusResponse;
+use crate::metrics::Metrics;
+use crate::models::{HouseholdRole, Picture};
+use crate::payloads::{PictureResponse, StatusResponse};
 use crate::repositories::{PictureRepository, StorageRepository};
 
 pub struct PictureServiceImpl {
     picture_repo: Arc<dyn PictureRepository>,
     storage_repo: Arc<dyn StorageRepository>,
     status_service: Arc<dyn StatusService>,
+    household_service: Arc<dyn HouseholdService>,
+    metrics: Arc<Metrics>,
+
+    signed_url_ttl: Duration,
 }
 
 impl PictureServiceImpl {
@@ -21,37 +25,45 @@ impl PictureServiceImpl {
         picture_repo: Arc<dyn PictureRepository>,
         storage_repo: Arc<dyn StorageRepository>,
         status_service: Arc<dyn StatusService>,
+        household_service: Arc<dyn HouseholdService>,
+        metrics: Arc<Metrics>,
+        signed_url_ttl: Duration,
     ) -> Self {
         Self {
             picture_repo,
             storage_repo,
             status_service,
+            household_service,
+            metrics,
+            signed_url_ttl,
         }
     }
-}
 
-#[async_trait]
-impl PictureService for PictureServiceImpl {
-    async fn upload_and_register_picture(
+    // Stores the picture and opens its status, `upload_and_register_picture` counts the outcome.
+    async fn store_picture(
         &self,
         user_id: Uuid,
+        device_id: Uuid,
         image_data: Vec<u8>,
     ) -> Result<StatusResponse, Error> {
         if !image_data.is_empty() {
-            let timestamp = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .map_err(|e| Error::Service(format!("System time error: {}", e)))?
-                .as_secs();
-
-            let object_name = format!("esp32_cam_{}.jpg", timestamp);
-            let url = self
-                .storage_repo
-                .upload_file(&object_name, image_data)
+            // Named after the picture ID, two uploads never share an object. The name is sent on
+            // to the main board, which only holds 64 characters, the camera is on the picture.
+            let mut new_picture = Picture::new(user_id, device_id, String::new());
+            new_picture.name = format!("esp32_cam_{}.jpg", new_picture.id);
+            // The returned url isn't persisted, links are signed on read instead.
+            self.storage_repo
+                .upload_file(&new_picture.name, image_data)
                 .await?;
 
-            let new_picture = Picture::new(user_id, object_name, url);
             let picture_id = new_picture.id;
-            self.picture_repo.insert(&new_picture).await?;
+            if let Err(e) = self.picture_repo.insert(&new_picture).await {
+                // Nothing would ever point to the object again.
+                if let Err(e) = self.storage_repo.delete(&new_picture.name).await {
+                    tracing::warn!(object = %new_picture.name, error = %e, "Failed to delete orphaned picture");
+                }
+                return Err(e);
+            }
 
             let status_response = self
                 .status_service
@@ -63,12 +75,57 @@ impl PictureService for PictureServiceImpl {
             Err(Error::Empty("Image data is empty".to_string()))
         }
     }
+}
+
+#[async_trait]
+impl PictureService for PictureServiceImpl {
+    async fn upload_and_register_picture(
+        &self,
+        user_id: Uuid,
+        device_id: Uuid,
+        image_data: Vec<u8>,
+    ) -> Result<StatusResponse, Error> {
+        let size = image_data.len();
+        let result = self.store_picture(user_id, device_id, image_data).await;
+        self.metrics
+            .record_upload(result.as_ref().ok().map(|_| size));
+
+        result
+    }
+
+    async fn get_all(&self, user_id: Uuid) -> Result<Vec<PictureResponse>, Error> {
+        let camera_ids: Vec<Uuid> = self
+            .household_service
+            .accessible_devices(user_id, HouseholdRole::Viewer)
+            .await?
+            .into_iter()
+            .map(|device| device.id)
+            .collect();
 
-    async fn get_all(&self, user_id: Uuid) -> Result<Vec<Picture>, Error> {
-        return self
+        let mut pictures = self
             .picture_repo
-            .find_by_user_id(user_id)
+            .find_by_device_ids(&camera_ids)
             .await
-            .map_err(|e| Error::Service(e.to_string()));
+            .map_err(|e| Error::Service(e.to_string()))?;
+        // Uploads made before devices existed only belong to their user.
+        pictures.extend(
+            self.picture_repo
+                .find_by_user_id(user_id)
+                .await
+                .map_err(|e| Error::Service(e.to_string()))?
+                .into_iter()
+                .filter(|picture| picture.device_id.is_none()),
+        );
+
+        let mut picture_responses = Vec::with_capacity(pictures.len());
+        for picture in pictures {
+            let url = self
+                .storage_repo
+                .signed_url(&picture.name, self.signed_url_ttl)
+                .await?;
+            picture_responses.push(PictureResponse::new(picture, url));
+        }
+
+        Ok(picture_responses)
     }
 }
@@ -0,0 +1,296 @@
+use async_trait::async_trait;
+use bson::Uuid;
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::SessionService;
+use crate::errors::Error;
+use crate::models::{Session, User};
+use crate::payloads::SessionTokens;
+use crate::repositories::{SessionRepository, UserRepository};
+
+const ISSUER: &str = "rusty-secure";
+const REFRESH_TOKEN_LEN: usize = 32;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AccessClaims {
+    iss: String,
+    // User ID.
+    sub: String,
+    // Session ID.
+    sid: String,
+    iat: i64,
+    exp: i64,
+}
+
+pub struct SessionServiceImpl {
+    session_repo: Arc<dyn SessionRepository>,
+    user_repo: Arc<dyn UserRepository>,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    access_token_ttl: Duration,
+    refresh_token_ttl: Duration,
+}
+
+impl SessionServiceImpl {
+    pub fn new(
+        session_repo: Arc<dyn SessionRepository>,
+        user_repo: Arc<dyn UserRepository>,
+        signing_secret: &[u8],
+        access_token_ttl: Duration,
+        refresh_token_ttl: Duration,
+    ) -> Self {
+        Self {
+            session_repo,
+            user_repo,
+            encoding_key: EncodingKey::from_secret(signing_secret),
+            decoding_key: DecodingKey::from_secret(signing_secret),
+            access_token_ttl,
+            refresh_token_ttl,
+        }
+    }
+
+    fn generate_refresh_token() -> String {
+        let mut token = [0u8; REFRESH_TOKEN_LEN];
+        rand::thread_rng().fill_bytes(&mut token);
+        hex::encode(token)
+    }
+
+    fn hash_token(token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+
+    fn refresh_expiry(&self) -> Result<chrono::DateTime<Utc>, Error> {
+        Ok(Utc::now()
+            + chrono::Duration::from_std(self.refresh_token_ttl)
+                .map_err(|e| Error::Parse(e.to_string()))?)
+    }
+
+    fn tokens(&self, session: &Session, refresh_token: String) -> Result<SessionTokens, Error> {
+        let now = Utc::now().timestamp();
+        let claims = AccessClaims {
+            iss: ISSUER.to_string(),
+            sub: session.user_id.to_string(),
+            sid: session.id.to_string(),
+            iat: now,
+            exp: now + self.access_token_ttl.as_secs() as i64,
+        };
+        let access_token = jsonwebtoken::encode(&Header::default(), &claims, &self.encoding_key)
+            .map_err(|e| Error::Internal(format!("failed to sign access token: {}", e)))?;
+
+        Ok(SessionTokens {
+            token_type: "Bearer".to_string(),
+            access_token,
+            expires_in: self.access_token_ttl.as_secs(),
+            refresh_token,
+            refresh_expires_in: self.refresh_token_ttl.as_secs(),
+        })
+    }
+
+    // The active session the refresh token is the current one of.
+    async fn find_by_refresh_token(&self, refresh_token: &str) -> Result<Session, Error> {
+        let token_hash = Self::hash_token(refresh_token);
+        let session = self
+            .session_repo
+            .find_by_token_hash(&token_hash)
+            .await?
+            .ok_or_else(|| Error::Unauthorized("invalid refresh token".to_string()))?;
+
+        if session.revoked_at.is_some() {
+            return Err(Error::Unauthorized("session revoked".to_string()));
+        }
+
+        // Someone holds a token that was already exchanged, whoever got the newer one
+        // can't be told apart from a thief so the whole session goes.
+        if session.refresh_token_hash != token_hash {
+            tracing::warn!(
+                session_id = %session.id,
+                "Rotated refresh token reused, revoking the session"
+            );
+            self.session_repo.revoke(session.id).await?;
+            return Err(Error::Unauthorized(
+                "refresh token already used".to_string(),
+            ));
+        }
+
+        if !session.is_active() {
+            return Err(Error::Unauthorized("session expired".to_string()));
+        }
+
+        Ok(session)
+    }
+}
+
+#[async_trait]
+impl SessionService for SessionServiceImpl {
+    async fn create(&self, user: &User) -> Result<SessionTokens, Error> {
+        let refresh_token = Self::generate_refresh_token();
+        let session = Session::new(
+            user.id,
+            Self::hash_token(&refresh_token),
+            self.refresh_expiry()?,
+        );
+        self.session_repo.insert(&session).await?;
+
+        self.tokens(&session, refresh_token)
+    }
+
+    async fn refresh(&self, refresh_token: String) -> Result<SessionTokens, Error> {
+        let session = self.find_by_refresh_token(&refresh_token).await?;
+
+        let next_token = Self::generate_refresh_token();
+        let session = self
+            .session_repo
+            .rotate(
+                session.id,
+                &session.refresh_token_hash,
+                &Self::hash_token(&next_token),
+                self.refresh_expiry()?,
+            )
+            .await?
+            // Another refresh with the same token won the race.
+            .ok_or_else(|| Error::Unauthorized("refresh token already used".to_string()))?;
+
+        self.tokens(&session, next_token)
+    }
+
+    async fn verify(&self, access_token: &str) -> Result<(User, Session), Error> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_issuer(&[ISSUER]);
+        validation.leeway = 0;
+
+        let claims =
+            jsonwebtoken::decode::<AccessClaims>(access_token, &self.decoding_key, &validation)
+                .map_err(|e| Error::Unauthorized(format!("invalid access token: {}", e)))?
+                .claims;
+
+        let session_id = Uuid::parse_str(&claims.sid)
+            .map_err(|_| Error::Unauthorized("invalid access token".to_string()))?;
+        let session = self
+            .session_repo
+            .find_by_id(session_id)
+            .await?
+            .filter(|session| session.is_active())
+            .ok_or_else(|| Error::Unauthorized("session revoked or expired".to_string()))?;
+
+        let user = self
+            .user_repo
+            .find_by_id(session.user_id)
+            .await?
+            .ok_or_else(|| Error::Unauthorized("User not found".to_string()))?;
+
+        Ok((user, session))
+    }
+
+    async fn logout(&self, refresh_token: String, all: bool) -> Result<Uuid, Error> {
+        let session = self.find_by_refresh_token(&refresh_token).await?;
+
+        if all {
+            let revoked = self.session_repo.revoke_by_user_id(session.user_id).await?;
+            tracing::info!(revoked, user_id = %session.user_id, "Revoked sessions");
+        } else {
+            self.session_repo.revoke(session.id).await?;
+        }
+
+        Ok(session.user_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::UserIdentity;
+    use crate::repositories::InMemoryRepository;
+
+    async fn setup() -> (SessionServiceImpl, User) {
+        let repo = Arc::new(InMemoryRepository::new());
+        let service = SessionServiceImpl::new(
+            repo.clone(),
+            repo.clone(),
+            b"signing secret",
+            Duration::from_secs(900),
+            Duration::from_secs(3600),
+        );
+
+        let user = User::new(
+            UserIdentity {
+                provider: "mock".to_string(),
+                subject: "alice".to_string(),
+            },
+            "alice@example.com".to_string(),
+            true,
+            "Alice".to_string(),
+            None,
+        );
+        UserRepository::insert(repo.as_ref(), &user).await.unwrap();
+
+        (service, user)
+    }
+
+    #[actix_web::test]
+    async fn refresh_rotates_the_token() {
+        let (service, user) = setup().await;
+        let first = service.create(&user).await.unwrap();
+
+        let second = service.refresh(first.refresh_token.clone()).await.unwrap();
+        assert_ne!(second.refresh_token, first.refresh_token);
+
+        let (verified_user, _) = service.verify(&second.access_token).await.unwrap();
+        assert_eq!(verified_user.id, user.id);
+        assert!(service.refresh(second.refresh_token).await.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn reused_refresh_token_revokes_the_session() {
+        let (service, user) = setup().await;
+        let first = service.create(&user).await.unwrap();
+        let second = service.refresh(first.refresh_token.clone()).await.unwrap();
+
+        let reused = service.refresh(first.refresh_token).await;
+        assert!(matches!(reused, Err(Error::Unauthorized(_))));
+
+        // Whoever holds the newer tokens is signed out as well.
+        assert!(service.refresh(second.refresh_token).await.is_err());
+        assert!(service.verify(&second.access_token).await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn other_sessions_survive_a_reuse() {
+        let (service, user) = setup().await;
+        let stolen = service.create(&user).await.unwrap();
+        let other = service.create(&user).await.unwrap();
+        service.refresh(stolen.refresh_token.clone()).await.unwrap();
+
+        assert!(service.refresh(stolen.refresh_token).await.is_err());
+        assert!(service.verify(&other.access_token).await.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn logout_revokes_the_access_token() {
+        let (service, user) = setup().await;
+        let tokens = service.create(&user).await.unwrap();
+
+        assert_eq!(
+            service
+                .logout(tokens.refresh_token.clone(), false)
+                .await
+                .unwrap(),
+            user.id
+        );
+        assert!(service.verify(&tokens.access_token).await.is_err());
+        assert!(service.refresh(tokens.refresh_token).await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn unknown_refresh_token_is_rejected() {
+        let (service, _) = setup().await;
+
+        let result = service.refresh("not a token".to_string()).await;
+        assert!(matches!(result, Err(Error::Unauthorized(_))));
+    }
+}
@@ -1,33 +1,230 @@
 use async_trait::async_trait;
 use bson::Uuid;
+use chrono::Utc;
+use serde_json::{json, Value};
 use std::sync::Arc;
+use std::time::Duration;
 
-use super::StatusService;
+use super::{AuditService, EventService, StatusService, WebhookService};
 use crate::errors::Error;
-use crate::models::{Picture, Status};
-use crate::payloads::StatusResponse;
-use crate::repositories::{PictureRepository, StatusRepository};
+use crate::logging::{current_request_id, redact_url};
+use crate::metrics::Metrics;
+use crate::models::{
+    AuditAction, AuditContext, AuditEvent, Delivery, EventKind, Picture, Status, StatusState,
+};
+use crate::payloads::{DeliveryResponse, StatusResponse};
+use crate::repositories::{
+    DeliveryRepository, DeviceRepository, PictureRepository, StatusRepository, StorageRepository,
+};
+
+pub struct StatusTimeouts {
+    // How long a minted picture url stays valid.
+    pub signed_url_ttl: Duration,
+    // How long a decision may wait in the outbox.
+    pub delivery_ttl: Duration,
+    // How long a picture waits for a decision.
+    pub pending_timeout: Duration,
+}
 
 pub struct StatusServiceImpl {
     status_repo: Arc<dyn StatusRepository>,
     picture_repo: Arc<dyn PictureRepository>,
+    storage_repo: Arc<dyn StorageRepository>,
+    device_repo: Arc<dyn DeviceRepository>,
+    delivery_repo: Arc<dyn DeliveryRepository>,
+    audit_service: Arc<dyn AuditService>,
+    event_service: Arc<dyn EventService>,
+    webhook_service: Arc<dyn WebhookService>,
+    metrics: Arc<Metrics>,
 
-    http_server_address: String,
+    timeouts: StatusTimeouts,
 }
 
 impl StatusServiceImpl {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         status_repo: Arc<dyn StatusRepository>,
         picture_repo: Arc<dyn PictureRepository>,
-        http_server_address: String,
+        storage_repo: Arc<dyn StorageRepository>,
+        device_repo: Arc<dyn DeviceRepository>,
+        delivery_repo: Arc<dyn DeliveryRepository>,
+        audit_service: Arc<dyn AuditService>,
+        event_service: Arc<dyn EventService>,
+        webhook_service: Arc<dyn WebhookService>,
+        metrics: Arc<Metrics>,
+        timeouts: StatusTimeouts,
     ) -> Self {
         Self {
             status_repo,
             picture_repo,
-            http_server_address,
+            storage_repo,
+            device_repo,
+            delivery_repo,
+            audit_service,
+            event_service,
+            webhook_service,
+            metrics,
+            timeouts,
         }
     }
 
+    // Counts a status leaving Pending, with the time it waited since the picture.
+    fn record_decision(&self, status: &Status) {
+        let state = format!("{:?}", status.state).to_lowercase();
+        let waited = (chrono::Local::now() - status.created_at)
+            .to_std()
+            .unwrap_or_default();
+        self.metrics.record_decision(&state, waited);
+    }
+
+    // What the audit log keeps of a status before and after a change.
+    fn snapshot(status: &Status) -> Value {
+        json!({
+            "state": status.state,
+            "decided_by": status.decided_by.map(|id| id.to_string()),
+            "reason": status.reason,
+        })
+    }
+
+    // Household of the camera that took the picture, `None` for pictures from before devices.
+    async fn household_of(&self, picture_id: Uuid) -> Result<Option<Uuid>, Error> {
+        let picture = self.find_picture_by_id(picture_id).await?;
+        let Some(camera_id) = picture.device_id else {
+            return Ok(None);
+        };
+
+        Ok(self
+            .device_repo
+            .find_by_id(camera_id)
+            .await?
+            .and_then(|camera| camera.household_id))
+    }
+
+    async fn audit_transition(
+        &self,
+        action: AuditAction,
+        context: AuditContext,
+        before: &Status,
+        after: &Status,
+    ) {
+        // Still recorded without it, only admins and the actor will read it.
+        let household_id = self
+            .household_of(after.picture_id)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!(status_id = %after.id, error = %e, "Failed to find the household of a status");
+                None
+            });
+
+        let event = AuditEvent {
+            action,
+            context,
+            household_id,
+            target: Some(after.id.to_string()),
+            before: Some(Self::snapshot(before)),
+            after: Some(Self::snapshot(after)),
+        };
+
+        if let Err(e) = self.audit_service.record(event).await {
+            tracing::warn!(status_id = %after.id, error = %e, "Failed to record audit entry");
+        }
+    }
+
+    // Only a pending status can be decided, Expired is reserved to the timeout.
+    fn check_transition(from: StatusState, to: StatusState) -> Result<(), Error> {
+        match (from, to) {
+            (
+                StatusState::Pending,
+                StatusState::Approved | StatusState::Denied | StatusState::Cancelled,
+            ) => Ok(()),
+            (StatusState::Pending, _) => Err(Error::BadRequest(format!(
+                "a status can't be moved to {:?}",
+                to
+            ))),
+            _ => Err(Error::Conflict(format!(
+                "status is already {:?}, it can't become {:?}",
+                from, to
+            ))),
+        }
+    }
+
+    // Controllers linked to the camera that took the picture and able to receive a status.
+    async fn find_controllers(&self, picture: &Picture) -> Result<Vec<(Uuid, String)>, Error> {
+        let camera_id = picture.device_id.ok_or_else(|| {
+            Error::NotFound(format!("No camera recorded for picture: {}", picture.id))
+        })?;
+
+        let controllers: Vec<(Uuid, String)> = self
+            .device_repo
+            .find_by_camera_id(camera_id)
+            .await?
+            .into_iter()
+            .filter(|device| !device.is_revoked())
+            .filter_map(|device| device.callback_url.map(|url| (device.id, url)))
+            .collect();
+
+        if controllers.is_empty() {
+            return Err(Error::NotFound(format!(
+                "No controller with a callback url for camera: {}",
+                camera_id
+            )));
+        }
+
+        Ok(controllers)
+    }
+
+    async fn status_response(
+        &self,
+        status: Status,
+        picture: Picture,
+    ) -> Result<StatusResponse, Error> {
+        let picture_url = self
+            .storage_repo
+            .signed_url(&picture.name, self.timeouts.signed_url_ttl)
+            .await?;
+
+        let deliveries = self
+            .delivery_repo
+            .find_by_status_id(status.id)
+            .await?
+            .into_iter()
+            .map(DeliveryResponse::new)
+            .collect();
+
+        Ok(StatusResponse::new(
+            status,
+            picture,
+            picture_url,
+            deliveries,
+        ))
+    }
+
+    // Pushes the status to the streams of the camera household and queues its webhooks.
+    async fn publish(&self, status: Status, picture: Picture) -> Result<StatusResponse, Error> {
+        let kind = EventKind::from_state(status.state);
+        let user_id = picture.user_id;
+        let household_id = match picture.device_id {
+            Some(device_id) => self
+                .device_repo
+                .find_by_id(device_id)
+                .await?
+                .and_then(|device| device.household_id),
+            None => None,
+        };
+        let status_response = self.status_response(status, picture).await?;
+        let event =
+            self.event_service
+                .publish(kind, user_id, household_id, status_response.clone());
+
+        // The change is kept even when the webhooks can't be queued.
+        let event_id = event.id;
+        if let Err(e) = self.webhook_service.enqueue(event).await {
+            tracing::warn!(event_id, error = %e, "Failed to queue webhooks for event");
+        }
+
+        Ok(status_response)
+    }
+
     async fn find_picture_by_id(&self, id: Uuid) -> Result<Picture, Error> {
         self.picture_repo
             .find_by_id(id)
@@ -46,36 +243,126 @@ impl StatusService for StatusServiceImpl {
             .ok_or_else(|| Error::NotFound(format!("Status not found for ID: {}", status_id)))?;
 
         let picture = self.find_picture_by_id(status.picture_id).await?;
-        Ok(StatusResponse::new(status, picture))
+        self.status_response(status, picture).await
     }
 
-    async fn update_authorisation(
+    async fn decide(
         &self,
         status_id: Uuid,
-        authorised: bool,
+        context: AuditContext,
+        state: StatusState,
+        reason: Option<String>,
     ) -> Result<StatusResponse, Error> {
-        // The error handling might be weaker here since it could be another error than not found
-        let mut updated_status = self
+        let previous_status = self
             .status_repo
-            .find_and_update_authorised(status_id, authorised)
+            .find_by_id(status_id)
             .await?
             .ok_or_else(|| Error::NotFound(format!("Status not found for ID: {}", status_id)))?;
 
-        updated_status.authorised = authorised;
-        updated_status.updated_at = Some(chrono::Local::now());
+        let from = previous_status.state;
+        Self::check_transition(from, state)?;
+        // Not swept by `StatusExpiryWorker` yet, but already too late to decide.
+        if previous_status.expires_at <= Utc::now() {
+            return Err(Error::Conflict(format!("Status {} has expired", status_id)));
+        }
+
+        let now = chrono::Local::now();
+        let mut status = previous_status.clone();
+        status.state = state;
+        status.decided_by = context.actor;
+        status.decided_at = Some(now);
+        status.reason = reason;
+        status.updated_at = Some(now);
+
+        // Someone else decided, or the status expired, since it was read.
+        let updated_status = self
+            .status_repo
+            .find_and_update_state(&status, from)
+            .await?
+            .ok_or_else(|| {
+                Error::Conflict(format!(
+                    "Status {} was changed concurrently or has expired",
+                    status_id
+                ))
+            })?;
+        self.record_decision(&updated_status);
+
+        self.audit_transition(
+            AuditAction::StatusDecided,
+            context,
+            &previous_status,
+            &updated_status,
+        )
+        .await;
+
+        // The decision is kept even when no controller can be notified yet.
+        if let Err(e) = self.send_status(status_id).await {
+            tracing::warn!(status_id = %status_id, error = %e, "Failed to queue status");
+        }
 
         let picture = self.find_picture_by_id(updated_status.picture_id).await?;
 
-        Ok(StatusResponse::new(updated_status, picture))
+        self.publish(updated_status, picture).await
     }
 
     async fn create_initial_status(&self, picture_id: Uuid) -> Result<StatusResponse, Error> {
         let picture = self.find_picture_by_id(picture_id).await?;
 
-        let status_model = Status::new(picture_id);
+        let expires_at = Utc::now()
+            + chrono::Duration::from_std(self.timeouts.pending_timeout)
+                .map_err(|e| Error::Parse(e.to_string()))?;
+
+        let status_model = Status::new(picture_id, expires_at);
         let _initial_status = self.status_repo.insert(&status_model).await?;
 
-        Ok(StatusResponse::new(status_model, picture))
+        self.publish(status_model, picture).await
+    }
+
+    async fn find_pending_by_camera(&self, camera_id: Uuid) -> Result<Vec<Status>, Error> {
+        self.status_repo.find_pending_by_camera(camera_id).await
+    }
+
+    async fn count_pending(&self) -> Result<u64, Error> {
+        self.status_repo.count_pending().await
+    }
+
+    async fn expire_stale(&self) -> Result<u64, Error> {
+        let mut expired = 0;
+
+        for previous_status in self.status_repo.find_stale_pending().await? {
+            let mut status = previous_status.clone();
+            status.state = StatusState::Expired;
+            status.updated_at = Some(chrono::Local::now());
+
+            // Skipped when it was decided in the meantime.
+            if let Some(updated_status) = self
+                .status_repo
+                .find_and_update_state(&status, StatusState::Pending)
+                .await?
+            {
+                self.record_decision(&updated_status);
+                self.audit_transition(
+                    AuditAction::StatusExpired,
+                    AuditContext::default(),
+                    &previous_status,
+                    &updated_status,
+                )
+                .await;
+
+                let status_id = updated_status.id;
+                let published = async {
+                    let picture = self.find_picture_by_id(updated_status.picture_id).await?;
+                    self.publish(updated_status, picture).await
+                };
+                if let Err(e) = published.await {
+                    tracing::warn!(status_id = %status_id, error = %e, "Failed to publish expiry");
+                }
+
+                expired += 1;
+            }
+        }
+
+        Ok(expired)
     }
 
     async fn send_status(&self, status_id: Uuid) -> Result<bool, Error> {
@@ -85,35 +372,261 @@ impl StatusService for StatusServiceImpl {
             .await?
             .ok_or_else(|| Error::NotFound(format!("Status not found for ID: {}", status_id)))?;
         let picture = self.find_picture_by_id(status.picture_id).await?;
+        let controllers = self.find_controllers(&picture).await?;
 
-        let status_payload = StatusResponse::new(status, picture);
+        let expires_at = Utc::now()
+            + chrono::Duration::from_std(self.timeouts.delivery_ttl)
+                .map_err(|e| Error::Parse(e.to_string()))?;
 
-        let client = reqwest::Client::new();
-        println!("Sending status to: {}", self.http_server_address);
-
-        match client
-            .post(&self.http_server_address)
-            .header("Content-Type", "application/json")
-            .json(&status_payload)
-            .send()
-            .await
-        {
-            Ok(response) => {
-                if response.status().is_success() {
-                    println!("Successfully sent status to HTTP Server");
-                    Ok(true)
-                } else {
-                    let status = response.status();
-                    let error_msg = format!("HTTP Server responded with error status: {}", status);
-                    println!("{}", error_msg);
-                    Err(Error::Service(error_msg))
+        for (device_id, callback_url) in controllers {
+            // The same decision is already waiting to be delivered.
+            if let Some(pending) = self
+                .delivery_repo
+                .find_pending(status.id, device_id)
+                .await?
+            {
+                if pending.authorised == status.is_authorised()
+                    && pending.callback_url == callback_url
+                {
+                    continue;
                 }
             }
-            Err(err) => {
-                let error_msg = format!("Failed to send status to HTTP Server: {}", err);
-                println!("{}", error_msg);
-                Err(Error::Service(error_msg))
-            }
+
+            self.delivery_repo
+                .supersede_pending(status.id, device_id)
+                .await?;
+
+            let delivery = Delivery::new(
+                status.id,
+                device_id,
+                callback_url,
+                status.is_authorised(),
+                expires_at,
+                current_request_id(),
+            );
+            tracing::info!(
+                status_id = %status.id,
+                device_id = %device_id,
+                callback_url = %redact_url(&delivery.callback_url),
+                "Queued status for controller"
+            );
+            self.delivery_repo.insert(&delivery).await?;
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DeliveryState, Device, DeviceType};
+    use crate::repositories::InMemoryRepository;
+    use crate::services::{AuditServiceImpl, EventServiceImpl, WebhookServiceImpl};
+
+    fn service(repo: &Arc<InMemoryRepository>) -> StatusServiceImpl {
+        StatusServiceImpl::new(
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            Arc::new(AuditServiceImpl::new(
+                repo.clone(),
+                repo.clone(),
+                Vec::new(),
+            )),
+            Arc::new(EventServiceImpl::new(10)),
+            Arc::new(WebhookServiceImpl::new(
+                repo.clone(),
+                repo.clone(),
+                repo.clone(),
+            )),
+            Arc::new(Metrics::new().unwrap()),
+            StatusTimeouts {
+                signed_url_ttl: Duration::from_secs(60),
+                delivery_ttl: Duration::from_secs(60),
+                pending_timeout: Duration::from_secs(600),
+            },
+        )
+    }
+
+    async fn pending_status(repo: &Arc<InMemoryRepository>, expires_in: i64) -> Status {
+        let picture = Picture::new(Uuid::new(), Uuid::new(), "picture.jpg".to_string());
+        PictureRepository::insert(repo.as_ref(), &picture)
+            .await
+            .unwrap();
+        let status = Status::new(
+            picture.id,
+            Utc::now() + chrono::Duration::seconds(expires_in),
+        );
+        StatusRepository::insert(repo.as_ref(), &status)
+            .await
+            .unwrap();
+        status
+    }
+
+    fn context() -> AuditContext {
+        AuditContext::new(Some(Uuid::new()), None, None)
+    }
+
+    #[test]
+    fn only_pending_statuses_are_decided() {
+        use StatusState::*;
+
+        for to in [Approved, Denied, Cancelled] {
+            assert!(StatusServiceImpl::check_transition(Pending, to).is_ok());
+        }
+        assert!(matches!(
+            StatusServiceImpl::check_transition(Pending, Expired),
+            Err(Error::BadRequest(_))
+        ));
+        assert!(matches!(
+            StatusServiceImpl::check_transition(Pending, Pending),
+            Err(Error::BadRequest(_))
+        ));
+        for from in [Approved, Denied, Expired, Cancelled] {
+            assert!(matches!(
+                StatusServiceImpl::check_transition(from, Approved),
+                Err(Error::Conflict(_))
+            ));
         }
     }
+
+    #[actix_web::test]
+    async fn status_is_decided_once() {
+        let repo = Arc::new(InMemoryRepository::new());
+        let service = service(&repo);
+        let status = pending_status(&repo, 600).await;
+        let decider = Some(Uuid::new());
+
+        let decided = service
+            .decide(
+                status.id,
+                AuditContext::new(decider, None, None),
+                StatusState::Approved,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(decided.authorised);
+
+        let stored = StatusRepository::find_by_id(repo.as_ref(), status.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.state, StatusState::Approved);
+        assert_eq!(stored.decided_by, decider);
+        assert!(stored.decided_at.is_some());
+
+        let again = service
+            .decide(status.id, context(), StatusState::Denied, None)
+            .await;
+        assert!(matches!(again, Err(Error::Conflict(_))));
+    }
+
+    #[actix_web::test]
+    async fn stale_pending_statuses_expire() {
+        let repo = Arc::new(InMemoryRepository::new());
+        let service = service(&repo);
+        let stale = pending_status(&repo, -1).await;
+        let fresh = pending_status(&repo, 600).await;
+
+        assert_eq!(service.expire_stale().await.unwrap(), 1);
+        assert_eq!(service.expire_stale().await.unwrap(), 0);
+
+        let stale = StatusRepository::find_by_id(repo.as_ref(), stale.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stale.state, StatusState::Expired);
+        assert_eq!(service.count_pending().await.unwrap(), 1);
+
+        // An expired status can't be decided anymore, the fresh one still can.
+        let late = service
+            .decide(stale.id, context(), StatusState::Approved, None)
+            .await;
+        assert!(matches!(late, Err(Error::Conflict(_))));
+        assert!(service
+            .decide(fresh.id, context(), StatusState::Denied, None)
+            .await
+            .is_ok());
+    }
+
+    #[actix_web::test]
+    async fn overdue_status_cant_be_decided_before_it_is_swept() {
+        let repo = Arc::new(InMemoryRepository::new());
+        let service = service(&repo);
+        let status = pending_status(&repo, -1).await;
+
+        let late = service
+            .decide(status.id, context(), StatusState::Approved, None)
+            .await;
+        assert!(matches!(late, Err(Error::Conflict(_))));
+
+        // Still pending for the sweep, which is the only way out.
+        assert_eq!(service.expire_stale().await.unwrap(), 1);
+    }
+
+    #[actix_web::test]
+    async fn decided_status_doesnt_expire() {
+        let repo = Arc::new(InMemoryRepository::new());
+        let service = service(&repo);
+        let status = pending_status(&repo, 600).await;
+
+        service
+            .decide(status.id, context(), StatusState::Denied, None)
+            .await
+            .unwrap();
+        assert_eq!(service.expire_stale().await.unwrap(), 0);
+    }
+
+    #[actix_web::test]
+    async fn new_decision_supersedes_the_queued_one() {
+        let repo = Arc::new(InMemoryRepository::new());
+        let service = service(&repo);
+        let status = pending_status(&repo, 600).await;
+        let picture = PictureRepository::find_by_id(repo.as_ref(), status.picture_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut controller = Device::new(
+            picture.user_id,
+            Uuid::new(),
+            "door".to_string(),
+            DeviceType::Esp32Main,
+            "secret".to_string(),
+        );
+        controller.camera_id = picture.device_id;
+        controller.callback_url = Some("http://192.168.1.20/authorised".to_string());
+        DeviceRepository::insert(repo.as_ref(), &controller)
+            .await
+            .unwrap();
+
+        service.send_status(status.id).await.unwrap();
+        // The same decision isn't queued twice.
+        service.send_status(status.id).await.unwrap();
+        // Made through the API, the request ID follows the decision to the controller.
+        crate::logging::with_request_id(
+            "decision-1".to_string(),
+            service.decide(status.id, context(), StatusState::Approved, None),
+        )
+        .await
+        .unwrap();
+
+        let deliveries = repo.find_by_status_id(status.id).await.unwrap();
+        assert_eq!(deliveries[0].request_id, None);
+        assert_eq!(deliveries[1].request_id.as_deref(), Some("decision-1"));
+        let states: Vec<_> = deliveries
+            .iter()
+            .map(|delivery| (delivery.state, delivery.authorised))
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                (DeliveryState::Superseded, false),
+                (DeliveryState::Pending, true),
+            ]
+        );
+    }
 }
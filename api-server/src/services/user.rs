@@ -3,9 +3,12 @@ use std::sync::Arc;
 
 use super::UserService;
 use crate::errors::Error;
-use crate::models::User;
+use crate::models::{User, UserIdentity};
 use crate::repositories::UserRepository;
 
+// Provider name of the accounts created when Google was the only one.
+const GOOGLE_PROVIDER: &str = "google";
+
 pub struct UserServiceImpl {
     user_repo: Arc<dyn UserRepository>,
 }
@@ -18,8 +21,23 @@ impl UserServiceImpl {
 
 #[async_trait]
 impl UserService for UserServiceImpl {
-    async fn get_by_google_id(&self, google_id: String) -> Result<Option<User>, Error> {
-        let user = self.user_repo.get_by_google_id(google_id).await?;
+    async fn find_by_identity(&self, identity: UserIdentity) -> Result<Option<User>, Error> {
+        if let Some(user) = self.user_repo.find_by_identity(&identity).await? {
+            return Ok(Some(user));
+        }
+
+        if identity.provider != GOOGLE_PROVIDER {
+            return Ok(None);
+        }
+
+        // An account from before identities, it gets its Google identity on the way.
+        let user = self
+            .user_repo
+            .get_by_google_id(identity.subject.clone())
+            .await?;
+        if let Some(user) = &user {
+            self.user_repo.add_identity(user.id, &identity).await?;
+        }
 
         Ok(user)
     }
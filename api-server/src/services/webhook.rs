@@ -0,0 +1,230 @@
+use async_trait::async_trait;
+use bson::Uuid;
+use rand::RngCore;
+use std::sync::Arc;
+
+use super::WebhookService;
+use crate::errors::Error;
+use crate::models::{EventKind, Webhook, WebhookDelivery};
+use crate::payloads::{Event, WebhookPayload};
+use crate::repositories::{HouseholdRepository, WebhookDeliveryRepository, WebhookRepository};
+use crate::url_guard::{self, Reach};
+
+const WEBHOOK_SECRET_LEN: usize = 32;
+const DELIVERY_LOG_LIMIT: i64 = 100;
+
+pub struct WebhookServiceImpl {
+    webhook_repo: Arc<dyn WebhookRepository>,
+    delivery_repo: Arc<dyn WebhookDeliveryRepository>,
+    household_repo: Arc<dyn HouseholdRepository>,
+}
+
+impl WebhookServiceImpl {
+    pub fn new(
+        webhook_repo: Arc<dyn WebhookRepository>,
+        delivery_repo: Arc<dyn WebhookDeliveryRepository>,
+        household_repo: Arc<dyn HouseholdRepository>,
+    ) -> Self {
+        Self {
+            webhook_repo,
+            delivery_repo,
+            household_repo,
+        }
+    }
+
+    // Every current member of the camera's household sees its history, so each gets the
+    // event. Events of cameras from before households only go to their user.
+    async fn recipients(&self, event: &Event) -> Result<Vec<Uuid>, Error> {
+        let Some(household_id) = event.household_id else {
+            return Ok(vec![event.user_id]);
+        };
+
+        Ok(self
+            .household_repo
+            .find_by_id(household_id)
+            .await?
+            .map(|household| {
+                household
+                    .members
+                    .into_iter()
+                    .map(|member| member.user_id)
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    fn generate_secret() -> String {
+        let mut secret = [0u8; WEBHOOK_SECRET_LEN];
+        rand::thread_rng().fill_bytes(&mut secret);
+        hex::encode(secret)
+    }
+
+    // Webhooks of other users are reported as not found to avoid leaking their IDs.
+    async fn find_owned(&self, user_id: Uuid, webhook_id: Uuid) -> Result<Webhook, Error> {
+        self.webhook_repo
+            .find_by_id(webhook_id)
+            .await?
+            .filter(|webhook| webhook.user_id == user_id)
+            .ok_or_else(|| Error::NotFound(format!("Webhook not found for ID: {}", webhook_id)))
+    }
+}
+
+#[async_trait]
+impl WebhookService for WebhookServiceImpl {
+    async fn register(
+        &self,
+        user_id: Uuid,
+        url: String,
+        events: Vec<EventKind>,
+    ) -> Result<Webhook, Error> {
+        let url = url_guard::validate(&url, "webhook url", Reach::Public).await?;
+
+        let mut subscribed: Vec<EventKind> = Vec::with_capacity(events.len());
+        for event in events {
+            if !subscribed.contains(&event) {
+                subscribed.push(event);
+            }
+        }
+        if subscribed.is_empty() {
+            return Err(Error::Empty(
+                "A webhook needs at least one event".to_string(),
+            ));
+        }
+
+        let webhook = Webhook::new(user_id, url, subscribed, Self::generate_secret());
+        self.webhook_repo.insert(&webhook).await?;
+
+        Ok(webhook)
+    }
+
+    async fn list(&self, user_id: Uuid) -> Result<Vec<Webhook>, Error> {
+        self.webhook_repo.find_by_user_id(user_id).await
+    }
+
+    // Deliveries still pending are failed by the worker once the webhook is gone.
+    async fn delete(&self, user_id: Uuid, webhook_id: Uuid) -> Result<(), Error> {
+        let webhook = self.find_owned(user_id, webhook_id).await?;
+        self.webhook_repo.delete(webhook.id).await
+    }
+
+    async fn deliveries(
+        &self,
+        user_id: Uuid,
+        webhook_id: Uuid,
+    ) -> Result<Vec<WebhookDelivery>, Error> {
+        let webhook = self.find_owned(user_id, webhook_id).await?;
+        self.delivery_repo
+            .find_by_webhook_id(webhook.id, DELIVERY_LOG_LIMIT)
+            .await
+    }
+
+    async fn enqueue(&self, event: Event) -> Result<usize, Error> {
+        let mut webhooks = Vec::new();
+        for user_id in self.recipients(&event).await? {
+            webhooks.extend(
+                self.webhook_repo
+                    .find_subscribed(user_id, event.kind)
+                    .await?,
+            );
+        }
+        if webhooks.is_empty() {
+            return Ok(0);
+        }
+
+        let (kind, household_id) = (event.kind, event.household_id);
+        let payload = serde_json::to_string(&WebhookPayload::new(event))
+            .map_err(|e| Error::Parse(e.to_string()))?;
+
+        for webhook in &webhooks {
+            let delivery = WebhookDelivery::new(webhook.id, household_id, kind, payload.clone());
+            self.delivery_repo.insert(&delivery).await?;
+        }
+
+        Ok(webhooks.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Household, HouseholdMember, HouseholdRole, Picture, Status};
+    use crate::payloads::StatusResponse;
+    use crate::repositories::InMemoryRepository;
+
+    fn event(user_id: Uuid, household_id: Option<Uuid>) -> Event {
+        let picture = Picture::new(user_id, Uuid::new(), "picture.jpg".to_string());
+        let status = Status::new(picture.id, chrono::Utc::now());
+        Event {
+            id: 1,
+            kind: EventKind::CaptureCreated,
+            user_id,
+            household_id,
+            status: StatusResponse::new(status, picture, String::new(), Vec::new()),
+        }
+    }
+
+    async fn webhook(repo: &InMemoryRepository, user_id: Uuid) -> Webhook {
+        let webhook = Webhook::new(
+            user_id,
+            "https://example.org/hook".to_string(),
+            vec![EventKind::CaptureCreated],
+            "secret".to_string(),
+        );
+        WebhookRepository::insert(repo, &webhook).await.unwrap();
+        webhook
+    }
+
+    #[actix_web::test]
+    async fn every_member_of_the_household_receives_the_event() {
+        let repo = Arc::new(InMemoryRepository::new());
+        let service = WebhookServiceImpl::new(repo.clone(), repo.clone(), repo.clone());
+        let (owner, approver) = (Uuid::new(), Uuid::new());
+        let household = Household::new("home".to_string(), owner);
+        HouseholdRepository::insert(repo.as_ref(), &household)
+            .await
+            .unwrap();
+        let member = HouseholdMember {
+            user_id: approver,
+            role: HouseholdRole::Approver,
+            joined_at: chrono::Local::now(),
+        };
+        repo.add_member(household.id, &member).await.unwrap();
+
+        let owner_webhook = webhook(&repo, owner).await;
+        let approver_webhook = webhook(&repo, approver).await;
+        let outsider_webhook = webhook(&repo, Uuid::new()).await;
+
+        let queued = service
+            .enqueue(event(owner, Some(household.id)))
+            .await
+            .unwrap();
+        assert_eq!(queued, 2);
+        for (webhook, expected) in [
+            (owner_webhook, 1),
+            (approver_webhook, 1),
+            (outsider_webhook, 0),
+        ] {
+            let deliveries = repo.find_by_webhook_id(webhook.id, 10).await.unwrap();
+            assert_eq!(deliveries.len(), expected);
+        }
+
+        // Removed members don't get the next events.
+        repo.remove_member(household.id, approver).await.unwrap();
+        let queued = service
+            .enqueue(event(owner, Some(household.id)))
+            .await
+            .unwrap();
+        assert_eq!(queued, 1);
+    }
+
+    #[actix_web::test]
+    async fn event_without_household_goes_to_its_user() {
+        let repo = Arc::new(InMemoryRepository::new());
+        let service = WebhookServiceImpl::new(repo.clone(), repo.clone(), repo.clone());
+        let user_id = Uuid::new();
+        webhook(&repo, user_id).await;
+        webhook(&repo, Uuid::new()).await;
+
+        assert_eq!(service.enqueue(event(user_id, None)).await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,217 @@
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+
+use crate::errors::Error;
+
+// Cloud metadata services, the IPv4 ones are link-local already.
+const METADATA_HOSTS: [&str; 2] = ["metadata", "metadata.google.internal"];
+const METADATA_IPV6: Ipv6Addr = Ipv6Addr::new(0xfd00, 0xec2, 0, 0, 0, 0, 0, 0x254);
+
+// Where the urls given by users and devices may point to. The server calls them itself, so
+// they must never reach the server's host or the cloud metadata endpoints.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reach {
+    // Devices live on the LAN, private addresses are fine.
+    Lan,
+    // Webhooks only go to public https endpoints.
+    Public,
+}
+
+impl Reach {
+    fn allows(self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        let reserved = ip.is_loopback()
+            || ip.is_unspecified()
+            || ip.is_multicast()
+            || match ip {
+                IpAddr::V4(ip) => ip.is_link_local() || ip.is_broadcast(),
+                IpAddr::V6(ip) => ip.is_unicast_link_local() || ip == METADATA_IPV6,
+            };
+        let private = match ip {
+            // 100.64.0.0/10 is the carrier-grade NAT range.
+            IpAddr::V4(ip) => {
+                ip.is_private() || (ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64)
+            }
+            IpAddr::V6(ip) => ip.is_unique_local(),
+        };
+
+        !reserved && (self == Reach::Lan || !private)
+    }
+
+    fn check_host(self, host: &str) -> Result<(), String> {
+        // IPv6 addresses are bracketed in urls.
+        let address = host.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(ip) = address.parse::<IpAddr>() {
+            if !self.allows(ip) {
+                return Err(format!("address {} is not allowed", ip));
+            }
+            return Ok(());
+        }
+
+        let domain = host.trim_end_matches('.').to_ascii_lowercase();
+        if domain == "localhost"
+            || domain.ends_with(".localhost")
+            || METADATA_HOSTS.contains(&domain.as_str())
+        {
+            return Err(format!("host {} is not allowed", domain));
+        }
+        Ok(())
+    }
+}
+
+// Checks the scheme and host of a url before it's stored, `what` names it in errors. Names
+// are resolved as well, though only `client` guards against what they resolve to later.
+pub async fn validate(url: &str, what: &str, reach: Reach) -> Result<String, Error> {
+    let url = Url::parse(url.trim())
+        .map_err(|e| Error::BadRequest(format!("invalid {}: {}", what, e)))?;
+
+    match (reach, url.scheme()) {
+        (Reach::Lan, "http" | "https") | (Reach::Public, "https") => {}
+        (_, scheme) => {
+            return Err(Error::BadRequest(format!(
+                "unsupported {} scheme: {}",
+                what, scheme
+            )))
+        }
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| Error::BadRequest(format!("invalid {}: missing host", what)))?;
+    reach
+        .check_host(host)
+        .map_err(|e| Error::BadRequest(format!("invalid {}: {}", what, e)))?;
+    GuardedResolver { reach }
+        .lookup(host.trim_start_matches('[').trim_end_matches(']'))
+        .await
+        .map_err(|e| Error::BadRequest(format!("invalid {}: {}", what, e)))?;
+
+    Ok(url.to_string())
+}
+
+// A client for urls checked with `validate`: names are resolved again on each connection and
+// refused when they point somewhere `reach` doesn't allow, and redirects aren't followed since
+// they could go anywhere.
+pub fn client(reach: Reach) -> Result<reqwest::Client, reqwest::Error> {
+    reqwest::Client::builder()
+        .dns_resolver(Arc::new(GuardedResolver { reach }))
+        .redirect(Policy::none())
+        .build()
+}
+
+struct GuardedResolver {
+    reach: Reach,
+}
+
+impl GuardedResolver {
+    async fn lookup(&self, host: &str) -> Result<Vec<SocketAddr>, String> {
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0))
+            .await
+            .map_err(|e| format!("failed to resolve {}: {}", host, e))?
+            .collect();
+
+        match addrs.iter().find(|addr| !self.reach.allows(addr.ip())) {
+            Some(addr) => Err(format!("{} resolves to {}, not allowed", host, addr.ip())),
+            None => Ok(addrs),
+        }
+    }
+}
+
+impl Resolve for GuardedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = GuardedResolver { reach: self.reach };
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs = resolver.lookup(&host).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn is_refused(url: &str, reach: Reach) -> bool {
+        matches!(validate(url, "url", reach).await, Err(Error::BadRequest(_)))
+    }
+
+    #[actix_web::test]
+    async fn server_host_and_metadata_are_refused() {
+        for url in [
+            "http://127.0.0.1:8080/",
+            "http://localhost/",
+            "http://api.localhost/",
+            "http://0.0.0.0/",
+            "http://[::1]/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://metadata.google.internal/",
+            "http://[fd00:ec2::254]/",
+            "http://[fe80::1]/",
+            // IPv4 addresses mapped into IPv6.
+            "http://[::ffff:127.0.0.1]/",
+            "http://[::ffff:169.254.169.254]/",
+        ] {
+            assert!(is_refused(url, Reach::Lan).await, "{} was accepted", url);
+            let url = url.replacen("http://", "https://", 1);
+            assert!(
+                is_refused(&url, Reach::Public).await,
+                "{} was accepted",
+                url
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn private_addresses_are_only_reached_on_the_lan() {
+        for url in [
+            "https://192.168.1.20/capture",
+            "https://10.0.0.5/",
+            "https://172.16.3.4/",
+            "https://100.64.0.1/",
+            "https://[fd12:3456::1]/",
+            "https://[::ffff:192.168.1.20]/",
+        ] {
+            assert!(is_refused(url, Reach::Public).await, "{} was accepted", url);
+            assert!(
+                validate(url, "url", Reach::Lan).await.is_ok(),
+                "{} was refused",
+                url
+            );
+        }
+
+        assert_eq!(
+            validate("http://192.168.1.20/capture", "url", Reach::Lan)
+                .await
+                .unwrap(),
+            "http://192.168.1.20/capture"
+        );
+        assert!(validate("https://93.184.216.34/hook", "url", Reach::Public)
+            .await
+            .is_ok());
+    }
+
+    #[actix_web::test]
+    async fn only_http_schemes_are_accepted() {
+        for url in [
+            "file:///etc/passwd",
+            "ftp://192.168.1.20/",
+            "gopher://192.168.1.20/",
+        ] {
+            assert!(is_refused(url, Reach::Lan).await, "{} was accepted", url);
+        }
+        // Webhooks are https only.
+        assert!(is_refused("http://93.184.216.34/hook", Reach::Public).await);
+    }
+
+    #[actix_web::test]
+    async fn names_resolving_to_the_server_are_refused() {
+        // What a stored name resolves to is checked again on each connection.
+        let resolver = GuardedResolver { reach: Reach::Lan };
+        assert!(resolver.lookup("localhost").await.is_err());
+        assert!(resolver.lookup("127.0.0.1").await.is_err());
+        assert!(resolver.lookup("192.168.1.20").await.is_ok());
+    }
+}
@@ -0,0 +1,490 @@
+use bson::Uuid;
+use rumqttc::{AsyncClient, QoS};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::mqtt_bridge::{availability_topic, camera_topic};
+use crate::errors::Error;
+use crate::models::{Device, DeviceType};
+use crate::repositories::{DeviceRepository, StorageRepository};
+use crate::services::StatusService;
+
+const NODE_PREFIX: &str = "rusty_secure";
+
+// An entity as Home Assistant discovers it, `config` is published retained on
+// `<discovery_prefix>/<component>/<node_id>/<object_id>/config`.
+struct Entity {
+    component: &'static str,
+    object_id: &'static str,
+    config: Value,
+}
+
+// Publishes Home Assistant MQTT discovery configs for the cameras and controllers, and the
+// state their entities read. Commands go through the bridge topics.
+pub struct HomeAssistant {
+    client: AsyncClient,
+    discovery_prefix: String,
+    topic_prefix: String,
+    refresh_interval: Duration,
+    // Only with unsigned decision commands, Home Assistant can't sign them.
+    decision_buttons: bool,
+    device_repo: Arc<dyn DeviceRepository>,
+    storage_repo: Arc<dyn StorageRepository>,
+    status_service: Arc<dyn StatusService>,
+}
+
+impl HomeAssistant {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        client: AsyncClient,
+        discovery_prefix: String,
+        topic_prefix: String,
+        refresh_interval: Duration,
+        decision_buttons: bool,
+        device_repo: Arc<dyn DeviceRepository>,
+        storage_repo: Arc<dyn StorageRepository>,
+        status_service: Arc<dyn StatusService>,
+    ) -> Self {
+        Self {
+            client,
+            discovery_prefix,
+            topic_prefix,
+            refresh_interval,
+            decision_buttons,
+            device_repo,
+            storage_repo,
+            status_service,
+        }
+    }
+
+    // Home Assistant publishes `online` there when it starts, configs are then sent again.
+    pub fn status_topic(&self) -> String {
+        format!("{}/status", self.discovery_prefix)
+    }
+
+    // Devices registered or revoked since the last run are picked up on the next one.
+    pub async fn run(&self) {
+        loop {
+            actix_web::rt::time::sleep(self.refresh_interval).await;
+
+            if let Err(e) = self.publish_discovery().await {
+                tracing::warn!(error = %e, "Failed to refresh Home Assistant discovery");
+            }
+        }
+    }
+
+    // Publishes the configs of every device, and removes those of the revoked ones.
+    pub async fn publish_discovery(&self) -> Result<(), Error> {
+        let devices = self.device_repo.find_all().await?;
+
+        for device in &devices {
+            // The camera of a controller may have been registered by another member, its
+            // topics are under that member.
+            let camera = device
+                .camera_id
+                .and_then(|camera_id| devices.iter().find(|camera| camera.id == camera_id));
+            for (topic, payload) in self.discovery_messages(device, camera)? {
+                self.publish(topic, payload).await?;
+            }
+
+            if !device.is_revoked() && device.device_type == DeviceType::Esp32Cam {
+                self.publish_pending(device.user_id, device.id).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Refreshes what the camera entities show after an event on one of its statuses.
+    pub async fn publish_state(
+        &self,
+        user_id: Uuid,
+        camera_id: Uuid,
+        picture_name: Option<&str>,
+    ) -> Result<(), Error> {
+        if let Some(picture_name) = picture_name {
+            let picture = self.storage_repo.download(picture_name).await?;
+            self.publish(
+                format!(
+                    "{}/picture",
+                    camera_topic(&self.topic_prefix, user_id, camera_id)
+                ),
+                picture,
+            )
+            .await?;
+        }
+
+        self.publish_pending(user_id, camera_id).await
+    }
+
+    async fn publish_pending(&self, user_id: Uuid, camera_id: Uuid) -> Result<(), Error> {
+        let pending = self
+            .status_service
+            .find_pending_by_camera(camera_id)
+            .await?
+            .len();
+
+        self.publish(
+            format!(
+                "{}/pending",
+                camera_topic(&self.topic_prefix, user_id, camera_id)
+            ),
+            pending.to_string().into_bytes(),
+        )
+        .await
+    }
+
+    async fn publish(&self, topic: String, payload: Vec<u8>) -> Result<(), Error> {
+        self.client
+            .publish(&topic, QoS::AtLeastOnce, true, payload)
+            .await
+            .map_err(|e| Error::Service(format!("Failed to publish on {}: {}", topic, e)))
+    }
+
+    // The retained config of each entity of the device, empty for one that must go.
+    fn discovery_messages(
+        &self,
+        device: &Device,
+        camera: Option<&Device>,
+    ) -> Result<Vec<(String, Vec<u8>)>, Error> {
+        let node_id = Self::node_id(device.id);
+        let active = !device.is_revoked();
+
+        self.entities(device, camera)
+            .into_iter()
+            .map(|entity| {
+                let topic = format!(
+                    "{}/{}/{}/{}/config",
+                    self.discovery_prefix, entity.component, node_id, entity.object_id
+                );
+                // An empty retained config deletes the entity.
+                let payload = match (active, entity.config) {
+                    (true, Value::Null) | (false, _) => Vec::new(),
+                    (true, config) => {
+                        serde_json::to_vec(&config).map_err(|e| Error::Parse(e.to_string()))?
+                    }
+                };
+                Ok((topic, payload))
+            })
+            .collect()
+    }
+
+    fn node_id(device_id: Uuid) -> String {
+        format!("{}_{}", NODE_PREFIX, device_id)
+    }
+
+    // The entities a device exposes, a `Null` config is one that must not exist (yet).
+    // `camera` is the one a controller follows.
+    fn entities(&self, device: &Device, camera: Option<&Device>) -> Vec<Entity> {
+        let node_id = Self::node_id(device.id);
+        let availability = availability_topic(&self.topic_prefix);
+
+        let entity = |object_id: &str, name: &str, mut config: Value| {
+            config["name"] = json!(name);
+            config["unique_id"] = json!(format!("{}_{}", node_id, object_id));
+            config["availability_topic"] = json!(availability);
+            config["device"] = json!({
+                "identifiers": [node_id],
+                "name": device.name,
+                "manufacturer": "Rusty Secure",
+                "model": match device.device_type {
+                    DeviceType::Esp32Cam => "ESP32-CAM",
+                    DeviceType::Esp32Main => "ESP32 controller",
+                    DeviceType::Integration => "Integration",
+                },
+            });
+            config
+        };
+
+        match device.device_type {
+            DeviceType::Esp32Cam => {
+                let topic = camera_topic(&self.topic_prefix, device.user_id, device.id);
+                let decision_topic = format!("{}/commands/decision", topic);
+
+                vec![
+                    Entity {
+                        component: "camera",
+                        object_id: "picture",
+                        config: entity(
+                            "picture",
+                            "Latest picture",
+                            json!({"topic": format!("{}/picture", topic)}),
+                        ),
+                    },
+                    Entity {
+                        component: "binary_sensor",
+                        object_id: "person",
+                        config: entity(
+                            "person",
+                            "Person at door",
+                            json!({
+                                "device_class": "occupancy",
+                                "state_topic": format!("{}/status", topic),
+                                "value_template":
+                                    "{{ 'ON' if value_json.state == 'Pending' else 'OFF' }}",
+                            }),
+                        ),
+                    },
+                    Entity {
+                        component: "sensor",
+                        object_id: "pending",
+                        config: entity(
+                            "pending",
+                            "Pending approvals",
+                            json!({
+                                "state_topic": format!("{}/pending", topic),
+                                "state_class": "measurement",
+                                "icon": "mdi:account-clock",
+                            }),
+                        ),
+                    },
+                    // Without a status ID the bridge decides the latest pending status.
+                    Entity {
+                        component: "button",
+                        object_id: "approve",
+                        config: if self.decision_buttons {
+                            entity(
+                                "approve",
+                                "Approve",
+                                json!({
+                                    "command_topic": decision_topic,
+                                    "payload_press": json!({"state": "Approved"}).to_string(),
+                                    "icon": "mdi:door-open",
+                                }),
+                            )
+                        } else {
+                            Value::Null
+                        },
+                    },
+                    Entity {
+                        component: "button",
+                        object_id: "deny",
+                        config: if self.decision_buttons {
+                            entity(
+                                "deny",
+                                "Deny",
+                                json!({
+                                    "command_topic": decision_topic,
+                                    "payload_press": json!({"state": "Denied"}).to_string(),
+                                    "icon": "mdi:door-closed-lock",
+                                }),
+                            )
+                        } else {
+                            Value::Null
+                        },
+                    },
+                ]
+            }
+            // A controller opens the door once the latest status of its camera is approved.
+            DeviceType::Esp32Main => {
+                let config = match camera {
+                    Some(camera) => {
+                        let mut config = entity(
+                            "access",
+                            "Access granted",
+                            json!({
+                                "state_topic": format!(
+                                    "{}/status",
+                                    camera_topic(&self.topic_prefix, camera.user_id, camera.id)
+                                ),
+                                "value_template":
+                                    "{{ 'ON' if value_json.state == 'Approved' else 'OFF' }}",
+                                "icon": "mdi:door",
+                            }),
+                        );
+                        config["device"]["via_device"] = json!(Self::node_id(camera.id));
+                        config
+                    }
+                    None => Value::Null,
+                };
+
+                vec![Entity {
+                    component: "binary_sensor",
+                    object_id: "access",
+                    config,
+                }]
+            }
+            DeviceType::Integration => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::Metrics;
+    use crate::repositories::InMemoryRepository;
+    use crate::services::{
+        AuditServiceImpl, EventServiceImpl, StatusServiceImpl, StatusTimeouts, WebhookServiceImpl,
+    };
+    use rumqttc::MqttOptions;
+
+    const PREFIX: &str = "rusty-secure";
+    const DISCOVERY_PREFIX: &str = "homeassistant";
+
+    fn home_assistant(decision_buttons: bool) -> HomeAssistant {
+        let repo = Arc::new(InMemoryRepository::new());
+        let status_service = Arc::new(StatusServiceImpl::new(
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            Arc::new(AuditServiceImpl::new(
+                repo.clone(),
+                repo.clone(),
+                Vec::new(),
+            )),
+            Arc::new(EventServiceImpl::new(10)),
+            Arc::new(WebhookServiceImpl::new(
+                repo.clone(),
+                repo.clone(),
+                repo.clone(),
+            )),
+            Arc::new(Metrics::new().unwrap()),
+            StatusTimeouts {
+                signed_url_ttl: Duration::from_secs(60),
+                delivery_ttl: Duration::from_secs(60),
+                pending_timeout: Duration::from_secs(600),
+            },
+        ));
+        let (client, _) = AsyncClient::new(MqttOptions::new("test", "localhost", 1883), 10);
+
+        HomeAssistant::new(
+            client,
+            DISCOVERY_PREFIX.to_string(),
+            PREFIX.to_string(),
+            Duration::from_secs(300),
+            decision_buttons,
+            repo.clone(),
+            repo,
+            status_service,
+        )
+    }
+
+    fn device(device_type: DeviceType) -> Device {
+        Device::new(
+            Uuid::new(),
+            Uuid::new(),
+            "door".to_string(),
+            device_type,
+            "secret".to_string(),
+        )
+    }
+
+    fn config(messages: &[(String, Vec<u8>)], topic: &str) -> Value {
+        let (_, payload) = messages
+            .iter()
+            .find(|(message_topic, _)| message_topic == topic)
+            .unwrap_or_else(|| panic!("nothing on {}", topic));
+        if payload.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(payload).unwrap()
+        }
+    }
+
+    #[test]
+    fn camera_entities_read_its_topics() {
+        let camera = device(DeviceType::Esp32Cam);
+        let node_id = HomeAssistant::node_id(camera.id);
+        let topic = camera_topic(PREFIX, camera.user_id, camera.id);
+
+        let messages = home_assistant(false)
+            .discovery_messages(&camera, None)
+            .unwrap();
+        assert_eq!(messages.len(), 5);
+
+        let picture = config(
+            &messages,
+            &format!("{}/camera/{}/picture/config", DISCOVERY_PREFIX, node_id),
+        );
+        assert_eq!(picture["unique_id"], format!("{}_picture", node_id));
+        assert_eq!(picture["topic"], format!("{}/picture", topic));
+        assert_eq!(picture["availability_topic"], "rusty-secure/availability");
+        assert_eq!(picture["device"]["model"], "ESP32-CAM");
+
+        let person = config(
+            &messages,
+            &format!(
+                "{}/binary_sensor/{}/person/config",
+                DISCOVERY_PREFIX, node_id
+            ),
+        );
+        assert_eq!(person["unique_id"], format!("{}_person", node_id));
+        assert_eq!(person["state_topic"], format!("{}/status", topic));
+
+        let pending = config(
+            &messages,
+            &format!("{}/sensor/{}/pending/config", DISCOVERY_PREFIX, node_id),
+        );
+        assert_eq!(pending["state_topic"], format!("{}/pending", topic));
+
+        // Only offered with unsigned decision commands.
+        let approve = format!("{}/button/{}/approve/config", DISCOVERY_PREFIX, node_id);
+        assert_eq!(config(&messages, &approve), Value::Null);
+        let messages = home_assistant(true)
+            .discovery_messages(&camera, None)
+            .unwrap();
+        let approve = config(&messages, &approve);
+        assert_eq!(
+            approve["command_topic"],
+            format!("{}/commands/decision", topic)
+        );
+        assert_eq!(approve["payload_press"], r#"{"state":"Approved"}"#);
+    }
+
+    #[test]
+    fn controller_follows_the_topics_of_its_camera() {
+        let camera = device(DeviceType::Esp32Cam);
+        // Registered by another member of the household.
+        let mut controller = device(DeviceType::Esp32Main);
+        controller.camera_id = Some(camera.id);
+        let node_id = HomeAssistant::node_id(controller.id);
+        let topic = format!(
+            "{}/binary_sensor/{}/access/config",
+            DISCOVERY_PREFIX, node_id
+        );
+        let home_assistant = home_assistant(false);
+
+        let messages = home_assistant
+            .discovery_messages(&controller, Some(&camera))
+            .unwrap();
+        assert_eq!(messages.len(), 1);
+        let access = config(&messages, &topic);
+        assert_eq!(access["unique_id"], format!("{}_access", node_id));
+        assert_eq!(
+            access["state_topic"],
+            format!("{}/status", camera_topic(PREFIX, camera.user_id, camera.id))
+        );
+        assert_eq!(
+            access["device"]["via_device"],
+            HomeAssistant::node_id(camera.id)
+        );
+
+        // Not paired yet.
+        let messages = home_assistant
+            .discovery_messages(&controller, None)
+            .unwrap();
+        assert_eq!(config(&messages, &topic), Value::Null);
+    }
+
+    #[test]
+    fn revoked_devices_have_their_entities_removed() {
+        let mut camera = device(DeviceType::Esp32Cam);
+        camera.revoked_at = Some(chrono::Local::now());
+
+        let messages = home_assistant(true)
+            .discovery_messages(&camera, None)
+            .unwrap();
+        assert_eq!(messages.len(), 5);
+        assert!(messages.iter().all(|(_, payload)| payload.is_empty()));
+
+        let integration = device(DeviceType::Integration);
+        let messages = home_assistant(true)
+            .discovery_messages(&integration, None)
+            .unwrap();
+        assert!(messages.is_empty());
+    }
+}
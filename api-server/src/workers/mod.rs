@@ -0,0 +1,22 @@
+mod outbox;
+pub use outbox::OutboxWorker;
+
+mod status_expiry;
+pub use status_expiry::StatusExpiryWorker;
+
+mod webhook;
+pub use webhook::WebhookWorker;
+
+mod mqtt_bridge;
+pub use mqtt_bridge::{availability_topic, MqttBridge};
+
+mod home_assistant;
+pub use home_assistant::HomeAssistant;
+
+use std::time::Duration;
+
+// Exponential backoff after a failed attempt: base, twice the base, four times... up to max.
+fn retry_backoff(attempts: u32, base: Duration, max: Duration) -> Duration {
+    let exponent = attempts.saturating_sub(1).min(16);
+    (base * 2u32.pow(exponent)).min(max)
+}
@@ -0,0 +1,534 @@
+use bson::Uuid;
+use rumqttc::{AsyncClient, Event as MqttEvent, EventLoop, Packet, QoS};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+use super::HomeAssistant;
+use crate::config::MqttDecisions;
+use crate::errors::Error;
+use crate::heartbeat::{Heartbeat, Heartbeats};
+use crate::models::{AuditContext, Device, DeviceType, EventKind, HouseholdRole};
+use crate::mqtt_client::KEEP_ALIVE;
+use crate::payloads::{MqttDecisionCommand, SignedMqttCommand, WebhookPayload};
+use crate::services::{DeviceService, EventService, HouseholdService, StatusService};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+const CAPTURE_COMMAND: &str = "capture";
+const DECISION_COMMAND: &str = "decision";
+
+pub fn camera_topic(topic_prefix: &str, user_id: Uuid, camera_id: Uuid) -> String {
+    format!("{}/{}/{}", topic_prefix, user_id, camera_id)
+}
+
+// `online` while the bridge is connected, the broker sets it `offline` otherwise.
+pub fn availability_topic(topic_prefix: &str) -> String {
+    format!("{}/availability", topic_prefix)
+}
+
+// Publishes the events of each camera under `<prefix>/<user_id>/<camera_id>/` and runs
+// the commands published under `<prefix>/<user_id>/<camera_id>/commands/`.
+pub struct MqttBridge {
+    client: AsyncClient,
+    topic_prefix: String,
+    event_service: Arc<dyn EventService>,
+    status_service: Arc<dyn StatusService>,
+    device_service: Arc<dyn DeviceService>,
+    household_service: Arc<dyn HouseholdService>,
+    decision_commands: MqttDecisions,
+    home_assistant: Option<Arc<HomeAssistant>>,
+    heartbeat: Heartbeat,
+}
+
+impl MqttBridge {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        client: AsyncClient,
+        topic_prefix: String,
+        event_service: Arc<dyn EventService>,
+        status_service: Arc<dyn StatusService>,
+        device_service: Arc<dyn DeviceService>,
+        household_service: Arc<dyn HouseholdService>,
+        decision_commands: MqttDecisions,
+        heartbeats: &Arc<Heartbeats>,
+    ) -> Self {
+        Self {
+            client,
+            topic_prefix,
+            event_service,
+            status_service,
+            device_service,
+            household_service,
+            decision_commands,
+            home_assistant: None,
+            // The broker answers a ping at least every keep alive while connected. The API works
+            // without it, so a broker down doesn't take the server out of rotation.
+            heartbeat: heartbeats.register_optional("mqtt_bridge", KEEP_ALIVE),
+        }
+    }
+
+    pub fn with_home_assistant(mut self, home_assistant: HomeAssistant) -> Self {
+        self.home_assistant = Some(Arc::new(home_assistant));
+        self
+    }
+
+    pub fn start(self, event_loop: EventLoop) {
+        let bridge = Arc::new(self);
+
+        if let Some(home_assistant) = bridge.home_assistant.clone() {
+            actix_web::rt::spawn(async move { home_assistant.run().await });
+        }
+
+        let publisher = bridge.clone();
+        actix_web::rt::spawn(async move { publisher.publish_events().await });
+        actix_web::rt::spawn(async move { bridge.poll(event_loop).await });
+    }
+
+    async fn publish_events(&self) {
+        let mut receiver = self.event_service.subscribe_all();
+
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::warn!(missed, "MQTT bridge fell behind, events not published");
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+
+            let Some(camera_id) = event.status.picture.device_id else {
+                continue;
+            };
+            let user_id = event.user_id;
+            // Only a new capture changes the picture.
+            let picture_name = (event.kind == EventKind::CaptureCreated)
+                .then(|| event.status.picture.name.clone());
+            let camera_topic = camera_topic(&self.topic_prefix, user_id, camera_id);
+            let event_topic = format!("{}/events/{}", camera_topic, event.kind.name());
+
+            let status = match serde_json::to_vec(&event.status) {
+                Ok(status) => status,
+                Err(e) => {
+                    tracing::error!(status_id = %event.status.id, error = %e, "Failed to serialise status");
+                    continue;
+                }
+            };
+            let payload = match serde_json::to_vec(&WebhookPayload::new(event)) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    tracing::error!(topic = %event_topic, error = %e, "Failed to serialise event");
+                    continue;
+                }
+            };
+
+            // The latest status is retained so a new subscriber gets it right away.
+            let published = async {
+                self.client
+                    .publish(
+                        format!("{}/status", camera_topic),
+                        QoS::AtLeastOnce,
+                        true,
+                        status,
+                    )
+                    .await?;
+                self.client
+                    .publish(&event_topic, QoS::AtLeastOnce, false, payload)
+                    .await
+            };
+            if let Err(e) = published.await {
+                tracing::warn!(topic = %event_topic, error = %e, "Failed to publish");
+            }
+
+            if let Some(home_assistant) = &self.home_assistant {
+                if let Err(e) = home_assistant
+                    .publish_state(user_id, camera_id, picture_name.as_deref())
+                    .await
+                {
+                    tracing::warn!(camera_id = %camera_id, error = %e, "Failed to update Home Assistant");
+                }
+            }
+        }
+    }
+
+    async fn poll(self: Arc<Self>, mut event_loop: EventLoop) {
+        tracing::info!("MQTT bridge started");
+
+        loop {
+            let event = event_loop.poll().await;
+            match &event {
+                Ok(_) => self.heartbeat.beat(),
+                Err(e) => self.heartbeat.fail(format!("MQTT connection error: {}", e)),
+            }
+
+            match event {
+                Ok(MqttEvent::Incoming(Packet::ConnAck(_))) => {
+                    tracing::info!("Connected to MQTT broker");
+                    self.subscribe();
+                    self.announce();
+                }
+                Ok(MqttEvent::Incoming(Packet::Publish(publish)))
+                    if self.is_home_assistant_online(&publish.topic, &publish.payload) =>
+                {
+                    self.announce();
+                }
+                Ok(MqttEvent::Incoming(Packet::Publish(publish))) => {
+                    // Handled aside, the event loop must keep being polled meanwhile.
+                    let bridge = self.clone();
+                    actix_web::rt::spawn(async move {
+                        if let Err(e) = bridge
+                            .handle_command(&publish.topic, &publish.payload)
+                            .await
+                        {
+                            tracing::warn!(topic = %publish.topic, error = %e, "MQTT command failed");
+                        }
+                    });
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!(error = %e, "MQTT connection error");
+                    actix_web::rt::time::sleep(RECONNECT_DELAY).await;
+                }
+            }
+        }
+    }
+
+    // Subscriptions don't survive a reconnection with a clean session.
+    fn subscribe(&self) {
+        let mut commands = vec![CAPTURE_COMMAND];
+        if self.decision_commands != MqttDecisions::Disabled {
+            commands.push(DECISION_COMMAND);
+        }
+        let mut topics: Vec<String> = commands
+            .iter()
+            .map(|command| format!("{}/+/+/commands/{}", self.topic_prefix, command))
+            .collect();
+        if let Some(home_assistant) = &self.home_assistant {
+            topics.push(home_assistant.status_topic());
+        }
+
+        for topic in topics {
+            if let Err(e) = self.client.try_subscribe(&topic, QoS::AtLeastOnce) {
+                tracing::warn!(topic = %topic, error = %e, "Failed to subscribe");
+            }
+        }
+    }
+
+    fn is_home_assistant_online(&self, topic: &str, payload: &[u8]) -> bool {
+        self.home_assistant
+            .as_ref()
+            .is_some_and(|home_assistant| topic == home_assistant.status_topic())
+            && payload == b"online"
+    }
+
+    // Marks the bridge online and sends the Home Assistant discovery configs.
+    fn announce(&self) {
+        let topic = availability_topic(&self.topic_prefix);
+        if let Err(e) = self
+            .client
+            .try_publish(&topic, QoS::AtLeastOnce, true, "online")
+        {
+            tracing::warn!(topic = %topic, error = %e, "Failed to publish");
+        }
+
+        if let Some(home_assistant) = self.home_assistant.clone() {
+            actix_web::rt::spawn(async move {
+                if let Err(e) = home_assistant.publish_discovery().await {
+                    tracing::warn!(error = %e, "Failed to publish Home Assistant discovery");
+                }
+            });
+        }
+    }
+
+    async fn handle_command(&self, topic: &str, payload: &[u8]) -> Result<(), Error> {
+        let parts: Vec<&str> = topic
+            .strip_prefix(&self.topic_prefix)
+            .and_then(|rest| rest.strip_prefix('/'))
+            .map(|rest| rest.split('/').collect())
+            .unwrap_or_default();
+
+        let [user_id, camera_id, "commands", command] = parts[..] else {
+            return Err(Error::BadRequest(format!("unexpected topic: {}", topic)));
+        };
+        let user_id = Uuid::parse_str(user_id)
+            .map_err(|_| Error::UuidFormat("Invalid user ID format".to_string()))?;
+        let camera_id = Uuid::parse_str(camera_id)
+            .map_err(|_| Error::UuidFormat("Invalid camera ID format".to_string()))?;
+
+        if command != CAPTURE_COMMAND && command != DECISION_COMMAND {
+            return Err(Error::BadRequest(format!("unknown command: {}", command)));
+        }
+        // Commands act on the camera for the user of the topic, whoever published them.
+        self.check_approver(user_id, camera_id).await?;
+
+        // Whoever can publish on the topic isn't trusted unless told so. Once decisions are
+        // signed, captures are as well, the topic alone proves nothing.
+        let (actor, device_id, payload) = match self.decision_commands {
+            MqttDecisions::Disabled if command == DECISION_COMMAND => {
+                return Err(Error::Forbidden(
+                    "decision commands are disabled".to_string(),
+                ))
+            }
+            MqttDecisions::Signed => {
+                let (integration, command) = self.authenticate_command(topic, payload).await?;
+                (integration.user_id, Some(integration.id), command)
+            }
+            MqttDecisions::Disabled | MqttDecisions::Unsigned => (user_id, None, payload.to_vec()),
+        };
+
+        if command == CAPTURE_COMMAND {
+            return self.device_service.trigger_capture(actor, camera_id).await;
+        }
+
+        let command: MqttDecisionCommand =
+            serde_json::from_slice(&payload).map_err(|e| Error::JSONUnmarshall(e.to_string()))?;
+        self.decide(actor, device_id, camera_id, command).await
+    }
+
+    // Cameras of other households are reported as not found to avoid leaking their IDs.
+    async fn check_approver(&self, user_id: Uuid, camera_id: Uuid) -> Result<(), Error> {
+        let can_approve = self
+            .household_service
+            .accessible_devices(user_id, HouseholdRole::Approver)
+            .await?
+            .iter()
+            .any(|device| device.id == camera_id);
+        if !can_approve {
+            return Err(Error::NotFound(format!(
+                "Device not found for ID: {}",
+                camera_id
+            )));
+        }
+
+        Ok(())
+    }
+
+    // Checks the signature of an integration device, replays included, and returns it with
+    // the signed command.
+    async fn authenticate_command(
+        &self,
+        topic: &str,
+        payload: &[u8],
+    ) -> Result<(Device, Vec<u8>), Error> {
+        let signed: SignedMqttCommand =
+            serde_json::from_slice(payload).map_err(|e| Error::JSONUnmarshall(e.to_string()))?;
+        let device_id = Uuid::parse_str(&signed.device_id)
+            .map_err(|_| Error::Unauthorized("invalid device ID".to_string()))?;
+
+        // The topic is signed as well, a command only applies to the camera it was made for.
+        let body = format!("{}.{}", topic, signed.command);
+        let device = self
+            .device_service
+            .authenticate(
+                device_id,
+                signed.timestamp,
+                &signed.signature,
+                body.as_bytes(),
+            )
+            .await?;
+        if device.device_type != DeviceType::Integration {
+            return Err(Error::Unauthorized(format!(
+                "device is not a {:?}",
+                DeviceType::Integration
+            )));
+        }
+
+        Ok((device, signed.command.into_bytes()))
+    }
+
+    // The status must be one of the camera's, and the actor an approver of its household.
+    async fn decide(
+        &self,
+        actor: Uuid,
+        device_id: Option<Uuid>,
+        camera_id: Uuid,
+        command: MqttDecisionCommand,
+    ) -> Result<(), Error> {
+        self.check_approver(actor, camera_id).await?;
+
+        let status_id = match command.status_id {
+            Some(status_id) => {
+                let status_id = Uuid::parse_str(&status_id)
+                    .map_err(|_| Error::UuidFormat("Invalid status ID format".to_string()))?;
+                let status = self.status_service.get_status_details(status_id).await?;
+                if status.picture.device_id != Some(camera_id) {
+                    return Err(Error::NotFound(format!(
+                        "Status not found for ID: {}",
+                        status_id
+                    )));
+                }
+                status_id
+            }
+            None => self
+                .status_service
+                .find_pending_by_camera(camera_id)
+                .await?
+                .first()
+                .map(|status| status.id)
+                .ok_or_else(|| {
+                    Error::NotFound(format!("No pending status for camera: {}", camera_id))
+                })?,
+        };
+
+        let context = AuditContext::new(Some(actor), device_id, None);
+        self.status_service
+            .decide(status_id, context, command.state, command.reason)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::Metrics;
+    use crate::models::{Household, HouseholdMember};
+    use crate::repositories::{DeviceRepository, HouseholdRepository, InMemoryRepository};
+    use crate::services::{
+        AuditServiceImpl, DeviceServiceImpl, EventServiceImpl, HouseholdServiceImpl,
+        StatusServiceImpl, StatusTimeouts, WebhookServiceImpl,
+    };
+    use rumqttc::MqttOptions;
+
+    const PREFIX: &str = "rusty-secure";
+
+    struct Setup {
+        bridge: MqttBridge,
+        owner: Uuid,
+        viewer: Uuid,
+        camera: Device,
+    }
+
+    async fn setup(decision_commands: MqttDecisions) -> Setup {
+        let repo = Arc::new(InMemoryRepository::new());
+        let household_service = Arc::new(HouseholdServiceImpl::new(
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            Duration::from_secs(3600),
+        ));
+        let event_service = Arc::new(EventServiceImpl::new(10));
+        let status_service = Arc::new(StatusServiceImpl::new(
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            Arc::new(AuditServiceImpl::new(
+                repo.clone(),
+                repo.clone(),
+                Vec::new(),
+            )),
+            event_service.clone(),
+            Arc::new(WebhookServiceImpl::new(
+                repo.clone(),
+                repo.clone(),
+                repo.clone(),
+            )),
+            Arc::new(Metrics::new().unwrap()),
+            StatusTimeouts {
+                signed_url_ttl: Duration::from_secs(60),
+                delivery_ttl: Duration::from_secs(60),
+                pending_timeout: Duration::from_secs(600),
+            },
+        ));
+        let device_service = Arc::new(DeviceServiceImpl::new(
+            repo.clone(),
+            household_service.clone(),
+            reqwest::Client::new(),
+            Duration::from_secs(300),
+        ));
+        let (client, _) = AsyncClient::new(MqttOptions::new("test", "localhost", 1883), 10);
+        let bridge = MqttBridge::new(
+            client,
+            PREFIX.to_string(),
+            event_service,
+            status_service,
+            device_service,
+            household_service,
+            decision_commands,
+            &Arc::new(Heartbeats::default()),
+        );
+
+        let (owner, viewer) = (Uuid::new(), Uuid::new());
+        let household = Household::new("home".to_string(), owner);
+        HouseholdRepository::insert(repo.as_ref(), &household)
+            .await
+            .unwrap();
+        let member = HouseholdMember {
+            user_id: viewer,
+            role: HouseholdRole::Viewer,
+            joined_at: chrono::Local::now(),
+        };
+        repo.add_member(household.id, &member).await.unwrap();
+        let camera = Device::new(
+            owner,
+            household.id,
+            "door".to_string(),
+            DeviceType::Esp32Cam,
+            "secret".to_string(),
+        );
+        DeviceRepository::insert(repo.as_ref(), &camera)
+            .await
+            .unwrap();
+
+        Setup {
+            bridge,
+            owner,
+            viewer,
+            camera,
+        }
+    }
+
+    fn command_topic(user_id: Uuid, camera_id: Uuid, command: &str) -> String {
+        format!(
+            "{}/commands/{}",
+            camera_topic(PREFIX, user_id, camera_id),
+            command
+        )
+    }
+
+    #[actix_web::test]
+    async fn signed_mode_requires_signed_captures() {
+        let setup = setup(MqttDecisions::Signed).await;
+        let topic = command_topic(setup.owner, setup.camera.id, CAPTURE_COMMAND);
+
+        let unsigned = setup.bridge.handle_command(&topic, b"").await;
+        assert!(matches!(unsigned, Err(Error::JSONUnmarshall(_))));
+
+        let forged = serde_json::json!({
+            "device_id": Uuid::new().to_string(),
+            "timestamp": chrono::Utc::now().timestamp(),
+            "signature": "00",
+            "command": "",
+        });
+        let forged = setup
+            .bridge
+            .handle_command(&topic, forged.to_string().as_bytes())
+            .await;
+        assert!(matches!(forged, Err(Error::Unauthorized(_))));
+    }
+
+    #[actix_web::test]
+    async fn topic_user_must_approve_for_the_camera() {
+        let setup = setup(MqttDecisions::Unsigned).await;
+
+        for user_id in [setup.viewer, Uuid::new()] {
+            for command in [CAPTURE_COMMAND, DECISION_COMMAND] {
+                let topic = command_topic(user_id, setup.camera.id, command);
+                let result = setup.bridge.handle_command(&topic, b"{}").await;
+                assert!(matches!(result, Err(Error::NotFound(_))));
+            }
+        }
+
+        // The owner gets past the role check, the camera has no capture url to call.
+        let topic = command_topic(setup.owner, setup.camera.id, CAPTURE_COMMAND);
+        let result = setup.bridge.handle_command(&topic, b"").await;
+        assert!(matches!(result, Err(Error::BadRequest(_))));
+    }
+}
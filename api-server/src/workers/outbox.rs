@@ -0,0 +1,176 @@
+use chrono::Utc;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::Instrument;
+
+use super::retry_backoff;
+use crate::errors::Error;
+use crate::heartbeat::{Heartbeat, Heartbeats};
+use crate::logging::{redact_url, REQUEST_ID_HEADER};
+use crate::metrics::Metrics;
+use crate::models::{Delivery, DeliveryState};
+use crate::payloads::AuthorisedNotification;
+use crate::repositories::DeliveryRepository;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+// Capped so a controller coming back online is noticed soon enough.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+// Delivers the queued decisions to the controllers `/authorised` endpoint.
+pub struct OutboxWorker {
+    delivery_repo: Arc<dyn DeliveryRepository>,
+    metrics: Arc<Metrics>,
+    heartbeat: Heartbeat,
+    client: reqwest::Client,
+
+    poll_interval: Duration,
+    max_attempts: u32,
+}
+
+impl OutboxWorker {
+    pub fn new(
+        delivery_repo: Arc<dyn DeliveryRepository>,
+        metrics: Arc<Metrics>,
+        client: reqwest::Client,
+        poll_interval: Duration,
+        max_attempts: u32,
+        heartbeats: &Arc<Heartbeats>,
+    ) -> Self {
+        Self {
+            delivery_repo,
+            metrics,
+            heartbeat: heartbeats.register("outbox_worker", poll_interval + REQUEST_TIMEOUT),
+            client,
+            poll_interval,
+            max_attempts,
+        }
+    }
+
+    pub fn start(self) {
+        actix_web::rt::spawn(async move { self.run().await });
+    }
+
+    async fn run(self) {
+        tracing::info!("Outbox worker started");
+
+        loop {
+            match self.drain().await {
+                Ok(()) => self.heartbeat.beat(),
+                Err(e) => {
+                    tracing::error!(error = %e, "Outbox worker error");
+                    self.heartbeat.fail(e);
+                }
+            }
+
+            actix_web::rt::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    async fn drain(&self) -> Result<(), Error> {
+        let expired = self.delivery_repo.expire_stale().await?;
+        if expired > 0 {
+            self.metrics.record_deliveries("expired", expired);
+            tracing::info!(expired, "Expired stale deliveries");
+        }
+
+        // The lease keeps another worker from sending the same delivery meanwhile.
+        while let Some(delivery) = self
+            .delivery_repo
+            .claim_due(REQUEST_TIMEOUT + self.poll_interval)
+            .await?
+        {
+            // Each delivery may take up to the request timeout.
+            self.heartbeat.beat();
+
+            // Decisions without a request behind them, from MQTT or an expiry, are traced
+            // by their delivery ID.
+            let request_id = delivery
+                .request_id
+                .clone()
+                .unwrap_or_else(|| delivery.id.to_string());
+            let span = tracing::info_span!(
+                "delivery",
+                request_id = %request_id,
+                status_id = %delivery.status_id,
+                device_id = %delivery.device_id,
+            );
+            self.deliver(delivery, request_id).instrument(span).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn deliver(&self, mut delivery: Delivery, request_id: String) -> Result<(), Error> {
+        let callback_url = redact_url(&delivery.callback_url);
+        tracing::info!(
+            callback_url = %callback_url,
+            attempt = delivery.attempts,
+            "Sending status"
+        );
+
+        match self.send(&delivery, &request_id).await {
+            Ok(()) => {
+                tracing::info!(callback_url = %callback_url, "Sent status");
+                self.metrics.record_deliveries("delivered", 1);
+                delivery.state = DeliveryState::Delivered;
+                delivery.last_error = None;
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to send status");
+                delivery.last_error = Some(e);
+
+                if delivery.attempts >= self.max_attempts {
+                    self.metrics.record_deliveries("failed", 1);
+                    delivery.state = DeliveryState::Failed;
+                } else {
+                    self.metrics.record_deliveries("retried", 1);
+                    let backoff = chrono::Duration::from_std(retry_backoff(
+                        delivery.attempts,
+                        BASE_BACKOFF,
+                        MAX_BACKOFF,
+                    ))
+                    .map_err(|e| Error::Parse(e.to_string()))?;
+                    delivery.next_attempt_at = Utc::now() + backoff;
+                }
+            }
+        }
+
+        delivery.updated_at = Utc::now();
+        // A newer decision superseded this one while it was being sent, it's left as is.
+        if !self.delivery_repo.update_claimed(&delivery).await? {
+            tracing::info!("Delivery superseded while sending, outcome dropped");
+        }
+
+        Ok(())
+    }
+
+    async fn send(&self, delivery: &Delivery, request_id: &str) -> Result<(), String> {
+        let callback_url = redact_url(&delivery.callback_url);
+        let response = self
+            .client
+            .post(&delivery.callback_url)
+            .header(REQUEST_ID_HEADER, request_id)
+            .timeout(REQUEST_TIMEOUT)
+            .json(&AuthorisedNotification::new(delivery))
+            .send()
+            .await
+            .map_err(|e| {
+                format!(
+                    "Failed to send status to {}: {}",
+                    callback_url,
+                    e.without_url()
+                )
+            })?;
+
+        if !response.status().is_success() {
+            return Err(format!(
+                "{} responded with error status: {}",
+                callback_url,
+                response.status()
+            ));
+        }
+
+        Ok(())
+    }
+}
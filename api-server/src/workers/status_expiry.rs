@@ -0,0 +1,46 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::heartbeat::{Heartbeat, Heartbeats};
+use crate::services::StatusService;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+// Moves the statuses nobody decided on in time to Expired.
+pub struct StatusExpiryWorker {
+    status_service: Arc<dyn StatusService>,
+    heartbeat: Heartbeat,
+}
+
+impl StatusExpiryWorker {
+    pub fn new(status_service: Arc<dyn StatusService>, heartbeats: &Arc<Heartbeats>) -> Self {
+        Self {
+            status_service,
+            heartbeat: heartbeats.register("status_expiry_worker", CHECK_INTERVAL),
+        }
+    }
+
+    pub fn start(self) {
+        actix_web::rt::spawn(async move { self.run().await });
+    }
+
+    async fn run(self) {
+        tracing::info!("Status expiry worker started");
+
+        loop {
+            match self.status_service.expire_stale().await {
+                Ok(0) => self.heartbeat.beat(),
+                Ok(expired) => {
+                    tracing::info!(expired, "Expired pending statuses");
+                    self.heartbeat.beat();
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, "Status expiry worker error");
+                    self.heartbeat.fail(e);
+                }
+            }
+
+            actix_web::rt::time::sleep(CHECK_INTERVAL).await;
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::retry_backoff;
+use crate::errors::Error;
+use crate::heartbeat::{Heartbeat, Heartbeats};
+use crate::logging::redact_url;
+use crate::models::{DeliveryState, Webhook, WebhookDelivery};
+use crate::payloads::WebhookPayload;
+use crate::repositories::{
+    HouseholdRepository, StorageRepository, WebhookDeliveryRepository, WebhookRepository,
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const BASE_BACKOFF: Duration = Duration::from_secs(10);
+const MAX_BACKOFF: Duration = Duration::from_secs(3600);
+
+const EVENT_HEADER: &str = "X-Rusty-Secure-Event";
+const DELIVERY_HEADER: &str = "X-Rusty-Secure-Delivery";
+const TIMESTAMP_HEADER: &str = "X-Rusty-Secure-Timestamp";
+const SIGNATURE_HEADER: &str = "X-Rusty-Secure-Signature";
+
+// Delivers what the status changes queued for the subscribed webhooks.
+pub struct WebhookWorker {
+    webhook_repo: Arc<dyn WebhookRepository>,
+    delivery_repo: Arc<dyn WebhookDeliveryRepository>,
+    household_repo: Arc<dyn HouseholdRepository>,
+    storage_repo: Arc<dyn StorageRepository>,
+    heartbeat: Heartbeat,
+    client: reqwest::Client,
+
+    signed_url_ttl: Duration,
+    poll_interval: Duration,
+    max_attempts: u32,
+}
+
+impl WebhookWorker {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        webhook_repo: Arc<dyn WebhookRepository>,
+        delivery_repo: Arc<dyn WebhookDeliveryRepository>,
+        household_repo: Arc<dyn HouseholdRepository>,
+        storage_repo: Arc<dyn StorageRepository>,
+        client: reqwest::Client,
+        signed_url_ttl: Duration,
+        poll_interval: Duration,
+        max_attempts: u32,
+        heartbeats: &Arc<Heartbeats>,
+    ) -> Self {
+        Self {
+            webhook_repo,
+            delivery_repo,
+            household_repo,
+            storage_repo,
+            heartbeat: heartbeats.register("webhook_worker", poll_interval + REQUEST_TIMEOUT),
+            client,
+            signed_url_ttl,
+            poll_interval,
+            max_attempts,
+        }
+    }
+
+    pub fn start(self) {
+        actix_web::rt::spawn(async move { self.run().await });
+    }
+
+    async fn run(&self) {
+        tracing::info!("Webhook worker started");
+
+        loop {
+            match self.drain().await {
+                Ok(()) => self.heartbeat.beat(),
+                Err(e) => {
+                    tracing::error!(error = %e, "Webhook worker error");
+                    self.heartbeat.fail(e);
+                }
+            }
+
+            actix_web::rt::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    async fn drain(&self) -> Result<(), Error> {
+        while let Some(delivery) = self
+            .delivery_repo
+            .claim_due(REQUEST_TIMEOUT + self.poll_interval)
+            .await?
+        {
+            // Each delivery may take up to the request timeout.
+            self.heartbeat.beat();
+            self.deliver(delivery).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn deliver(&self, mut delivery: WebhookDelivery) -> Result<(), Error> {
+        let mut exhausted = delivery.attempts >= self.max_attempts;
+        let webhook = match self.webhook_repo.find_by_id(delivery.webhook_id).await? {
+            Some(webhook) if !self.is_member(&webhook, &delivery).await? => {
+                // Left the household after the event was queued, nothing is sent anymore.
+                exhausted = true;
+                Err("webhook user is no longer a member of the household".to_string())
+            }
+            Some(webhook) => Ok(webhook),
+            None => {
+                // Not worth retrying, the webhook won't come back.
+                exhausted = true;
+                Err("webhook was deleted".to_string())
+            }
+        };
+        let result = match webhook {
+            Ok(webhook) => match self.payload(&delivery).await {
+                Ok(payload) => {
+                    self.send(&delivery, &payload, &webhook.url, &webhook.secret)
+                        .await
+                }
+                Err(e) => Err((None, format!("Failed to prepare the payload: {}", e))),
+            },
+            Err(e) => Err((None, e)),
+        };
+
+        match result {
+            Ok(response_status) => {
+                delivery.state = DeliveryState::Delivered;
+                delivery.response_status = Some(response_status);
+                delivery.last_error = None;
+            }
+            Err((response_status, e)) => {
+                tracing::warn!(delivery_id = %delivery.id, error = %e, "Webhook delivery failed");
+                delivery.response_status = response_status;
+                delivery.last_error = Some(e);
+
+                if exhausted {
+                    delivery.state = DeliveryState::Failed;
+                } else {
+                    let backoff = chrono::Duration::from_std(retry_backoff(
+                        delivery.attempts,
+                        BASE_BACKOFF,
+                        MAX_BACKOFF,
+                    ))
+                    .map_err(|e| Error::Parse(e.to_string()))?;
+                    delivery.next_attempt_at = Utc::now() + backoff;
+                }
+            }
+        }
+
+        delivery.updated_at = Utc::now();
+        if !self.delivery_repo.update_claimed(&delivery).await? {
+            tracing::info!(delivery_id = %delivery.id, "Webhook delivery claimed again meanwhile, outcome dropped");
+        }
+
+        Ok(())
+    }
+
+    // Checked on each attempt, the picture url is minted again for whoever receives it.
+    async fn is_member(
+        &self,
+        webhook: &Webhook,
+        delivery: &WebhookDelivery,
+    ) -> Result<bool, Error> {
+        let Some(household_id) = delivery.household_id else {
+            return Ok(true);
+        };
+
+        Ok(self
+            .household_repo
+            .find_by_id(household_id)
+            .await?
+            .is_some_and(|household| household.role_of(webhook.user_id).is_some()))
+    }
+
+    // The queued payload with a picture url minted for this attempt, the one it was queued
+    // with expires long before the last retries.
+    async fn payload(&self, delivery: &WebhookDelivery) -> Result<String, Error> {
+        let mut payload: WebhookPayload = serde_json::from_str(&delivery.payload)
+            .map_err(|e| Error::JSONUnmarshall(e.to_string()))?;
+        payload.status.picture.url = self
+            .storage_repo
+            .signed_url(&payload.status.picture.name, self.signed_url_ttl)
+            .await?;
+
+        serde_json::to_string(&payload).map_err(|e| Error::Parse(e.to_string()))
+    }
+
+    async fn send(
+        &self,
+        delivery: &WebhookDelivery,
+        payload: &str,
+        url: &str,
+        secret: &str,
+    ) -> Result<u16, (Option<u16>, String)> {
+        let timestamp = Utc::now().timestamp();
+        let signature =
+            Self::sign(secret, timestamp, payload).map_err(|e| (None, e.to_string()))?;
+        let redacted_url = redact_url(url);
+
+        let response = self
+            .client
+            .post(url)
+            .timeout(REQUEST_TIMEOUT)
+            .header("Content-Type", "application/json")
+            .header(EVENT_HEADER, delivery.event.name())
+            .header(DELIVERY_HEADER, delivery.id.to_string())
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+            .body(payload.to_string())
+            .send()
+            .await
+            .map_err(|e| {
+                (
+                    None,
+                    format!(
+                        "Failed to send webhook to {}: {}",
+                        redacted_url,
+                        e.without_url()
+                    ),
+                )
+            })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err((
+                Some(status.as_u16()),
+                format!("{} responded with error status: {}", redacted_url, status),
+            ));
+        }
+
+        Ok(status.as_u16())
+    }
+
+    // Same scheme as the devices: HMAC-SHA256 of "{timestamp}.{body}" keyed by the secret.
+    fn sign(secret: &str, timestamp: i64, body: &str) -> Result<String, Error> {
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+            .map_err(|e| Error::Internal(e.to_string()))?;
+        mac.update(format!("{}.{}", timestamp, body).as_bytes());
+
+        Ok(hex::encode(mac.finalize().into_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{EventKind, Household, HouseholdMember, HouseholdRole};
+    use crate::repositories::InMemoryRepository;
+    use bson::Uuid;
+
+    #[actix_web::test]
+    async fn removed_member_gets_no_more_deliveries() {
+        let repo = Arc::new(InMemoryRepository::new());
+        let worker = WebhookWorker::new(
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            repo.clone(),
+            reqwest::Client::new(),
+            Duration::from_secs(60),
+            Duration::from_secs(1),
+            10,
+            &Arc::new(Heartbeats::default()),
+        );
+
+        let (owner, viewer) = (Uuid::new(), Uuid::new());
+        let household = Household::new("home".to_string(), owner);
+        HouseholdRepository::insert(repo.as_ref(), &household)
+            .await
+            .unwrap();
+        let member = HouseholdMember {
+            user_id: viewer,
+            role: HouseholdRole::Viewer,
+            joined_at: chrono::Local::now(),
+        };
+        repo.add_member(household.id, &member).await.unwrap();
+        let webhook = Webhook::new(
+            viewer,
+            "https://example.org/hook".to_string(),
+            vec![EventKind::CaptureCreated],
+            "secret".to_string(),
+        );
+        WebhookRepository::insert(repo.as_ref(), &webhook)
+            .await
+            .unwrap();
+        let delivery = WebhookDelivery::new(
+            webhook.id,
+            Some(household.id),
+            EventKind::CaptureCreated,
+            "{}".to_string(),
+        );
+        WebhookDeliveryRepository::insert(repo.as_ref(), &delivery)
+            .await
+            .unwrap();
+
+        repo.remove_member(household.id, viewer).await.unwrap();
+        let claimed = repo
+            .claim_due(Duration::from_secs(30))
+            .await
+            .unwrap()
+            .unwrap();
+        worker.deliver(claimed).await.unwrap();
+
+        let delivery = &repo.find_by_webhook_id(webhook.id, 10).await.unwrap()[0];
+        assert_eq!(delivery.state, DeliveryState::Failed);
+        assert!(delivery.response_status.is_none());
+    }
+}
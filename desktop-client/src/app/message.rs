@@ -19,7 +19,6 @@ pub enum Message {
     CheckboxToggled(bool),
 
     LoginWithGoogle,
-    AuthUrlReceived(Result<(String, String), String>),
     TokenInputChanged(String),
     SubmitToken,
     UserFetched(Result<Option<User>, String>),
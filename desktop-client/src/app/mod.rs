@@ -66,23 +66,11 @@ fn update(state: &mut AppState, message: Message) -> Task<Message> {
             }
         },
         Message::LoginWithGoogle => {
-            let api = state.api_service.clone();
-
-            Task::perform(
-                async move { api.get_auth_url().await.map_err(|e| e.to_string()) },
-                Message::AuthUrlReceived,
-            )
-        }
-        Message::AuthUrlReceived(result) => {
-            match result {
-                Ok((url, _auth_state)) => {
-                    println!("Go to URL: {}", url);
-                    webbrowser::open(&url).ok();
-                }
-                Err(e) => {
-                    println!("Auth Error: {}", e);
-                    state.error_message = Some(e);
-                }
+            let url = state.api_service.login_url();
+            println!("Go to URL: {}", url);
+            if let Err(e) = webbrowser::open(&url) {
+                println!("Auth Error: {}", e);
+                state.error_message = Some(e.to_string());
             }
             Task::none()
         }
@@ -97,17 +85,17 @@ fn update(state: &mut AppState, message: Message) -> Task<Message> {
             match parsed {
                 Ok(auth_data) => {
                     println!(
-                        "Token valid. Fetching profile for Google ID: {}",
+                        "Token valid. Fetching profile for: {}",
                         auth_data.user_info.id,
                     );
                     state.set_loading(true);
 
                     let api = state.api_service.clone();
-                    let google_id = auth_data.user_info.id;
+                    let access_token = auth_data.token.access_token;
 
                     Task::perform(
                         async move {
-                            api.get_user_by_google_id(google_id)
+                            api.get_current_user(access_token)
                                 .await
                                 .map_err(|e| e.to_string())
                         },
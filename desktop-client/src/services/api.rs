@@ -22,33 +22,15 @@ impl RustySecureApiImpl {
 }
 
 impl RustySecureApi for RustySecureApiImpl {
-    async fn get_auth_url(&self) -> Result<(String, String), crate::errors::Error> {
-        let response = self
-            .client
-            .get(format!(
-                "{}/api/auth/url?response_type=html",
-                self.api_base_url.clone()
-            ))
-            .send()
-            .await
-            .map_err(|e| Error::ApiError(e.to_string()))?;
-
-        if response.status() != 200 {
-            return Err(Error::ApiError("Unexpected status code".to_string()));
-        }
-
-        let (auth_url, state): (String, String) = response
-            .json()
-            .await
-            .map_err(|e| Error::ApiError(e.to_string()))?;
-
-        Ok((auth_url, state))
+    fn login_url(&self) -> String {
+        format!("{}/api/auth/login?response_type=html", self.api_base_url)
     }
 
-    async fn get_user_by_google_id(&self, id: String) -> Result<Option<User>, Error> {
+    async fn get_current_user(&self, access_token: String) -> Result<Option<User>, Error> {
         let response = self
             .client
-            .get(format!("{}/api/user/{}", self.api_base_url.clone(), id))
+            .get(format!("{}/api/user/me", self.api_base_url))
+            .bearer_auth(access_token)
             .send()
             .await
             .map_err(|e| Error::ApiError(e.to_string()))?;
@@ -5,6 +5,8 @@ use crate::errors::Error;
 use crate::models::User;
 
 pub trait RustySecureApi {
-    async fn get_auth_url(&self) -> Result<(String, String), Error>;
-    async fn get_user_by_google_id(&self, id: String) -> Result<Option<User>, Error>;
-}
\ No newline at end of file
+    // Opened in the browser, which must start the login itself to receive the state cookie.
+    fn login_url(&self) -> String;
+    // The user the access token was issued to.
+    async fn get_current_user(&self, access_token: String) -> Result<Option<User>, Error>;
+}
@@ -7,6 +7,7 @@ use esp_idf_hal::gpio::{Gpio4, Output, PinDriver};
 use esp_idf_hal::peripherals::Peripherals;
 use esp_idf_svc::eventloop::EspSystemEventLoop;
 use esp_idf_svc::nvs::EspNvsPartition;
+use esp_idf_svc::sntp::{EspSntp, SyncStatus};
 use esp_idf_svc::wifi::{ClientConfiguration, Configuration as WifiConfiguration, EspWifi};
 use log::{error, info};
 
@@ -96,6 +97,26 @@ fn main() {
         return;
     }
 
+    // Uploads are signed with a timestamp the API server checks, so the clock must be set.
+    let sntp = match EspSntp::new_default() {
+        Ok(sntp) => sntp,
+        Err(e) => {
+            error!("Failed to start SNTP: {:?}", e);
+            return;
+        }
+    };
+    for _ in 0..20 {
+        if sntp.get_sync_status() == SyncStatus::Completed {
+            break;
+        }
+        thread::sleep(Duration::from_millis(500));
+    }
+    if sntp.get_sync_status() != SyncStatus::Completed {
+        error!("SNTP not synced yet, uploads will be rejected until it is");
+    } else {
+        info!("SNTP synced.");
+    }
+
     let camera_controller: SharedCamera = match CameraController::new(
         pins.gpio32,
         pins.gpio0,
@@ -147,7 +168,13 @@ fn main() {
     let camera_clone = camera_controller.clone();
     let flash_clone = flash_led.clone();
 
-    let _http_server = match CameraHttpServer::new(camera_clone, flash_clone, config.api_url) {
+    let _http_server = match CameraHttpServer::new(
+        camera_clone,
+        flash_clone,
+        config.api_url,
+        config.device_id,
+        config.device_secret,
+    ) {
         Ok(server) => server,
         Err(e) => {
             log::error!("Failed to create HTTP server: {:?}", e);
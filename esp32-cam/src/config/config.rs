@@ -2,6 +2,8 @@ use crate::config::secrets;
 
 pub struct Config {
     pub api_url: &'static str,
+    pub device_id: &'static str,
+    pub device_secret: &'static str,
     pub ssid: &'static str,
     pub password: &'static str,
 }
@@ -10,6 +12,8 @@ impl Config {
     pub fn new() -> Self {
         Self {
             api_url: secrets::API_URL,
+            device_id: secrets::DEVICE_ID,
+            device_secret: secrets::DEVICE_SECRET,
             ssid: secrets::WIFI_SSID,
             password: secrets::WIFI_PASSWORD,
         }
@@ -1,30 +1,67 @@
 use anyhow::{anyhow, Context, Result};
 use embedded_svc::{http::client::Client as HttpClientTrait, io::Write};
 use esp_idf_svc::http::client::EspHttpConnection;
+use hmac::{Hmac, Mac};
 use log::info;
+use sha2::Sha256;
+use std::fmt::Write as _;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use super::StatusResponse;
 
+type HmacSha256 = Hmac<Sha256>;
+
 pub struct CameraHttpClient {
     client: HttpClientTrait<EspHttpConnection>,
     api_url: String,
+    device_id: String,
+    device_secret: String,
 }
 
 impl CameraHttpClient {
     pub fn new(
         wrapped_client: HttpClientTrait<EspHttpConnection>,
         api_url: String,
+        device_id: String,
+        device_secret: String,
     ) -> Result<Self> {
         Ok(Self {
             client: wrapped_client,
             api_url,
+            device_id,
+            device_secret,
         })
     }
 
+    // The API server expects an HMAC-SHA256 of `<timestamp>.<body>` keyed with the device secret.
+    fn sign(&self, timestamp: &str, body: &[u8]) -> Result<String> {
+        let mut mac = HmacSha256::new_from_slice(self.device_secret.as_bytes())
+            .map_err(|e| anyhow!("Client: Invalid device secret: {}", e))?;
+        mac.update(timestamp.as_bytes());
+        mac.update(b".");
+        mac.update(body);
+
+        let mut signature = String::new();
+        for byte in mac.finalize().into_bytes() {
+            write!(signature, "{:02x}", byte)?;
+        }
+        Ok(signature)
+    }
+
     pub fn post_picture(&mut self, image_data: &[u8]) -> Result<StatusResponse, anyhow::Error> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .context("Client: System time is before the epoch, is SNTP synced?")?
+            .as_secs()
+            .to_string();
+        let signature = self.sign(&timestamp, image_data)?;
+
         let headers = [
             ("accept", "application/json"),
             ("Content-Type", "image/jpeg"),
+            ("X-Device-Id", self.device_id.as_str()),
+            ("X-Timestamp", timestamp.as_str()),
+            ("X-Signature", signature.as_str()),
         ];
 
         info!(
@@ -27,6 +27,8 @@ impl<'a> CameraHttpServer<'a> {
         camera: SharedCamera<'static>,
         flash_led: SharedFlashPin<'static>,
         api_url: &str,
+        device_id: &str,
+        device_secret: &str,
     ) -> Result<Self> {
         let server_configuration = Configuration {
             stack_size: 10240,
@@ -36,6 +38,8 @@ impl<'a> CameraHttpServer<'a> {
         let mut server = EspHttpServer::new(&server_configuration)?;
 
         let api_url_owned = api_url.to_string();
+        let device_id_owned = device_id.to_string();
+        let device_secret_owned = device_secret.to_string();
 
         server.fn_handler::<anyhow::Error, _>("/capture", Method::Get, move |req| {
             info!("Received capture request");
@@ -84,7 +88,12 @@ impl<'a> CameraHttpServer<'a> {
                         let http_client = HttpClientTrait::wrap(connection);
 
                         let mut camera_client =
-                            CameraHttpClient::new(http_client, api_url_owned.clone())
+                            CameraHttpClient::new(
+                                http_client,
+                                api_url_owned.clone(),
+                                device_id_owned.clone(),
+                                device_secret_owned.clone(),
+                            )
                                 .context("Handler: Failed create CameraHttpClient")?;
 
                         info!("Calling post_picture...");
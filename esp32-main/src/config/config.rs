@@ -2,6 +2,10 @@ use crate::config::secrets;
 
 pub struct Config {
     pub cam_capture_url: &'static str,
+    // Base url of the API server, e.g. `http://192.168.1.10:8080`.
+    pub api_url: &'static str,
+    pub device_id: &'static str,
+    pub device_secret: &'static str,
     pub ssid: &'static str,
     pub password: &'static str,
 }
@@ -10,6 +14,9 @@ impl Config {
     pub fn new() -> Self {
         Self {
             cam_capture_url: secrets::CAM_CAPTURE_URL,
+            api_url: secrets::API_URL,
+            device_id: secrets::DEVICE_ID,
+            device_secret: secrets::DEVICE_SECRET,
             ssid: secrets::WIFI_SSID,
             password: secrets::WIFI_PASSWORD,
         }
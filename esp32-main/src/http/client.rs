@@ -1,10 +1,19 @@
 extern crate alloc;
 
 use crate::http::CamStatusResponse;
+use alloc::format;
+use core::fmt::Write as _;
 use embedded_nal_async::{Dns, TcpConnect};
+use heapless::String;
+use hmac::{Hmac, Mac};
 use log::{error, info};
+use reqwless::headers::ContentType;
+use reqwless::request::RequestBuilder;
 use reqwless::{client::HttpClient as ReqwlessHttpClient, request::Method, response::StatusCode};
 use serde_json_core::from_slice;
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
 
 #[derive(Debug, Clone)]
 pub enum ClientError {
@@ -13,19 +22,115 @@ pub enum ClientError {
     StatusError(StatusCode),
     BodyReadFailed,
     JsonParseFailed,
+    // The server answered without a usable `Date` header.
+    ServerTimeUnavailable,
+    SigningFailed,
 }
 
 pub struct HttpClient<'a, T: TcpConnect, D: Dns> {
     client: ReqwlessHttpClient<'a, T, D>,
     cam_capture_url: &'static str,
+    api_url: &'static str,
+    device_id: &'static str,
+    device_secret: &'static str,
 }
 
 impl<'a, T: TcpConnect, D: Dns> HttpClient<'a, T, D> {
-    pub fn new(tcp: &'a T, dns: &'a D, cam_capture_url: &'static str) -> Self {
+    pub fn new(
+        tcp: &'a T,
+        dns: &'a D,
+        cam_capture_url: &'static str,
+        api_url: &'static str,
+        device_id: &'static str,
+        device_secret: &'static str,
+    ) -> Self {
         Self {
             client: ReqwlessHttpClient::new(tcp, dns),
             cam_capture_url,
+            api_url,
+            device_id,
+            device_secret,
+        }
+    }
+
+    // Tells the API server where to post the decisions, our address may change with each
+    // DHCP lease.
+    pub async fn register_callback(&mut self, callback_url: &str) -> Result<(), ClientError> {
+        info!("Registering callback url {}", callback_url);
+        // There is no clock on the board, the signature is dated with the server's own.
+        let timestamp = self.server_time().await?;
+        let body = format!("{{\"callback_url\":\"{}\"}}", callback_url);
+        let mut timestamp_str = String::<20>::new();
+        write!(timestamp_str, "{}", timestamp).map_err(|_| ClientError::SigningFailed)?;
+        let signature = self.sign(&timestamp_str, body.as_bytes())?;
+
+        let url = format!("{}/device/callback", self.api_url.trim_end_matches('/'));
+        let headers = [
+            ("X-Device-Id", self.device_id),
+            ("X-Timestamp", timestamp_str.as_str()),
+            ("X-Signature", signature.as_str()),
+        ];
+        let request = self.client.request(Method::PUT, &url).await.map_err(|e| {
+            error!("Failed to create request: {:?}", e);
+            ClientError::RequestCreationFailed
+        })?;
+        let mut request = request
+            .body(body.as_bytes())
+            .content_type(ContentType::ApplicationJson)
+            .headers(&headers);
+
+        let mut rx_buf = [0u8; 2048];
+        let response = request.send(&mut rx_buf).await.map_err(|e| {
+            error!("Failed to send request: {:?}", e);
+            ClientError::SendFailed
+        })?;
+
+        if !response.status.is_successful() {
+            error!(
+                "Callback registration failed with status: {:?}",
+                response.status
+            );
+            return Err(ClientError::StatusError(response.status));
+        }
+
+        info!("Callback url registered");
+        Ok(())
+    }
+
+    async fn server_time(&mut self) -> Result<i64, ClientError> {
+        let url = format!("{}/healthz", self.api_url.trim_end_matches('/'));
+        let mut request = self.client.request(Method::GET, &url).await.map_err(|e| {
+            error!("Failed to create request: {:?}", e);
+            ClientError::RequestCreationFailed
+        })?;
+
+        let mut rx_buf = [0u8; 1024];
+        let response = request.send(&mut rx_buf).await.map_err(|e| {
+            error!("Failed to send request: {:?}", e);
+            ClientError::SendFailed
+        })?;
+
+        response
+            .headers()
+            .find(|(name, _)| name.eq_ignore_ascii_case("date"))
+            .and_then(|(_, value)| core::str::from_utf8(value).ok())
+            .and_then(parse_http_date)
+            .ok_or(ClientError::ServerTimeUnavailable)
+    }
+
+    // The API server expects an HMAC-SHA256 of `<timestamp>.<body>` keyed with the device secret.
+    fn sign(&self, timestamp: &str, body: &[u8]) -> Result<String<64>, ClientError> {
+        let mut mac = HmacSha256::new_from_slice(self.device_secret.as_bytes())
+            .map_err(|_| ClientError::SigningFailed)?;
+        mac.update(timestamp.as_bytes());
+        mac.update(b".");
+        mac.update(body);
+
+        let mut signature = String::new();
+        for byte in mac.finalize().into_bytes() {
+            write!(signature, "{:02x}", byte).map_err(|_| ClientError::SigningFailed)?;
         }
+        Ok(signature)
     }
 
     pub async fn request_camera_capture(&mut self) -> Result<CamStatusResponse, ClientError> {
@@ -37,7 +142,8 @@ impl<'a, T: TcpConnect, D: Dns> HttpClient<'a, T, D> {
             ClientError::RequestCreationFailed
         })?;
 
-        let mut rx_buf = [0u8; 1024];
+        // Picture urls are signed and can be several hundred bytes long.
+        let mut rx_buf = [0u8; 4096];
         info!("Sending request...");
         let response = request.send(&mut rx_buf).await.map_err(|e| {
             error!("Failed to send request: {:?}", e);
@@ -84,3 +190,35 @@ impl<'a, T: TcpConnect, D: Dns> HttpClient<'a, T, D> {
         }
     }
 }
+
+// Unix time of an HTTP date, e.g. `Sun, 18 Oct 2026 11:38:35 GMT`.
+fn parse_http_date(date: &str) -> Option<i64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let mut parts = date.split_whitespace().skip(1);
+    let day: i64 = parts.next()?.parse().ok()?;
+    let month_name = parts.next()?;
+    let month = MONTHS.iter().position(|m| *m == month_name)? as i64 + 1;
+    let year: i64 = parts.next()?.parse().ok()?;
+    let mut time = parts
+        .next()?
+        .split(':')
+        .map(|part| part.parse::<i64>().ok());
+    let (hours, minutes, seconds) = (time.next()??, time.next()??, time.next()??);
+
+    // Days since the epoch of a proleptic Gregorian date, years starting in March.
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    Some(days * 86_400 + hours * 3_600 + minutes * 60 + seconds)
+}
@@ -15,7 +15,6 @@ pub enum HttpMessage {
 pub struct CamPictureResponse {
     pub id: String<36>,
     pub name: String<64>,
-    pub url: String<128>,
     pub created_at: String<64>,
     #[serde(default)]
     pub updated_at: Option<String<64>>,
@@ -1,9 +1,12 @@
+extern crate alloc;
+
+use alloc::format;
 use embassy_net::dns::DnsSocket;
 use embassy_net::tcp::client::{TcpClient, TcpClientState};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::channel::{Receiver, Sender};
 use embassy_time::{Duration, Timer};
-use log::{error, info};
+use log::{error, info, warn};
 use static_cell::StaticCell;
 
 use crate::http::client::{ClientError, HttpClient};
@@ -11,6 +14,7 @@ use crate::http::CamStatusResponse;
 use crate::http::HttpMessage;
 
 static TCP_CLIENT_STATE: StaticCell<TcpClientState<2, 1024, 1024>> = StaticCell::new();
+const CALLBACK_REGISTRATION_ATTEMPTS: u32 = 5;
 
 #[embassy_executor::task(pool_size = 8)]
 pub async fn http_camera_task(
@@ -35,10 +39,34 @@ pub async fn http_camera_task(
     let state: &mut TcpClientState<2, 1024, 1024> = TCP_CLIENT_STATE.init(TcpClientState::new());
     let tcp_client = TcpClient::new(*stack, state);
     let dns = DnsSocket::new(*stack);
-    let mut client = HttpClient::new(&tcp_client, &dns, config.cam_capture_url);
+    let mut client = HttpClient::new(
+        &tcp_client,
+        &dns,
+        config.cam_capture_url,
+        config.api_url,
+        config.device_id,
+        config.device_secret,
+    );
 
     info!("HTTP camera task initialized and network is up");
 
+    // The server only knows where to post the decisions once we told it.
+    if let Some(network) = stack.config_v4() {
+        let callback_url = format!("http://{}/authorised", network.address.address());
+        for attempt in 1..=CALLBACK_REGISTRATION_ATTEMPTS {
+            match client.register_callback(&callback_url).await {
+                Ok(()) => break,
+                Err(e) => {
+                    warn!(
+                        "Callback registration attempt {}/{} failed: {:?}",
+                        attempt, CALLBACK_REGISTRATION_ATTEMPTS, e
+                    );
+                    Timer::after(Duration::from_secs(5)).await;
+                }
+            }
+        }
+    }
+
     loop {
         info!("Waiting for request message");
         match receiver.receive().await {
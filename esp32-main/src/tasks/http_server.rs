@@ -15,6 +15,18 @@ use crate::display::DisplayMessage;
 use crate::http::AuthUpdatePayload;
 
 const HTTP_PORT: u16 = 80;
+// Sent by the server with each decision, logged so both sides can be matched up.
+const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+// Value of the header, `head` being the request line and headers.
+fn header_value<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then_some(value.trim())
+    })
+}
 
 #[embassy_executor::task]
 pub async fn http_server_task(
@@ -114,13 +126,18 @@ pub async fn http_server_task(
             let first_line = request_str.lines().next().unwrap_or("");
 
             if first_line.starts_with("POST /authorised") {
+                let request_id = header_value(&request_str[..body_start_index], REQUEST_ID_HEADER)
+                    .unwrap_or("-");
+                info!("HTTP Server: /authorised called, request_id={}", request_id);
+
                 if !body_str.is_empty() {
                     match from_slice::<AuthUpdatePayload>(body_str.as_bytes()) {
                         Ok((payload, _consumed)) => {
                             info!(
-                                "HTTP Server: Parsed AuthUpdatePayload: ID={}, Authorised={}",
+                                "HTTP Server: Parsed AuthUpdatePayload: ID={}, Authorised={}, request_id={}",
                                 payload.id.as_str(),
-                                payload.authorised
+                                payload.authorised,
+                                request_id
                             );
                             display_sender
                                 .send(DisplayMessage::AuthStatus(payload.authorised))
@@ -134,7 +151,10 @@ pub async fn http_server_task(
                             };
                         }
                         Err(e) => {
-                            error!("HTTP Server: JSON parse error: {:?}", e);
+                            error!(
+                                "HTTP Server: JSON parse error: {:?}, request_id={}",
+                                e, request_id
+                            );
                             if body_str.len() > 100 {
                                 error!(
                                     "HTTP Server: Failed to parse body: {}...",
@@ -149,7 +169,8 @@ pub async fn http_server_task(
                     }
                 } else {
                     warn!(
-                        "HTTP Server: /authorised endpoint called (POST) but no JSON body found."
+                        "HTTP Server: /authorised endpoint called (POST) but no JSON body found, request_id={}",
+                        request_id
                     );
                     response_status_code = "400 Bad Request";
                     response_body_content = "Missing JSON body for /authorised";